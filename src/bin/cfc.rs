@@ -1,10 +1,11 @@
 // The compiler for CFlat code.
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use derive_more::Display;
 use lowering::commons::skip_validation;
 use lowering::front_end::*;
-use lowering::middle_end::lir;
+use lowering::middle_end::translation_validation::Bounds;
+use lowering::middle_end::{dot, lir, passes};
 use std::str::FromStr;
 
 // Input/output file types
@@ -13,6 +14,7 @@ enum FileType {
     CFlat,
     Ast,
     Lir,
+    Dot,
 }
 
 // File names with associated file types.  This is used for determining input
@@ -33,21 +35,51 @@ impl FromStr for File {
         let name = String::from(s);
         let typ = s.rsplit_once('.').and_then(|(_, extension)| match extension {
             "lir" => Some(Lir),
+            "dot" => Some(Dot),
             "json" => Some(Ast),
             "cf" | "cb" => Some(CFlat),
             _ => None,
-        }).ok_or_else(|| format!("Expected a file name with one of the following extensions: json, lir, cf, cb. Got {}", s))?;
+        }).ok_or_else(|| format!("Expected a file name with one of the following extensions: json, lir, dot, cf, cb. Got {}", s))?;
 
         Ok(File { typ, name })
     }
 }
 
+// The graph to emit when the output file is a .dot file
+#[derive(Clone, Copy, ValueEnum)]
+enum GraphKind {
+    // control-flow graphs of all functions
+    Cfg,
+    // the program's call graph
+    CallGraph,
+    // dominator trees of all functions
+    DomTree,
+}
+
 // Command-line arguments
 #[derive(Parser)]
 #[command(version, about)]
 struct Args {
     input_file: File,
     output_file: File,
+    // which graph to emit for .dot output files
+    #[arg(long, value_enum, default_value = "cfg")]
+    graph: GraphKind,
+    // canonically rename the temporaries and basic blocks of the lowered program
+    #[arg(long)]
+    canonicalize: bool,
+    // a pass to run on the lowered program; can be given several times, and the
+    // passes run in order (before --canonicalize)
+    #[arg(long = "pass")]
+    passes: Vec<String>,
+    // check that each pass preserves the semantics of every function, using
+    // bounded translation validation
+    #[arg(long)]
+    verify_passes: bool,
+    // write the source map of the lowered program, which `liri --coverage`
+    // uses, to this file; requires a CFlat input and no passes
+    #[arg(long)]
+    source_map: Option<String>,
 }
 
 pub fn main() {
@@ -61,23 +93,74 @@ pub fn main() {
     )
     .expect("The input file does not contain valid utf-8 text");
 
+    if args.source_map.is_some() {
+        if args.input_file.typ != FileType::CFlat {
+            panic!("--source-map needs a CFlat input file, which has the statements' positions");
+        }
+        if args.canonicalize || !args.passes.is_empty() {
+            panic!("--source-map can't be combined with passes, which change the basic blocks");
+        }
+    }
+
     let cf_program: ast::Program;
     let program: lir::Program = match args.input_file.typ {
         FileType::Lir => panic!("The input file must be a CFlat program, not an LIR program."),
+        FileType::Dot => panic!("The input file must be a CFlat program, not a DOT graph."),
         FileType::Ast => {
 	    cf_program = serde_json::from_str(&input_string).unwrap_or_else(|e| panic!("AST JSON file is not valid: {e}"));
             lower(&skip_validation(cf_program.clone()))
 	}
-        FileType::CFlat => {
-            cf_program =
-                parse(&input_string).unwrap_or_else(|e| panic!("Syntax error: {e}"));
-            lower(&skip_validation(cf_program.clone()))
+        FileType::CFlat => match &args.source_map {
+            Some(map_file) => {
+                let positions;
+                (cf_program, positions) = parse_with_positions(&input_string)
+                    .unwrap_or_else(|e| panic!("Syntax error: {e}"));
+                let (program, mut map) =
+                    lower_with_source_map(&skip_validation(cf_program.clone()), &positions);
+                map.source_file = input_file.to_string();
+                std::fs::write(map_file, map.to_json())
+                    .unwrap_or_else(|_| panic!("Failed to write the source map to {map_file}"));
+                program
+            }
+            None => {
+                cf_program = parse(&input_string).unwrap_or_else(|e| panic!("Syntax error: {e}"));
+                lower(&skip_validation(cf_program.clone()))
+            }
+        },
+    };
+
+    let mut manager = passes::PassManager::new();
+    for name in &args.passes {
+        manager.add_by_name(name).unwrap_or_else(|e| panic!("{e}"));
+    }
+    if args.canonicalize {
+        manager.add("canonicalize", passes::canonicalize);
+    }
+    if args.verify_passes {
+        manager.verify(Bounds::default());
+    }
+    let program = match manager.run(program) {
+        Ok((program, warnings)) => {
+            for warning in warnings {
+                eprintln!("warning: {warning}");
+            }
+            program
+        }
+        Err(err) => {
+            eprintln!("error: {err}");
+            std::process::exit(1);
         }
     };
 
     let output = match args.output_file.typ {
         FileType::Lir => program.to_string().into_bytes(),
         FileType::Ast => serde_json::to_string_pretty(&cf_program).unwrap().into_bytes(),
+        FileType::Dot => match args.graph {
+            GraphKind::Cfg => dot::program_cfgs_to_dot(&program),
+            GraphKind::CallGraph => dot::call_graph_to_dot(&program),
+            GraphKind::DomTree => dot::program_dominator_trees_to_dot(&program),
+        }
+        .into_bytes(),
         FileType::CFlat => panic!("Cannot output a .cb file"),
     };
 
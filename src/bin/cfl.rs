@@ -0,0 +1,50 @@
+// The LIR linker
+
+use clap::Parser;
+use lowering::middle_end::lir;
+
+// Command-line arguments
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    // the LIR modules to link
+    #[arg(required = true)]
+    modules: Vec<String>,
+    // the file to write the linked program to
+    #[arg(short, long)]
+    output: String,
+}
+
+pub fn main() {
+    let args = Args::parse();
+
+    let modules = args
+        .modules
+        .iter()
+        .map(|input_file| {
+            let input_string = String::from_utf8(
+                std::fs::read(input_file)
+                    .unwrap_or_else(|_| panic!("Could not read the input file {}", input_file)),
+            )
+            .expect("The input file does not contain valid utf-8 text");
+
+            input_string
+                .parse::<lir::Program>()
+                .unwrap_or_else(|e| panic!("Failed to parse LIR code in {input_file}: {e:?}"))
+        })
+        .collect::<Vec<_>>();
+
+    let program = lir::link(&modules).unwrap_or_else(|e| {
+        for error in e.errors {
+            eprintln!("{error}");
+        }
+        std::process::exit(1)
+    });
+
+    std::fs::write(&args.output, program.to_string()).unwrap_or_else(|_| {
+        panic!(
+            "Failed to write the linked program to the output file: {}",
+            args.output
+        )
+    });
+}
@@ -0,0 +1,166 @@
+// The test-case reducer for CFlat and LIR programs
+
+use clap::{ArgGroup, Parser};
+use lowering::commons::skip_validation;
+use lowering::front_end::{ast, lower};
+use lowering::interpreter::{Interpreter, Limits, RuntimeError, RuntimeErrorKind, Sink};
+use lowering::middle_end::{lir, passes};
+use lowering::reduce::{reduce, TestCase};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+// Command-line arguments
+#[derive(Parser)]
+#[command(version, about)]
+#[command(group(ArgGroup::new("predicate").required(true).args(["command", "validate_fails", "differs_after"])))]
+struct Args {
+    // the .cf or .lir program to reduce
+    input_file: String,
+    // the file to write the reduced program to (by default, the input file
+    // name with .reduced inserted before the extension)
+    #[arg(short, long)]
+    output: Option<String>,
+    // a program is interesting if this command exits successfully when given
+    // the name of a file containing the program as its last argument
+    #[arg(long)]
+    command: Option<String>,
+    // a program is interesting if validating it fails with an error message
+    // containing this text
+    #[arg(long)]
+    validate_fails: Option<String>,
+    // a program is interesting if it is valid and interpreting it gives a
+    // different result before and after running this pass
+    #[arg(long)]
+    differs_after: Option<String>,
+    // the number of instructions and terminals after which an interpreted
+    // program is considered to diverge, which makes it uninteresting
+    #[arg(long, default_value_t = 10_000_000)]
+    max_steps: u64,
+}
+
+pub fn main() {
+    let args = Args::parse();
+    let input_file = args.input_file.as_str();
+
+    let input_string = String::from_utf8(
+        std::fs::read(input_file)
+            .unwrap_or_else(|_| panic!("Could not read the input file {}", input_file)),
+    )
+    .expect("The input file does not contain valid utf-8 text");
+
+    let (stem, extension) = input_file.rsplit_once('.').unwrap_or_else(|| {
+        panic!("Expected a file name ending in .cf, .cb or .lir. Got {input_file}")
+    });
+    let output_file = args
+        .output
+        .clone()
+        .unwrap_or_else(|| format!("{stem}.reduced.{extension}"));
+
+    let output = match extension {
+        "lir" => {
+            let program = lir::Program::from_text(&input_string).expect("Failed to parse LIR code");
+            run(program, &args, extension, |p| p.clone())
+        }
+        "cf" | "cb" => {
+            let program =
+                ast::Program::from_text(&input_string).expect("Failed to parse CFlat code");
+            run(program, &args, extension, |p| {
+                lower(&skip_validation(p.clone()))
+            })
+        }
+        _ => panic!("Expected a file name ending in .cf, .cb or .lir. Got {input_file}"),
+    };
+
+    std::fs::write(&output_file, output).unwrap_or_else(|_| {
+        panic!(
+            "Failed to write the reduced program to the output file: {}",
+            output_file
+        )
+    });
+}
+
+// reduces the program and returns the reduced program's text. to_lir is used
+// by the built-in predicates, which work on LIR.
+fn run<T: TestCase>(
+    program: T,
+    args: &Args,
+    extension: &str,
+    to_lir: impl Fn(&T) -> lir::Program,
+) -> String {
+    let pass = args.differs_after.as_ref().map(|name| {
+        passes::pass_by_name(name).unwrap_or_else(|| {
+            let names = passes::PASSES.iter().map(|(name, _)| *name);
+            panic!(
+                "Unknown pass {name}; the passes are: {}",
+                names.collect::<Vec<_>>().join(", ")
+            )
+        })
+    });
+    let scratch_file =
+        std::env::temp_dir().join(format!("cfreduce-{}.{extension}", std::process::id()));
+
+    let mut interesting = |program: &T| -> bool {
+        if let Some(command) = &args.command {
+            std::fs::write(&scratch_file, program.to_text())
+                .expect("Failed to write the candidate program to a temporary file");
+            let mut words = command.split_whitespace();
+            return std::process::Command::new(words.next().expect("The command is empty"))
+                .args(words)
+                .arg(&scratch_file)
+                .stdout(std::process::Stdio::null())
+                .stderr(std::process::Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success());
+        }
+
+        // a candidate that makes the compiler panic isn't interesting.
+        catch_unwind(AssertUnwindSafe(|| {
+            let lir_program = to_lir(program);
+            let result = lir::validate(&lir_program);
+            if let Some(message) = &args.validate_fails {
+                result.is_err_and(|e| e.errors.iter().any(|e| e.contains(message.as_str())))
+            } else if let (Some(pass), Ok(())) = (pass, result) {
+                let before = interpret_with_limit(lir_program.clone(), args.max_steps);
+                let after = interpret_with_limit(pass(lir_program), args.max_steps);
+                before.is_some() && after.is_some() && before != after
+            } else {
+                false
+            }
+        }))
+        .unwrap_or(false)
+    };
+
+    // keep panic messages from candidates out of the output.
+    std::panic::set_hook(Box::new(|_| {}));
+    if !interesting(&program) {
+        eprintln!("The input program isn't interesting");
+        std::process::exit(1);
+    }
+    let size = program.to_text().lines().count();
+    let reduced = reduce(program, &mut interesting);
+    let _ = std::panic::take_hook();
+    let _ = std::fs::remove_file(&scratch_file);
+
+    let text = reduced.to_text();
+    eprintln!("Reduced {size} lines to {} lines", text.lines().count());
+    text
+}
+
+// interprets the program, giving up (and returning None) if it doesn't finish
+// within max_steps steps.
+fn interpret_with_limit(
+    program: lir::Program,
+    max_steps: u64,
+) -> Option<Result<i64, RuntimeError>> {
+    let limits = Limits {
+        max_steps: Some(max_steps),
+        ..Limits::default()
+    };
+    let report = Interpreter::new()
+        .limits(limits)
+        .sink(Sink::Capture)
+        .run(program);
+    match report.result {
+        Err(e) if e.kind == RuntimeErrorKind::StepLimit => None,
+        result => Some(result),
+    }
+}
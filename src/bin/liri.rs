@@ -1,7 +1,11 @@
 // The LIR interpreter
 
 use clap::Parser;
-use lowering::interpreter::interpret;
+use lowering::front_end::{Coverage, CoverageCounts, SourceMap};
+use lowering::interpreter::{
+    debugger, ArithmeticMode, ExternRegistry, HeapFormat, Interpreter, Limits, Sink, TraceFormat,
+    Tracer,
+};
 use lowering::middle_end::lir;
 
 // Command-line arguments
@@ -9,10 +13,69 @@ use lowering::middle_end::lir;
 #[command(version, about)]
 struct Args {
     program: String,
+    // the seed for the `rand` extern
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+    // integer semantics: wrapping (32-bit), trapping (32-bit, overflow is an
+    // error) or unbounded (64-bit)
+    #[arg(long, default_value_t = ArithmeticMode::Unbounded)]
+    arithmetic: ArithmeticMode,
+    // stop after executing this many instructions and terminals
+    #[arg(long)]
+    max_steps: Option<u64>,
+    // stop when more than this many calls are active, counting main
+    #[arg(long)]
+    max_depth: Option<usize>,
+    // stop when more than this many heap cells are allocated at once
+    #[arg(long)]
+    max_heap: Option<u64>,
+    // collect garbage when an allocation would take the heap above this many
+    // cells
+    #[arg(long)]
+    gc: Option<u64>,
+    // run the program under the interactive debugger
+    #[arg(long)]
+    debug: bool,
+    // log every executed instruction and terminal to this file
+    #[arg(long)]
+    trace: Option<String>,
+    // the trace's format: text or json (one object per line)
+    #[arg(long, default_value_t = TraceFormat::Text)]
+    trace_format: TraceFormat,
+    // only trace this function; can be given multiple times
+    #[arg(long)]
+    trace_function: Vec<String>,
+    // write a flat and a call-tree profile to this file
+    #[arg(long)]
+    profile: Option<String>,
+    // write the profile's counts as JSON to this file
+    #[arg(long)]
+    profile_json: Option<String>,
+    // write the heap objects reachable when the program exits to this file
+    #[arg(long)]
+    dump_heap: Option<String>,
+    // the heap dump's format: json or dot (Graphviz)
+    #[arg(long, default_value_t = HeapFormat::Json)]
+    heap_format: HeapFormat,
+    // report the coverage of the CFlat source, using this source map from
+    // `cfc --source-map`
+    #[arg(long)]
+    coverage: Option<String>,
+    // write the coverage in lcov's format to this file
+    #[arg(long, requires = "coverage")]
+    lcov: Option<String>,
+    // write the CFlat source annotated with the coverage to this file
+    #[arg(long, requires = "coverage")]
+    annotate: Option<String>,
+    // aggregate the coverage over runs: add the counts in this file (if it
+    // exists) to this run's and write them back to it
+    #[arg(long, requires = "coverage")]
+    coverage_counts: Option<String>,
 }
 
 pub fn main() {
-    let input_file = Args::parse().program;
+    let args = Args::parse();
+    let input_file = args.program;
 
     let input_string = String::from_utf8(
         std::fs::read(&input_file)
@@ -22,5 +85,107 @@ pub fn main() {
 
     let program: lir::Program = input_string.parse().expect("Failed to parse LIR code");
 
-    println!("main returned {}", interpret(program).unwrap());
+    let mut interpreter = Interpreter::new()
+        .externs(ExternRegistry::stdlib(args.seed))
+        .arithmetic(args.arithmetic)
+        .limits(Limits {
+            max_steps: args.max_steps,
+            max_depth: args.max_depth,
+            max_heap_cells: args.max_heap,
+        })
+        .sink(Sink::Stdout);
+
+    if let Some(trace_file) = args.trace {
+        let file = std::fs::File::create(&trace_file)
+            .unwrap_or_else(|_| panic!("Could not create the trace file {trace_file}"));
+        let mut tracer = Tracer::new(std::io::BufWriter::new(file), args.trace_format);
+        if !args.trace_function.is_empty() {
+            tracer = tracer.only(args.trace_function);
+        }
+        interpreter = interpreter.trace(tracer);
+    }
+
+    if let Some(threshold) = args.gc {
+        interpreter = interpreter.gc(threshold);
+    }
+
+    let mut coverage = args.coverage.as_ref().map(|map_file| {
+        let json = std::fs::read_to_string(map_file)
+            .unwrap_or_else(|_| panic!("Could not read the source map {map_file}"));
+        let map = SourceMap::from_json(&json).unwrap_or_else(|e| panic!("{e}"));
+        let mut coverage = Coverage::new(&program, map).unwrap_or_else(|e| panic!("{e}"));
+        if let Some(counts_file) = &args.coverage_counts {
+            if let Ok(json) = std::fs::read_to_string(counts_file) {
+                let counts = CoverageCounts::from_json(&json).unwrap_or_else(|e| panic!("{e}"));
+                coverage
+                    .add_counts(&counts)
+                    .unwrap_or_else(|e| panic!("{e}"));
+            }
+        }
+        coverage
+    });
+
+    if args.profile.is_some() || args.profile_json.is_some() || coverage.is_some() {
+        interpreter = interpreter.profile();
+    }
+
+    if args.debug {
+        let mut execution = interpreter.start(program).unwrap();
+        debugger::debug(&mut execution, std::io::stdin().lock(), std::io::stdout())
+            .expect("Could not talk to the terminal");
+        return;
+    }
+
+    let report = match &args.dump_heap {
+        Some(file) => {
+            let mut execution = interpreter.start(program).unwrap();
+            while execution.step().is_none() {}
+            std::fs::write(file, execution.heap_snapshot().render(args.heap_format))
+                .unwrap_or_else(|_| panic!("Could not write the heap to {file}"));
+            execution.finish()
+        }
+        None => interpreter.run(program),
+    };
+    if let Some(profile) = &report.profile {
+        let write = |file: &String, contents: String| {
+            std::fs::write(file, contents)
+                .unwrap_or_else(|_| panic!("Could not write the profile to {file}"))
+        };
+        if let Some(file) = &args.profile {
+            write(
+                file,
+                format!("{}\n{}", profile.flat_report(), profile.call_tree_report()),
+            );
+        }
+        if let Some(file) = &args.profile_json {
+            write(file, profile.to_json());
+        }
+    }
+    if let (Some(coverage), Some(profile)) = (&mut coverage, &report.profile) {
+        coverage.add(profile);
+        eprint!("{}", coverage.summary());
+        let write = |file: &String, contents: String| {
+            std::fs::write(file, contents)
+                .unwrap_or_else(|_| panic!("Could not write the coverage to {file}"))
+        };
+        if let Some(file) = &args.lcov {
+            write(file, coverage.to_lcov(coverage.source_file()));
+        }
+        if let Some(file) = &args.annotate {
+            let source_file = coverage.source_file();
+            let source = std::fs::read_to_string(source_file)
+                .unwrap_or_else(|_| panic!("Could not read the source file {source_file}"));
+            write(file, coverage.annotate(&source));
+        }
+        if let Some(file) = &args.coverage_counts {
+            write(file, coverage.counts().to_json());
+        }
+    }
+    if let Some(stats) = &report.gc {
+        eprintln!("gc: {stats}");
+    }
+    for leak in &report.leaks {
+        eprintln!("leak: {leak}");
+    }
+    println!("main returned {}", report.result.unwrap());
 }
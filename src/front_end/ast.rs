@@ -2,6 +2,7 @@
 
 use serde::{Deserialize, Serialize};
 
+pub mod arbitrary_impl;
 pub mod associated_impl;
 pub mod display_impl;
 pub mod fromstr_impl;
@@ -78,6 +79,8 @@ pub enum Stmt {
         callee: Lval,
         args: Vec<Exp>,
     },
+    // frees the object(s) the pointer points to
+    Delete(Exp),
     If {
         guard: Exp,
         tt: Vec<Stmt>,
@@ -0,0 +1,763 @@
+// generation of random valid cflat programs (e.g., for differential testing of
+// the compiler against an interpreter). every generated program is well-typed,
+// terminates, and can't have runtime errors:
+//
+// - every loop has the form `i = 0; while i < n { i = i + 1; ... }`, where the
+//   counter i isn't assigned anywhere else, and functions only call functions
+//   that are defined before them (directly or through function pointers), so
+//   there is no recursion.
+// - pointer variables are assigned a `new` allocation before they are used and
+//   are never assigned nil, so they can be dereferenced. pointers read from the
+//   heap (e.g., struct fields) may be nil, so they are only compared or stored.
+// - only objects allocated for a `delete` are deleted, right after they are
+//   allocated, through a local that nothing else refers to, so deleted objects
+//   aren't used again.
+// - arrays have ARRAY_LEN elements and are only indexed by constants.
+// - division is only by positive constants, and every value stored in a
+//   variable, in the heap, passed as an argument or returned is reduced to less
+//   than VALUE_BOUND in absolute value, which keeps all the intermediate values
+//   within the range of an i32.
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use super::*;
+use crate::middle_end::lir::LirType;
+
+// SECTION: interface
+
+impl<'a> Arbitrary<'a> for Program {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Generator::new(u).program()
+    }
+}
+
+// SECTION: generator
+
+// the size limits of the generated programs.
+const MAX_STRUCTS: usize = 2;
+const MAX_FIELDS: usize = 3;
+const MAX_GLOBALS: usize = 3;
+const MAX_FUNCTIONS: usize = 3;
+const MAX_PARAMS: usize = 3;
+const MAX_LOCALS: usize = 4;
+const MAX_STMTS: usize = 4;
+const MAX_NESTING: usize = 2;
+const MAX_EXP_DEPTH: usize = 3;
+const MAX_ITERATIONS: i32 = 5;
+const MAX_CONST: i32 = 10;
+
+const ARRAY_LEN: i32 = 4;
+const VALUE_BOUND: i64 = 100;
+// the largest bound of a product; larger products are replaced with sums.
+const PRODUCT_BOUND: i64 = 100_000_000;
+
+struct Generator<'a, 'u> {
+    u: &'u mut Unstructured<'a>,
+    typedefs: Vec<Typedef>,
+    globals: Vec<Decl>,
+    externs: Vec<Decl>,
+    // the functions generated so far, which the current function may call.
+    functions: Vec<Function>,
+    // the variables in scope in the current function (including globals).
+    vars: Vec<Decl>,
+    // the counters of the enclosing loops of the current function.
+    counters: Vec<String>,
+    // the number of counters the current function needs.
+    num_counters: usize,
+    // the types of the locals the current function deletes through.
+    deleted: Vec<Type>,
+    rettyp: Option<Type>,
+}
+
+impl<'a, 'u> Generator<'a, 'u> {
+    fn new(u: &'u mut Unstructured<'a>) -> Self {
+        Generator {
+            u,
+            typedefs: vec![],
+            globals: vec![],
+            externs: vec![],
+            functions: vec![],
+            vars: vec![],
+            counters: vec![],
+            num_counters: 0,
+            deleted: vec![],
+            rettyp: None,
+        }
+    }
+
+    fn program(mut self) -> Result<Program> {
+        // structs are generated in two steps because fields may point to any
+        // struct.
+        let num_structs = self.u.int_in_range(0..=MAX_STRUCTS)?;
+        let names = (0..num_structs)
+            .map(|i| format!("st{i}"))
+            .collect::<Vec<_>>();
+        for name in &names {
+            let mut fields = vec![];
+            for j in 0..self.u.int_in_range(1..=MAX_FIELDS)? {
+                let typ = match self.u.int_in_range(0..=2)? {
+                    0 => ptr_ty(int_ty()),
+                    1 => ptr_ty(struct_ty(struct_id(self.u.choose(&names)?))),
+                    _ => int_ty(),
+                };
+                fields.push(Decl {
+                    name: format!("x{j}"),
+                    typ,
+                });
+            }
+            self.typedefs.push(Typedef {
+                name: name.clone(),
+                fields,
+            });
+        }
+
+        for i in 0..self.u.int_in_range(0..=MAX_GLOBALS)? {
+            let typ = self.var_type(false)?;
+            self.globals.push(Decl {
+                name: format!("g{i}"),
+                typ,
+            });
+        }
+
+        if self.u.arbitrary()? {
+            self.externs.push(Decl {
+                name: "print".to_string(),
+                typ: func_ty(None, vec![int_ty()]),
+            });
+        }
+
+        for i in 0..self.u.int_in_range(0..=MAX_FUNCTIONS)? {
+            let mut params = vec![];
+            for j in 0..self.u.int_in_range(0..=MAX_PARAMS)? {
+                let typ = self.var_type(true)?;
+                params.push(Decl {
+                    name: format!("v{j}"),
+                    typ,
+                });
+            }
+            let rettyp = self.u.arbitrary::<bool>()?.then(int_ty);
+            let function = self.function(format!("f{i}"), params, rettyp)?;
+            self.functions.push(function);
+        }
+        let main = self.function("main".to_string(), vec![], Some(int_ty()))?;
+        self.functions.push(main);
+
+        Ok(Program {
+            globals: self.globals,
+            typedefs: self.typedefs,
+            externs: self.externs,
+            functions: self.functions,
+        })
+    }
+
+    fn function(
+        &mut self,
+        name: String,
+        params: Vec<Decl>,
+        rettyp: Option<Type>,
+    ) -> Result<Function> {
+        let mut decls = vec![];
+        for j in params.len()..params.len() + self.u.int_in_range(0..=MAX_LOCALS)? {
+            let typ = self.var_type(true)?;
+            let init = if typ.is_int() && self.u.arbitrary()? {
+                Some(Exp::Num(self.u.int_in_range(0..=MAX_CONST)?))
+            } else {
+                None
+            };
+            decls.push((
+                Decl {
+                    name: format!("v{j}"),
+                    typ,
+                },
+                init,
+            ));
+        }
+
+        // pointer variables are initialized first (including the globals, in
+        // main, which runs before any other function).
+        let mut stmts = vec![];
+        let globals = match name.as_str() {
+            "main" => self.globals.clone(),
+            _ => vec![],
+        };
+        for decl in globals.iter().chain(decls.iter().map(|(decl, _)| decl)) {
+            if let Some(rhs) = self.init(&decl.typ)? {
+                stmts.push(Stmt::Assign {
+                    lhs: Lval::Id(decl.name.clone()),
+                    rhs,
+                });
+            }
+        }
+
+        self.vars = self.globals.clone();
+        self.vars.extend(params.iter().cloned());
+        self.vars.extend(decls.iter().map(|(decl, _)| decl.clone()));
+        self.counters = vec![];
+        self.num_counters = 0;
+        self.deleted = vec![];
+        self.rettyp = rettyp.clone();
+
+        stmts.extend(self.stmts(MAX_NESTING)?);
+        stmts.push(self.ret()?);
+
+        decls.extend((0..self.num_counters).map(|i| {
+            let decl = Decl {
+                name: format!("i{i}"),
+                typ: int_ty(),
+            };
+            (decl, None)
+        }));
+        decls.extend(self.deleted.iter().enumerate().map(|(i, typ)| {
+            let decl = Decl {
+                name: format!("d{i}"),
+                typ: typ.clone(),
+            };
+            (decl, None)
+        }));
+
+        Ok(Function {
+            name,
+            params,
+            rettyp,
+            body: Body { decls, stmts },
+        })
+    }
+
+    // SECTION: statements
+
+    fn stmts(&mut self, nesting: usize) -> Result<Vec<Stmt>> {
+        let mut stmts = vec![];
+        for _ in 0..self.u.int_in_range(1..=MAX_STMTS)? {
+            self.stmt(nesting, &mut stmts)?;
+        }
+        Ok(stmts)
+    }
+
+    fn stmt(&mut self, nesting: usize, stmts: &mut Vec<Stmt>) -> Result<()> {
+        match self.u.int_in_range(0..=6)? {
+            0 if nesting > 0 => {
+                let guard = self.int_exp(MAX_EXP_DEPTH)?.0;
+                // only the true branch may end the block early, so the
+                // statements after the if are always reachable.
+                let mut tt = self.stmts(nesting - 1)?;
+                if self.u.ratio(1, 3)? {
+                    tt.push(self.jump()?);
+                }
+                let ff = match self.u.arbitrary()? {
+                    true => self.stmts(nesting - 1)?,
+                    false => vec![],
+                };
+                stmts.push(Stmt::If { guard, tt, ff });
+            }
+            1 if nesting > 0 => {
+                let counter = format!("i{}", self.counters.len());
+                let iterations = self.u.int_in_range(0..=MAX_ITERATIONS)?;
+                let increment = Stmt::Assign {
+                    lhs: Lval::Id(counter.clone()),
+                    rhs: Rhs::Exp(Exp::Arith(
+                        Box::new(Exp::Id(counter.clone())),
+                        ArithOp::Add,
+                        Box::new(Exp::Num(1)),
+                    )),
+                };
+
+                self.counters.push(counter.clone());
+                self.num_counters = self.num_counters.max(self.counters.len());
+                let mut body = vec![increment];
+                body.extend(self.stmts(nesting - 1)?);
+                self.counters.pop();
+
+                stmts.push(Stmt::Assign {
+                    lhs: Lval::Id(counter.clone()),
+                    rhs: Rhs::Exp(Exp::Num(0)),
+                });
+                stmts.push(Stmt::While {
+                    guard: Exp::Compare(
+                        Box::new(Exp::Id(counter)),
+                        CompareOp::Lt,
+                        Box::new(Exp::Num(iterations)),
+                    ),
+                    body,
+                });
+            }
+            2 => match self.call(false, MAX_EXP_DEPTH)? {
+                Some(Exp::Call { callee, args }) => {
+                    let Exp::Id(callee) = *callee else {
+                        unreachable!()
+                    };
+                    stmts.push(Stmt::Call {
+                        callee: Lval::Id(callee),
+                        args,
+                    });
+                }
+                _ => self.assign(stmts)?,
+            },
+            3 => self.delete(stmts)?,
+            _ => self.assign(stmts)?,
+        }
+        Ok(())
+    }
+
+    // the allocation, initialization and deletion of an int array or a struct,
+    // through a fresh local.
+    fn delete(&mut self, stmts: &mut Vec<Stmt>) -> Result<()> {
+        let mut targets = vec![int_ty()];
+        targets.extend(
+            self.typedefs
+                .iter()
+                .map(|typedef| struct_ty(struct_id(&typedef.name))),
+        );
+        let typ = ptr_ty(self.u.choose(&targets)?.clone());
+        let name = format!("d{}", self.deleted.len());
+        let var = || Lval::Id(name.clone());
+
+        stmts.push(Stmt::Assign {
+            lhs: var(),
+            rhs: self.init(&typ)?.unwrap(),
+        });
+        if typ == ptr_ty(int_ty()) {
+            let index = self.u.int_in_range(0..=ARRAY_LEN - 1)?;
+            stmts.push(Stmt::Assign {
+                lhs: Lval::ArrayAccess {
+                    ptr: Box::new(var()),
+                    index: Exp::Num(index),
+                },
+                rhs: Rhs::Exp(self.bounded_int_exp(MAX_EXP_DEPTH)?),
+            });
+        }
+        stmts.push(Stmt::Delete(Exp::Id(name)));
+        self.deleted.push(typ);
+        Ok(())
+    }
+
+    // an assignment to any variable or heap location that can be assigned.
+    fn assign(&mut self, stmts: &mut Vec<Stmt>) -> Result<()> {
+        let mut lvals = self
+            .vars
+            .iter()
+            .map(|decl| (decl.typ.clone(), Lval::Id(decl.name.clone())))
+            .collect::<Vec<_>>();
+        lvals.extend(self.heap_places());
+        if lvals.is_empty() {
+            return Ok(());
+        }
+
+        let (typ, lhs) = self.u.choose(&lvals)?.clone();
+        let heap = !matches!(lhs, Lval::Id(_));
+        let rhs = if typ.is_int() {
+            Rhs::Exp(self.bounded_int_exp(MAX_EXP_DEPTH)?)
+        } else if is_fn_ptr(&typ) {
+            Rhs::Exp(Exp::Id(self.function_of_type(&typ, self.functions.len())?))
+        } else {
+            // heap locations may also be assigned pointers that may be nil.
+            let mut exps = self.ptr_reads(&typ, heap);
+            if heap {
+                exps.push(Exp::Nil);
+            }
+            if exps.is_empty() || self.u.ratio(1, 3)? {
+                self.init(&typ)?.unwrap()
+            } else {
+                Rhs::Exp(self.u.choose(&exps)?.clone())
+            }
+        };
+        stmts.push(Stmt::Assign { lhs, rhs });
+        Ok(())
+    }
+
+    // a break, continue or return statement.
+    fn jump(&mut self) -> Result<Stmt> {
+        if self.counters.is_empty() {
+            return self.ret();
+        }
+        Ok(match self.u.int_in_range(0..=2)? {
+            0 => Stmt::Break,
+            1 => Stmt::Continue,
+            _ => self.ret()?,
+        })
+    }
+
+    fn ret(&mut self) -> Result<Stmt> {
+        Ok(match self.rettyp {
+            Some(_) => Stmt::Return(Some(self.bounded_int_exp(MAX_EXP_DEPTH)?)),
+            None => Stmt::Return(None),
+        })
+    }
+
+    // SECTION: expressions
+
+    // an int expression whose value is less than VALUE_BOUND in absolute value.
+    fn bounded_int_exp(&mut self, depth: usize) -> Result<Exp> {
+        let (exp, bound) = self.int_exp(depth)?;
+        if bound < VALUE_BOUND {
+            return Ok(exp);
+        }
+        // the reduction evaluates exp twice, which calls may make differ (and
+        // would duplicate their side effects).
+        if has_call(&exp) {
+            return Ok(self.int_leaf()?.0);
+        }
+
+        // exp - (exp / VALUE_BOUND) * VALUE_BOUND
+        let modulus = || Box::new(Exp::Num(VALUE_BOUND as i32));
+        let quotient = Exp::Arith(Box::new(exp.clone()), ArithOp::Divide, modulus());
+        Ok(Exp::Arith(
+            Box::new(exp),
+            ArithOp::Subtract,
+            Box::new(Exp::Arith(Box::new(quotient), ArithOp::Multiply, modulus())),
+        ))
+    }
+
+    // an int expression and a bound on the absolute value of its result.
+    fn int_exp(&mut self, depth: usize) -> Result<(Exp, i64)> {
+        use Exp::*;
+
+        if depth == 0 || self.u.ratio(1, 3)? {
+            return self.int_leaf();
+        }
+
+        let exp = match self.u.int_in_range(0..=7)? {
+            0 => {
+                let (exp, bound) = self.int_exp(depth - 1)?;
+                (Neg(Box::new(exp)), bound)
+            }
+            1 => (Not(Box::new(self.int_exp(depth - 1)?.0)), 1),
+            2 => {
+                let (lhs, lhs_bound) = self.int_exp(depth - 1)?;
+                let (rhs, rhs_bound) = self.int_exp(depth - 1)?;
+                let op = *self
+                    .u
+                    .choose(&[ArithOp::Add, ArithOp::Subtract, ArithOp::Multiply])?;
+                let (op, bound) = match lhs_bound.checked_mul(rhs_bound) {
+                    Some(bound) if op == ArithOp::Multiply && bound <= PRODUCT_BOUND => (op, bound),
+                    _ if op == ArithOp::Subtract => (op, lhs_bound + rhs_bound),
+                    _ => (ArithOp::Add, lhs_bound + rhs_bound),
+                };
+                (Arith(Box::new(lhs), op, Box::new(rhs)), bound)
+            }
+            3 => {
+                let (lhs, bound) = self.int_exp(depth - 1)?;
+                let divisor = Num(self.u.int_in_range(1..=MAX_CONST)?);
+                (
+                    Arith(Box::new(lhs), ArithOp::Divide, Box::new(divisor)),
+                    bound,
+                )
+            }
+            4 => {
+                let lhs = self.int_exp(depth - 1)?.0;
+                let rhs = self.int_exp(depth - 1)?.0;
+                let op = *self.u.choose(&[
+                    CompareOp::Equal,
+                    CompareOp::NotEq,
+                    CompareOp::Lt,
+                    CompareOp::Lte,
+                    CompareOp::Gt,
+                    CompareOp::Gte,
+                ])?;
+                (Compare(Box::new(lhs), op, Box::new(rhs)), 1)
+            }
+            5 => match self.ptr_comparison()? {
+                Some(exp) => (exp, 1),
+                None => self.int_leaf()?,
+            },
+            6 => {
+                let lhs = Box::new(self.int_exp(depth - 1)?.0);
+                let rhs = Box::new(self.int_exp(depth - 1)?.0);
+                match self.u.arbitrary()? {
+                    true => (And(lhs, rhs), 1),
+                    false => (Or(lhs, rhs), 1),
+                }
+            }
+            _ => match self.call(true, depth - 1)? {
+                Some(call) => (call, VALUE_BOUND - 1),
+                None => self.int_leaf()?,
+            },
+        };
+        Ok(exp)
+    }
+
+    // a constant or a read of an int variable or heap location.
+    fn int_leaf(&mut self) -> Result<(Exp, i64)> {
+        let mut reads = self
+            .vars
+            .iter()
+            .filter(|decl| decl.typ.is_int())
+            .map(|decl| (Exp::Id(decl.name.clone()), VALUE_BOUND - 1))
+            .collect::<Vec<_>>();
+        reads.extend(
+            self.counters
+                .iter()
+                .map(|counter| (Exp::Id(counter.clone()), MAX_ITERATIONS as i64)),
+        );
+        reads.extend(
+            self.heap_places()
+                .into_iter()
+                .filter(|(typ, _)| typ.is_int())
+                .map(|(_, lval)| (lval_to_exp(lval), VALUE_BOUND - 1)),
+        );
+
+        if reads.is_empty() || self.u.arbitrary()? {
+            let n = self.u.int_in_range(0..=MAX_CONST)?;
+            Ok((Exp::Num(n), n as i64))
+        } else {
+            Ok(self.u.choose(&reads)?.clone())
+        }
+    }
+
+    // an equality comparison of two pointers of the same type (which may be
+    // nil, but not both).
+    fn ptr_comparison(&mut self) -> Result<Option<Exp>> {
+        let mut types: Vec<Type> = vec![];
+        for typ in self
+            .vars
+            .iter()
+            .map(|decl| decl.typ.clone())
+            .chain(self.heap_places().into_iter().map(|(typ, _)| typ))
+        {
+            if typ.is_ptr() && !is_fn_ptr(&typ) && !types.contains(&typ) {
+                types.push(typ);
+            }
+        }
+        if types.is_empty() {
+            return Ok(None);
+        }
+
+        let typ = self.u.choose(&types)?.clone();
+        let exps = self.ptr_reads(&typ, true);
+        let lhs = self.u.choose(&exps)?.clone();
+        let rhs = match self.u.arbitrary()? {
+            true => self.u.choose(&exps)?.clone(),
+            false => Exp::Nil,
+        };
+        let op = *self.u.choose(&[CompareOp::Equal, CompareOp::NotEq])?;
+        Ok(Some(Exp::Compare(Box::new(lhs), op, Box::new(rhs))))
+    }
+
+    // the expressions reading a pointer of the given type, which are only
+    // guaranteed to be non-nil if maybe_nil is false.
+    fn ptr_reads(&self, typ: &Type, maybe_nil: bool) -> Vec<Exp> {
+        let mut exps = self
+            .vars
+            .iter()
+            .filter(|decl| decl.typ == *typ)
+            .map(|decl| Exp::Id(decl.name.clone()))
+            .collect::<Vec<_>>();
+        if maybe_nil {
+            exps.extend(
+                self.heap_places()
+                    .into_iter()
+                    .filter(|(t, _)| t == typ)
+                    .map(|(_, lval)| lval_to_exp(lval)),
+            );
+        }
+        exps
+    }
+
+    // a call of a function that returns an int (if returns_int is true), or of
+    // any function or extern, if there is one that can be called.
+    fn call(&mut self, returns_int: bool, depth: usize) -> Result<Option<Exp>> {
+        let has_ptr = |vars: &[Decl], typ: &Type| {
+            is_fn_ptr(typ) || !typ.is_ptr() || vars.iter().any(|decl| decl.typ == *typ)
+        };
+
+        // the callees, with the index of the function they call (if known).
+        let mut callees = vec![];
+        for (i, function) in self.functions.iter().enumerate() {
+            if (function.rettyp.is_some() || !returns_int)
+                && function.params.iter().all(|p| has_ptr(&self.vars, &p.typ))
+            {
+                callees.push((function.name.clone(), fn_type(function), Some(i)));
+            }
+        }
+        for decl in &self.vars {
+            let Some(LirType::Function { ret_ty, param_ty }) =
+                decl.typ.get_deref_type().map(|typ| &*typ.0)
+            else {
+                continue;
+            };
+            if (ret_ty.is_some() || !returns_int)
+                && param_ty.iter().all(|typ| has_ptr(&self.vars, typ))
+            {
+                callees.push((decl.name.clone(), decl.typ.clone(), None));
+            }
+        }
+        if !returns_int {
+            for decl in &self.externs {
+                callees.push((decl.name.clone(), ptr_ty(decl.typ.clone()), None));
+            }
+        }
+        if callees.is_empty() {
+            return Ok(None);
+        }
+
+        let (callee, typ, index) = self.u.choose(&callees)?.clone();
+        let Some(LirType::Function { param_ty, .. }) = typ.get_deref_type().map(|typ| &*typ.0)
+        else {
+            unreachable!()
+        };
+        let mut args = vec![];
+        for typ in param_ty {
+            let arg = if typ.is_int() {
+                self.bounded_int_exp(depth)?
+            } else if is_fn_ptr(typ) {
+                // only function pointer types without function pointer
+                // parameters are used for indirect calls, so the index of a
+                // function with such parameters is known.
+                Exp::Id(self.function_of_type(typ, index.unwrap())?)
+            } else {
+                let exps = self.ptr_reads(typ, false);
+                self.u.choose(&exps)?.clone()
+            };
+            args.push(arg);
+        }
+
+        Ok(Some(Exp::Call {
+            callee: Box::new(Exp::Id(callee)),
+            args,
+        }))
+    }
+
+    // SECTION: helpers
+
+    // a type for a variable: an int, a pointer to an int, struct or int pointer
+    // (which are always initialized) or a function pointer (if fn_ptrs is true
+    // and there is a function it can point to).
+    fn var_type(&mut self, fn_ptrs: bool) -> Result<Type> {
+        let mut types = vec![
+            int_ty(),
+            int_ty(),
+            ptr_ty(int_ty()),
+            ptr_ty(ptr_ty(int_ty())),
+        ];
+        types.extend(
+            self.typedefs
+                .iter()
+                .map(|typedef| ptr_ty(struct_ty(struct_id(&typedef.name)))),
+        );
+        if fn_ptrs {
+            // function pointers with function pointer parameters would allow
+            // for calls whose callee isn't known, and so may be recursive.
+            types.extend(
+                self.functions
+                    .iter()
+                    .filter(|function| !function.params.iter().any(|p| is_fn_ptr(&p.typ)))
+                    .map(fn_type),
+            );
+        }
+        Ok(self.u.choose(&types)?.clone())
+    }
+
+    // the initial value of a variable of the given type, if it needs one.
+    fn init(&mut self, typ: &Type) -> Result<Option<Rhs>> {
+        let Some(target) = typ.get_deref_type() else {
+            return Ok(None);
+        };
+        Ok(Some(if target.is_function() {
+            Rhs::Exp(Exp::Id(self.function_of_type(typ, self.functions.len())?))
+        } else if target.is_struct() {
+            Rhs::New {
+                typ: target.clone(),
+                num: None,
+            }
+        } else {
+            Rhs::New {
+                typ: target.clone(),
+                num: Some(Exp::Num(ARRAY_LEN)),
+            }
+        }))
+    }
+
+    // the name of one of the first n functions with the given (function
+    // pointer) type.
+    fn function_of_type(&mut self, typ: &Type, n: usize) -> Result<String> {
+        let names = self.functions[..n]
+            .iter()
+            .filter(|function| fn_type(function) == *typ)
+            .map(|function| function.name.clone())
+            .collect::<Vec<_>>();
+        Ok(self.u.choose(&names)?.clone())
+    }
+
+    // the heap locations that can be accessed through the variables in scope,
+    // with their types.
+    fn heap_places(&self) -> Vec<(Type, Lval)> {
+        let mut places = vec![];
+        for decl in &self.vars {
+            let var = || Box::new(Lval::Id(decl.name.clone()));
+            let Some(target) = decl.typ.get_deref_type() else {
+                continue;
+            };
+            match &*target.0 {
+                LirType::Struct(id) => {
+                    let typedef = self
+                        .typedefs
+                        .iter()
+                        .find(|typedef| typedef.name == id.name())
+                        .unwrap();
+                    places.extend(typedef.fields.iter().map(|field| {
+                        let lval = Lval::FieldAccess {
+                            ptr: var(),
+                            field: field.name.clone(),
+                        };
+                        (field.typ.clone(), lval)
+                    }));
+                }
+                LirType::Function { .. } => {}
+                _ => {
+                    places.push((target.clone(), Lval::Deref(var())));
+                    places.extend((0..ARRAY_LEN).map(|i| {
+                        let lval = Lval::ArrayAccess {
+                            ptr: var(),
+                            index: Exp::Num(i),
+                        };
+                        (target.clone(), lval)
+                    }));
+                }
+            }
+        }
+        places
+    }
+}
+
+fn is_fn_ptr(typ: &Type) -> bool {
+    typ.get_deref_type().is_some_and(|typ| typ.is_function())
+}
+
+// the type of a pointer to the function.
+fn fn_type(function: &Function) -> Type {
+    ptr_ty(func_ty(
+        function.rettyp.clone(),
+        function.params.iter().map(|p| p.typ.clone()).collect(),
+    ))
+}
+
+fn lval_to_exp(lval: Lval) -> Exp {
+    match lval {
+        Lval::Id(name) => Exp::Id(name),
+        Lval::Deref(ptr) => Exp::Deref(Box::new(lval_to_exp(*ptr))),
+        Lval::ArrayAccess { ptr, index } => Exp::ArrayAccess {
+            ptr: Box::new(lval_to_exp(*ptr)),
+            index: Box::new(index),
+        },
+        Lval::FieldAccess { ptr, field } => Exp::FieldAccess {
+            ptr: Box::new(lval_to_exp(*ptr)),
+            field,
+        },
+    }
+}
+
+fn has_call(exp: &Exp) -> bool {
+    match exp {
+        Exp::Num(_) | Exp::Id(_) | Exp::Nil => false,
+        Exp::Neg(exp) | Exp::Deref(exp) | Exp::Not(exp) => has_call(exp),
+        Exp::FieldAccess { ptr, .. } => has_call(ptr),
+        Exp::Arith(lhs, _, rhs)
+        | Exp::Compare(lhs, _, rhs)
+        | Exp::And(lhs, rhs)
+        | Exp::Or(lhs, rhs)
+        | Exp::ArrayAccess {
+            ptr: lhs,
+            index: rhs,
+        } => has_call(lhs) || has_call(rhs),
+        Exp::Call { .. } => true,
+    }
+}
@@ -169,6 +169,7 @@ impl Stmt {
                     .collect::<Vec<_>>()
                     .join(", ")
             ),
+            Stmt::Delete(exp) => format!("{ind}delete {};", exp.pretty_print()),
             Stmt::Break => format!("{ind}break;"),
             Stmt::Continue => format!("{ind}continue;"),
             Stmt::Return(op) => match op {
@@ -0,0 +1,494 @@
+// source-level code coverage for C♭ programs.
+//
+// coverage is computed from profiles of runs of the lowered program (see
+// `Interpreter::profile`) and a source map that says which C♭ statements and
+// guards each LIR basic block comes from. the AST doesn't record source
+// positions, so the source map is produced alongside the lowered program and
+// saved as JSON.
+//
+// the parser records where statements start (`parse_with_positions`), and
+// `lower_with_source_map` turns that into the source map by noting which blocks
+// the code of each statement and guard is lowered to.
+//
+// a statement is covered if a block containing (part of) its code was executed,
+// a line if a statement starting on it was, and a side of an `if` or `while`
+// guard if control went from one of the guard's `$branch`es to that side.
+
+use super::*;
+use crate::interpreter::Profile;
+use crate::middle_end::lir;
+use derive_more::Display;
+use serde::{Deserialize, Serialize};
+
+// A position in the source; lines and columns start at 1.
+#[derive(
+    Clone, Copy, Debug, Deserialize, Display, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize,
+)]
+#[display(fmt = "{line}:{column}")]
+pub struct SourcePos {
+    pub line: u32,
+    pub column: u32,
+}
+
+impl SourcePos {
+    // The position of the byte at `offset` in `code`.
+    pub fn at(code: &str, offset: usize) -> Self {
+        let before = &code[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        SourcePos {
+            line: before.matches('\n').count() as u32 + 1,
+            column: before[line_start..].chars().count() as u32 + 1,
+        }
+    }
+}
+
+// Where the statements of each function start, by function name, in the order
+// the lowering visits them: in source order, with the statements of an `if` or
+// `while` after the `if` or `while` itself. Initialized `let` declarations
+// count as statements.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SourcePositions {
+    pub functions: Map<String, Vec<StmtPosition>>,
+}
+
+impl SourcePositions {
+    // Splits the positions of all the statements of program, in order, by
+    // function.
+    pub(crate) fn split(positions: Vec<StmtPosition>, program: &Program) -> Self {
+        let mut positions = positions.into_iter();
+        let functions = program
+            .functions
+            .iter()
+            .map(|func| {
+                let inits = func.body.decls.iter().filter(|(_, init)| init.is_some());
+                let count = inits.count() + func.body.stmts.iter().map(count_stmts).sum::<usize>();
+                (func.name.clone(), positions.by_ref().take(count).collect())
+            })
+            .collect();
+        assert!(
+            positions.next().is_none(),
+            "recorded the positions of statements outside of functions"
+        );
+        SourcePositions { functions }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("invalid source positions: {e}"))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct StmtPosition {
+    pub stmt: SourcePos,
+    // where the guard starts, for `if` and `while` statements
+    #[serde(default)]
+    pub guard: Option<SourcePos>,
+}
+
+// Where the code of each block of the lowered program comes from, by function
+// and block name. Blocks that don't appear here (e.g., the single `$ret` block)
+// aren't counted.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct SourceMap {
+    // the name of the source file, for reports
+    #[serde(default)]
+    pub source_file: String,
+    pub blocks: Map<String, Map<String, BlockSource>>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct BlockSource {
+    // where the statements whose code is (partly) in the block start
+    #[serde(default)]
+    pub stmts: Vec<SourcePos>,
+    // where the `if` or `while` guard the block's `$branch` evaluates starts
+    #[serde(default)]
+    pub guard: Option<SourcePos>,
+}
+
+impl SourceMap {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("source maps can be serialized")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("invalid source map: {e}"))
+    }
+}
+
+// The coverage of one program, aggregated over any number of runs.
+#[derive(Clone, Debug)]
+pub struct Coverage {
+    map: SourceMap,
+    // the targets of the guard `$branch`es when the guard is true and false,
+    // by (function, block)
+    targets: Map<(String, String), (String, String)>,
+    runs: u64,
+    calls: Map<String, u64>,
+    blocks: Map<(String, String), u64>,
+    // how many times each guard `$branch` went to its true and false target
+    branches: Map<(String, String), (u64, u64)>,
+}
+
+// The counts a `Coverage` has aggregated, by function and block, to save them
+// and keep aggregating in later processes.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CoverageCounts {
+    pub runs: u64,
+    pub calls: Map<String, u64>,
+    pub blocks: Map<String, Map<String, u64>>,
+    // how many times each guard `$branch` went to its true and false target
+    pub branches: Map<String, Map<String, (u64, u64)>>,
+}
+
+impl CoverageCounts {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("coverage counts can be serialized")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("invalid coverage counts: {e}"))
+    }
+}
+
+// One `$branch` of a guard and how many times it went each way.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BranchCoverage {
+    pub guard: SourcePos,
+    pub func: String,
+    pub block: String,
+    pub taken: u64,
+    pub not_taken: u64,
+}
+
+impl Coverage {
+    // Fails if the source map mentions blocks the program doesn't have, or
+    // guards in blocks that don't end with a `$branch`.
+    pub fn new(program: &lir::Program, map: SourceMap) -> Result<Self, String> {
+        let mut targets = Map::new();
+        for (func, blocks) in &map.blocks {
+            let Some(f) = program.functions.get(&lir::func_id(func)) else {
+                return Err(format!(
+                    "the source map refers to an unknown function {func}"
+                ));
+            };
+            for (bb, source) in blocks {
+                let Some(block) = f.body.get(&lir::bb_id(bb)) else {
+                    return Err(format!(
+                        "the source map refers to an unknown block {func}:{bb}"
+                    ));
+                };
+                match (&source.guard, &block.term) {
+                    (None, _) => {}
+                    (Some(_), lir::Terminal::Branch { tt, ff, .. }) => {
+                        let key = (func.clone(), bb.clone());
+                        targets.insert(key, (tt.to_string(), ff.to_string()));
+                    }
+                    (Some(pos), term) => {
+                        return Err(format!(
+                            "the guard at {pos} is mapped to {func}:{bb}, which ends with {term} instead of a $branch"
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(Coverage {
+            map,
+            targets,
+            runs: 0,
+            calls: Map::new(),
+            blocks: Map::new(),
+            branches: Map::new(),
+        })
+    }
+
+    // Add the counts of a run of the program.
+    pub fn add(&mut self, profile: &Profile) {
+        self.runs += 1;
+        for (func, blocks) in &self.map.blocks {
+            let calls = profile.functions.get(func).map_or(0, |f| f.calls);
+            *self.calls.entry(func.clone()).or_default() += calls;
+            for bb in blocks.keys() {
+                let key = (func.clone(), bb.clone());
+                *self.blocks.entry(key.clone()).or_default() += profile.block_count(func, bb);
+                if let Some((tt, ff)) = self.targets.get(&key) {
+                    let (taken, not_taken) = self.branches.entry(key).or_default();
+                    *taken += profile.edge_count(func, bb, tt);
+                    *not_taken += profile.edge_count(func, bb, ff);
+                }
+            }
+        }
+    }
+
+    // Add the counts of another `Coverage` of the program. Fails if they
+    // mention blocks or guards the source map doesn't have.
+    pub fn add_counts(&mut self, counts: &CoverageCounts) -> Result<(), String> {
+        for (func, blocks) in &counts.blocks {
+            for bb in blocks.keys() {
+                if !self
+                    .map
+                    .blocks
+                    .get(func)
+                    .is_some_and(|b| b.contains_key(bb))
+                {
+                    return Err(format!(
+                        "the coverage counts refer to an unknown block {func}:{bb}"
+                    ));
+                }
+            }
+        }
+        for (func, branches) in &counts.branches {
+            for bb in branches.keys() {
+                if !self.targets.contains_key(&(func.clone(), bb.clone())) {
+                    return Err(format!(
+                        "the coverage counts refer to an unknown guard in {func}:{bb}"
+                    ));
+                }
+            }
+        }
+
+        self.runs += counts.runs;
+        for (func, calls) in &counts.calls {
+            *self.calls.entry(func.clone()).or_default() += calls;
+        }
+        for (func, blocks) in &counts.blocks {
+            for (bb, n) in blocks {
+                *self.blocks.entry((func.clone(), bb.clone())).or_default() += n;
+            }
+        }
+        for (func, branches) in &counts.branches {
+            for (bb, (tt, ff)) in branches {
+                let (taken, not_taken) =
+                    self.branches.entry((func.clone(), bb.clone())).or_default();
+                *taken += tt;
+                *not_taken += ff;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn counts(&self) -> CoverageCounts {
+        let mut counts = CoverageCounts {
+            runs: self.runs,
+            calls: self.calls.clone(),
+            ..CoverageCounts::default()
+        };
+        for ((func, bb), n) in &self.blocks {
+            counts
+                .blocks
+                .entry(func.clone())
+                .or_default()
+                .insert(bb.clone(), *n);
+        }
+        for ((func, bb), n) in &self.branches {
+            counts
+                .branches
+                .entry(func.clone())
+                .or_default()
+                .insert(bb.clone(), *n);
+        }
+        counts
+    }
+
+    pub fn runs(&self) -> u64 {
+        self.runs
+    }
+
+    // The source file the source map is for.
+    pub fn source_file(&self) -> &str {
+        &self.map.source_file
+    }
+
+    // How many times each statement was executed: the largest count of the
+    // blocks its code is in.
+    pub fn statements(&self) -> Map<SourcePos, u64> {
+        let mut stmts = Map::<SourcePos, u64>::new();
+        for (func, blocks) in &self.map.blocks {
+            for (bb, source) in blocks {
+                let n = self.block_count(func, bb);
+                for pos in &source.stmts {
+                    let count = stmts.entry(*pos).or_default();
+                    *count = (*count).max(n);
+                }
+            }
+        }
+        stmts
+    }
+
+    // How many times each line with statements was executed: the largest count
+    // of the statements starting on it.
+    pub fn lines(&self) -> Map<u32, u64> {
+        let mut lines = Map::<u32, u64>::new();
+        for (pos, n) in self.statements() {
+            let count = lines.entry(pos.line).or_default();
+            *count = (*count).max(n);
+        }
+        lines
+    }
+
+    // The guard `$branch`es in source order.
+    pub fn branches(&self) -> Vec<BranchCoverage> {
+        let mut branches = self
+            .targets
+            .keys()
+            .map(|(func, bb)| {
+                let (taken, not_taken) = self
+                    .branches
+                    .get(&(func.clone(), bb.clone()))
+                    .copied()
+                    .unwrap_or_default();
+                BranchCoverage {
+                    guard: self.map.blocks[func][bb].guard.unwrap(),
+                    func: func.clone(),
+                    block: bb.clone(),
+                    taken,
+                    not_taken,
+                }
+            })
+            .collect::<Vec<_>>();
+        branches.sort_by(|b1, b2| {
+            (b1.guard, &b1.func, &b1.block).cmp(&(b2.guard, &b2.func, &b2.block))
+        });
+        branches
+    }
+
+    // The guards that weren't evaluated both ways, i.e., that have a `$branch`
+    // that didn't go both ways.
+    pub fn partial_guards(&self) -> Set<SourcePos> {
+        self.branches()
+            .into_iter()
+            .filter(|b| b.taken == 0 || b.not_taken == 0)
+            .map(|b| b.guard)
+            .collect()
+    }
+
+    // The number of covered lines, statements and branch sides out of all of
+    // them, one per line.
+    pub fn summary(&self) -> String {
+        let (lines, stmts, branches) = (self.lines(), self.statements(), self.branches());
+        let covered = |n: &u64| *n > 0;
+        let sides = branches
+            .iter()
+            .map(|b| covered(&b.taken) as usize + covered(&b.not_taken) as usize)
+            .sum::<usize>();
+        format!(
+            "lines: {}\nstatements: {}\nbranches: {}\n",
+            ratio(lines.values().filter(|n| covered(n)).count(), lines.len()),
+            ratio(stmts.values().filter(|n| covered(n)).count(), stmts.len()),
+            ratio(sides, 2 * branches.len()),
+        )
+    }
+
+    // The coverage in lcov's tracefile format, for the source file
+    // `source_file`. Functions start at their first statement.
+    pub fn to_lcov(&self, source_file: &str) -> String {
+        let mut lcov = format!("TN:\nSF:{source_file}\n");
+
+        let mut functions = vec![];
+        for (func, blocks) in &self.map.blocks {
+            if let Some(line) = blocks
+                .values()
+                .flat_map(|b| &b.stmts)
+                .map(|pos| pos.line)
+                .min()
+            {
+                functions.push((line, func, self.calls.get(func).copied().unwrap_or(0)));
+            }
+        }
+        functions.sort();
+        for (line, func, _) in &functions {
+            lcov += &format!("FN:{line},{func}\n");
+        }
+        for (_, func, calls) in &functions {
+            lcov += &format!("FNDA:{calls},{func}\n");
+        }
+        lcov += &format!(
+            "FNF:{}\nFNH:{}\n",
+            functions.len(),
+            functions.iter().filter(|(_, _, calls)| *calls > 0).count()
+        );
+
+        let branches = self.branches();
+        for (i, b) in branches.iter().enumerate() {
+            let line = b.guard.line;
+            if self.block_count(&b.func, &b.block) == 0 {
+                lcov += &format!("BRDA:{line},{i},0,-\nBRDA:{line},{i},1,-\n");
+            } else {
+                lcov += &format!(
+                    "BRDA:{line},{i},0,{}\nBRDA:{line},{i},1,{}\n",
+                    b.taken, b.not_taken
+                );
+            }
+        }
+        let hit = branches
+            .iter()
+            .map(|b| (b.taken > 0) as usize + (b.not_taken > 0) as usize)
+            .sum::<usize>();
+        lcov += &format!("BRF:{}\nBRH:{hit}\n", 2 * branches.len());
+
+        let lines = self.lines();
+        for (line, n) in &lines {
+            lcov += &format!("DA:{line},{n}\n");
+        }
+        lcov += &format!(
+            "LF:{}\nLH:{}\nend_of_record\n",
+            lines.len(),
+            lines.values().filter(|n| **n > 0).count()
+        );
+        lcov
+    }
+
+    // The source with each line prefixed by how many times it was executed
+    // ("-" if it has no statements, "#####" if it was never executed), the
+    // guards' branches under their lines, and a summary at the end.
+    pub fn annotate(&self, source: &str) -> String {
+        let lines = self.lines();
+        let mut branches = Map::<u32, Vec<BranchCoverage>>::new();
+        for b in self.branches() {
+            branches.entry(b.guard.line).or_default().push(b);
+        }
+
+        let mut report = String::new();
+        for (i, text) in source.lines().enumerate() {
+            let line = i as u32 + 1;
+            let count = match lines.get(&line) {
+                None => "-".to_string(),
+                Some(0) => "#####".to_string(),
+                Some(n) => n.to_string(),
+            };
+            report += &format!("{count:>9}:{line:>5}: {text}\n");
+            for (k, b) in branches.get(&line).into_iter().flatten().enumerate() {
+                report += &format!(
+                    "{:>17}branch {k} taken {}, not taken {}\n",
+                    "", b.taken, b.not_taken
+                );
+            }
+        }
+        report + "\n" + &self.summary()
+    }
+
+    fn block_count(&self, func: &str, bb: &str) -> u64 {
+        self.blocks
+            .get(&(func.to_string(), bb.to_string()))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+fn ratio(covered: usize, total: usize) -> String {
+    if total == 0 {
+        return "0/0".to_string();
+    }
+    let percent = 100.0 * covered as f64 / total as f64;
+    format!("{covered}/{total} ({percent:.1}%)")
+}
+
+// the number of statements in stmt, including itself, in the order of
+// `SourcePositions`.
+pub(crate) fn count_stmts(stmt: &Stmt) -> usize {
+    1 + match stmt {
+        Stmt::If { tt, ff, .. } => tt.iter().chain(ff).map(count_stmts).sum(),
+        Stmt::While { body, .. } => body.iter().map(count_stmts).sum(),
+        _ => 0,
+    }
+}
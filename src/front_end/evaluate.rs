@@ -0,0 +1,594 @@
+// a tree-walking interpreter for C♭ programs. it implements the language
+// semantics directly on the AST so that it can be used as an oracle for the
+// lowering pass: running a program with `evaluate` and running its lowered
+// version with the LIR interpreter should produce the same return value and the
+// same printed output.
+//
+// the runtime representation (heap layout, nil handling) deliberately mirrors
+// the LIR interpreter so that the two can be compared directly. errors are
+// worded in terms of C♭; the differential tests compare their kinds rather
+// than their messages.
+
+use super::*;
+use crate::interpreter::{ArithmeticMode, ExternRegistry, Host, RuntimeError, Sink};
+use crate::middle_end::lir::ArithmeticOp;
+use crate::middle_end::lir::LirType;
+use std::mem;
+
+// evaluate given program, return the return value of `main`.
+pub fn evaluate(program: &Valid<Program>, externs: ExternRegistry) -> Result<i64, RuntimeError> {
+    let mut e = Evaluator::new(&program.0, externs, Host::new(vec![Sink::Stdout]));
+    e.run()
+}
+
+// evaluate given program without printing anything, return the return value of
+// `main` along with the lines printed by the program.
+pub fn evaluate_with_output(
+    program: &Valid<Program>,
+    externs: ExternRegistry,
+) -> (Result<i64, RuntimeError>, Vec<String>) {
+    let mut e = Evaluator::new(&program.0, externs, Host::new(vec![Sink::Capture]));
+    let result = e.run();
+    (result, e.host.output)
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Address {
+    // null pointer
+    Nil,
+    // the element at an offset in a heap object
+    ToHeap(u32, u32),
+    // addresses to a field of an object
+    Field(Box<Address>, String),
+}
+
+impl Address {
+    // the heap object the address is in, if any.
+    fn object(&self) -> Option<u32> {
+        match self {
+            Address::Nil => None,
+            Address::ToHeap(object, _) => Some(*object),
+            Address::Field(base, _) => base.object(),
+        }
+    }
+}
+
+// C♭ values
+#[derive(Clone, Debug)]
+enum Value {
+    // function pointer
+    FnPtr(String),
+    Ptr(Address),
+    Int(i64),
+    Struct(Map<String, Value>),
+}
+
+// where an assignment writes its value
+enum Place {
+    Var(String),
+    Mem(Address),
+}
+
+// how a statement (or a sequence of statements) finished executing
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(Option<Value>),
+}
+
+struct Evaluator<'a> {
+    // struct definitions
+    typedefs: Map<&'a str, &'a [Decl]>,
+    // internal functions
+    functions: Map<&'a str, &'a Function>,
+    // declared external functions
+    externs: Map<&'a str, &'a Type>,
+    // current environment (parameters and locals)
+    env: Map<String, Value>,
+    // global environment
+    glob: Map<String, Value>,
+    // store/heap, by object
+    store: Map<u32, Vec<Value>>,
+    // the objects that have been deleted
+    freed: Set<u32>,
+    // next available heap object
+    next_object: u32,
+    // implementations of the external functions
+    registry: ExternRegistry,
+    // printed output and other state shared with external functions
+    host: Host,
+}
+
+// why evaluation stopped early
+enum Stop {
+    Error(RuntimeError),
+    // the program called `exit`
+    Exit(i64),
+}
+
+impl From<RuntimeError> for Stop {
+    fn from(e: RuntimeError) -> Self {
+        Stop::Error(e)
+    }
+}
+
+fn err<T>(msg: String) -> Result<T, Stop> {
+    Err(Stop::Error(RuntimeError::new(msg)))
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(program: &'a Program, registry: ExternRegistry, host: Host) -> Self {
+        let mut e = Evaluator {
+            typedefs: program
+                .typedefs
+                .iter()
+                .map(|t| (t.name.as_str(), t.fields.as_slice()))
+                .collect(),
+            functions: program
+                .functions
+                .iter()
+                .map(|f| (f.name.as_str(), f))
+                .collect(),
+            externs: program
+                .externs
+                .iter()
+                .map(|d| (d.name.as_str(), &d.typ))
+                .collect(),
+            env: Map::new(),
+            glob: Map::new(),
+            store: Map::new(),
+            freed: Set::new(),
+            next_object: 1,
+            registry,
+            host,
+        };
+        e.glob = program
+            .globals
+            .iter()
+            .map(|d| (d.name.clone(), e.zero_init(&d.typ)))
+            .collect();
+        e
+    }
+
+    fn run(&mut self) -> Result<i64, RuntimeError> {
+        for (name, typ) in &self.externs {
+            self.registry.check_declaration(name, typ)?;
+        }
+        let result = match self.call("main", vec![]) {
+            Ok(Some(Value::Int(n))) | Err(Stop::Exit(n)) => Ok(n),
+            Ok(v) => Err(RuntimeError::new(format!("main returned non-int value {v:?}"))),
+            Err(Stop::Error(e)) => Err(e),
+        };
+        let flushed = self.host.flush();
+        result.and_then(|r| flushed.map(|_| r))
+    }
+
+    fn zero_init(&self, typ: &Type) -> Value {
+        use LirType::*;
+        match &*typ.0 {
+            Int => Value::Int(0),
+            Struct(name) => Value::Struct(
+                self.typedefs[name.name()]
+                    .iter()
+                    .map(|f| (f.name.clone(), self.zero_init(&f.typ)))
+                    .collect(),
+            ),
+            Function { .. } => unreachable!("function values are not allowed in C♭"),
+            Pointer(_) => Value::Ptr(Address::Nil),
+        }
+    }
+
+    fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Option<Value>, Stop> {
+        let f = self.functions[name];
+        assert_eq!(f.params.len(), args.len());
+
+        let mut env: Map<String, Value> = f
+            .params
+            .iter()
+            .map(|p| p.name.clone())
+            .zip(args)
+            .collect();
+        for (decl, _) in &f.body.decls {
+            env.insert(decl.name.clone(), self.zero_init(&decl.typ));
+        }
+        let caller_env = mem::replace(&mut self.env, env);
+
+        let result = self.call_body(&f.body);
+        self.env = caller_env;
+
+        match result? {
+            Flow::Return(v) => Ok(v),
+            _ => err(format!("function {name} finished without returning")),
+        }
+    }
+
+    fn call_body(&mut self, body: &Body) -> Result<Flow, Stop> {
+        for (decl, init) in &body.decls {
+            if let Some(init) = init {
+                let v = self.eval(init)?;
+                self.env.insert(decl.name.clone(), v);
+            }
+        }
+        self.exec_all(&body.stmts)
+    }
+
+    fn exec_all(&mut self, stmts: &[Stmt]) -> Result<Flow, Stop> {
+        for stmt in stmts {
+            match self.exec(stmt)? {
+                Flow::Normal => {}
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn exec(&mut self, stmt: &Stmt) -> Result<Flow, Stop> {
+        match stmt {
+            Stmt::Break => Ok(Flow::Break),
+            Stmt::Continue => Ok(Flow::Continue),
+            Stmt::Return(None) => Ok(Flow::Return(None)),
+            Stmt::Return(Some(e)) => Ok(Flow::Return(Some(self.eval(e)?))),
+            Stmt::Assign { lhs, rhs } => {
+                let v = match rhs {
+                    Rhs::Exp(e) => self.eval(e)?,
+                    Rhs::New { typ, num } => {
+                        let n = match num {
+                            Some(num) => self.eval_to_int(num)?,
+                            None => 1,
+                        };
+                        if n < 0 {
+                            return err("cannot allocate a negative number of elements".into());
+                        }
+                        let n = u32::try_from(n)
+                            .or_else(|_| err(format!("cannot allocate {n} elements")))?;
+                        Value::Ptr(self.alloc_array(n, typ))
+                    }
+                };
+                let place = self.place(lhs)?;
+                self.write(place, v)?;
+                Ok(Flow::Normal)
+            }
+            Stmt::Call { callee, args } => {
+                self.call_exp(callee, args)?;
+                Ok(Flow::Normal)
+            }
+            Stmt::Delete(e) => {
+                match self.eval(e)? {
+                    Value::Ptr(address) => self.free(address)?,
+                    v => err(format!("can only delete pointers, got {v:?}"))?,
+                }
+                Ok(Flow::Normal)
+            }
+            Stmt::If { guard, tt, ff } => {
+                if self.eval_guard(guard)? {
+                    self.exec_all(tt)
+                } else {
+                    self.exec_all(ff)
+                }
+            }
+            Stmt::While { guard, body } => {
+                while self.eval_guard(guard)? {
+                    match self.exec_all(body)? {
+                        Flow::Normal | Flow::Continue => {}
+                        Flow::Break => break,
+                        flow @ Flow::Return(_) => return Ok(flow),
+                    }
+                }
+                Ok(Flow::Normal)
+            }
+        }
+    }
+
+    fn alloc_array(&mut self, n: u32, typ: &Type) -> Address {
+        let object = self.next_object;
+        self.next_object += 1;
+
+        let zero_initialized_value = self.zero_init(typ);
+        self.store
+            .insert(object, vec![zero_initialized_value; n as usize]);
+
+        Address::ToHeap(object, 0)
+    }
+
+    fn free(&mut self, address: Address) -> Result<(), Stop> {
+        let object = match address {
+            Address::Nil => return Ok(()),
+            Address::ToHeap(object, 0) => object,
+            Address::ToHeap(..) => {
+                return err("invalid delete: the pointer points into the middle of an array".into())
+            }
+            Address::Field(..) => {
+                return err("invalid delete: the pointer doesn't point to an array".into())
+            }
+        };
+        if !self.freed.insert(object) {
+            return err("double delete".into());
+        }
+        self.store.remove(&object);
+        Ok(())
+    }
+
+    // evaluate a call in either statement or expression position. `callee` is
+    // anything that can be evaluated to a function pointer.
+    fn call_exp<C: Callee>(&mut self, callee: &C, args: &[Exp]) -> Result<Option<Value>, Stop> {
+        let args = args
+            .iter()
+            .map(|a| self.eval(a))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(name) = callee.as_id().filter(|name| self.externs.contains_key(name)) {
+            return self.call_ext(name, args);
+        }
+
+        match callee.value(self)? {
+            Value::FnPtr(f) => self.call(&f, args),
+            v => err(format!("tried to call non-function value {v:?}")),
+        }
+    }
+
+    fn call_ext(&mut self, name: &str, args: Vec<Value>) -> Result<Option<Value>, Stop> {
+        let args = args
+            .into_iter()
+            .map(|a| match a {
+                Value::Int(n) => Ok(n),
+                v => err(format!("expected int arguments to {name}, got {v:?}")),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let result = self.registry.call(name, &mut self.host, &args)?;
+        match self.host.exit {
+            Some(code) => Err(Stop::Exit(code)),
+            None => Ok(result.map(Value::Int)),
+        }
+    }
+
+    fn eval_guard(&mut self, guard: &Exp) -> Result<bool, Stop> {
+        match self.eval(guard)? {
+            Value::Int(n) => Ok(n != 0),
+            v => err(format!("guards must be ints, got {v:?}")),
+        }
+    }
+
+    fn eval_to_int(&mut self, exp: &Exp) -> Result<i64, Stop> {
+        match self.eval(exp)? {
+            Value::Int(n) => Ok(n),
+            v => err(format!("expected an int, got {v:?}")),
+        }
+    }
+
+    fn eval(&mut self, exp: &Exp) -> Result<Value, Stop> {
+        match exp {
+            Exp::Num(n) => Ok(Value::Int(*n as i64)),
+            Exp::Id(x) => self.lookup(x),
+            Exp::Nil => Ok(Value::Ptr(Address::Nil)),
+            Exp::Neg(e) => Ok(Value::Int(-self.eval_to_int(e)?)),
+            Exp::Deref(e) => match self.eval(e)? {
+                Value::Ptr(address) => self.read(&address),
+                v => err(format!("can only dereference pointers, got {v:?}")),
+            },
+            Exp::Not(e) => {
+                let v = self.eval(e)?;
+                compare(CompareOp::Equal, v, Value::Int(0))
+            }
+            Exp::Arith(e1, op, e2) => {
+                let (n1, n2) = (self.eval_to_int(e1)?, self.eval_to_int(e2)?);
+                // the LIR interpreter's default integer semantics.
+                let aop = match op {
+                    ArithOp::Add => ArithmeticOp::Add,
+                    ArithOp::Subtract => ArithmeticOp::Subtract,
+                    ArithOp::Multiply => ArithmeticOp::Multiply,
+                    ArithOp::Divide => ArithmeticOp::Divide,
+                };
+                Ok(Value::Int(ArithmeticMode::Unbounded.arith(aop, n1, n2)?))
+            }
+            Exp::Compare(e1, op, e2) => {
+                let (v1, v2) = (self.eval(e1)?, self.eval(e2)?);
+                compare(*op, v1, v2)
+            }
+            // `and` and `or` short-circuit, and their result is the value of
+            // whichever operand was evaluated last.
+            Exp::And(e1, e2) => {
+                let v1 = self.eval(e1)?;
+                match v1 {
+                    Value::Int(0) => Ok(v1),
+                    Value::Int(_) => self.eval(e2),
+                    v => err(format!("the operands of and must be ints, got {v:?}")),
+                }
+            }
+            Exp::Or(e1, e2) => {
+                let v1 = self.eval(e1)?;
+                match v1 {
+                    Value::Int(0) => self.eval(e2),
+                    Value::Int(_) => Ok(v1),
+                    v => err(format!("the operands of or must be ints, got {v:?}")),
+                }
+            }
+            Exp::ArrayAccess { ptr, index } => {
+                let (ptr, index) = (self.eval(ptr)?, self.eval_to_int(index)?);
+                let address = element(ptr, index)?;
+                self.read(&address)
+            }
+            Exp::FieldAccess { ptr, field } => {
+                let ptr = self.eval(ptr)?;
+                let address = field_of(ptr, field)?;
+                self.read(&address)
+            }
+            Exp::Call { callee, args } => self
+                .call_exp(&**callee, args)?
+                .ok_or_else(|| RuntimeError::new("used the result of a call that returns nothing".into()).into()),
+        }
+    }
+
+    // resolve a name to its value: locals and parameters, then globals, then
+    // internal functions.
+    fn lookup(&self, x: &str) -> Result<Value, Stop> {
+        if let Some(v) = self.env.get(x).or_else(|| self.glob.get(x)) {
+            Ok(v.clone())
+        } else if self.functions.contains_key(x) {
+            Ok(Value::FnPtr(x.to_string()))
+        } else {
+            err(format!("undefined variable {x}"))
+        }
+    }
+
+    // evaluate an lval to the location it denotes.
+    fn place(&mut self, lval: &Lval) -> Result<Place, Stop> {
+        match lval {
+            Lval::Id(x) => Ok(Place::Var(x.clone())),
+            Lval::Deref(ptr) => match self.lval_value(ptr)? {
+                Value::Ptr(address) => Ok(Place::Mem(address)),
+                v => err(format!("can only dereference pointers, got {v:?}")),
+            },
+            Lval::ArrayAccess { ptr, index } => {
+                let ptr = self.lval_value(ptr)?;
+                let index = self.eval_to_int(index)?;
+                Ok(Place::Mem(element(ptr, index)?))
+            }
+            Lval::FieldAccess { ptr, field } => {
+                let ptr = self.lval_value(ptr)?;
+                Ok(Place::Mem(field_of(ptr, field)?))
+            }
+        }
+    }
+
+    // the current value stored at the location an lval denotes.
+    fn lval_value(&mut self, lval: &Lval) -> Result<Value, Stop> {
+        match self.place(lval)? {
+            Place::Var(x) => self.lookup(&x),
+            Place::Mem(address) => self.read(&address),
+        }
+    }
+
+    fn write(&mut self, place: Place, v: Value) -> Result<(), Stop> {
+        match place {
+            Place::Var(x) => {
+                if let Some(existing) = self.env.get_mut(&x).or(self.glob.get_mut(&x)) {
+                    *existing = v;
+                    Ok(())
+                } else {
+                    err(format!("undefined variable: {x}"))
+                }
+            }
+            Place::Mem(address) => {
+                *self.value_ref(&address)? = v;
+                Ok(())
+            }
+        }
+    }
+
+    fn read(&mut self, address: &Address) -> Result<Value, Stop> {
+        self.value_ref(address).cloned()
+    }
+
+    fn value_ref(&mut self, address: &Address) -> Result<&mut Value, Stop> {
+        match address {
+            Address::ToHeap(object, _) if self.freed.contains(object) => {
+                err("use after delete".into())
+            }
+            Address::ToHeap(object, offset) => self
+                .store
+                .get_mut(object)
+                .and_then(|cells| cells.get_mut(*offset as usize))
+                .ok_or_else(|| RuntimeError::new("out-of-bounds access".into()).into()),
+            Address::Field(base, field) => match self.value_ref(base)? {
+                Value::Struct(strukt) => strukt.get_mut(field).ok_or_else(|| {
+                    RuntimeError::new(format!(
+                        "invalid address: the struct at {base:?} does not have the field {field}"
+                    ))
+                    .into()
+                }),
+                _ => err(format!("invalid address: {base:?} does not refer to a struct")),
+            },
+            Address::Nil => err("tried to dereference a null pointer".into()),
+        }
+    }
+}
+
+// things that can be called: expressions in expression position and lvals in
+// statement position.
+trait Callee {
+    // the name of the callee if it is a plain identifier.
+    fn as_id(&self) -> Option<&str>;
+    // the function pointer to call.
+    fn value(&self, e: &mut Evaluator) -> Result<Value, Stop>;
+}
+
+impl Callee for Exp {
+    fn as_id(&self) -> Option<&str> {
+        match self {
+            Exp::Id(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn value(&self, e: &mut Evaluator) -> Result<Value, Stop> {
+        e.eval(self)
+    }
+}
+
+impl Callee for Lval {
+    fn as_id(&self) -> Option<&str> {
+        match self {
+            Lval::Id(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    fn value(&self, e: &mut Evaluator) -> Result<Value, Stop> {
+        e.lval_value(self)
+    }
+}
+
+// the address of `ptr[index]`.
+fn element(ptr: Value, index: i64) -> Result<Address, Stop> {
+    match ptr {
+        Value::Ptr(Address::ToHeap(object, offset)) => u32::try_from(offset as i64 + index)
+            .map(|offset| Address::ToHeap(object, offset))
+            .or_else(|_| err(format!("array index {index} is out of range"))),
+        v => err(format!("can only index arrays, got {v:?}")),
+    }
+}
+
+// the address of `ptr.field`.
+fn field_of(ptr: Value, field: &str) -> Result<Address, Stop> {
+    match ptr {
+        Value::Ptr(address) => Ok(Address::Field(Box::new(address), field.to_string())),
+        v => err(format!("can only access fields via pointers, got {v:?}")),
+    }
+}
+
+fn compare(op: CompareOp, v1: Value, v2: Value) -> Result<Value, Stop> {
+    fn cmp<T: Ord>(op: CompareOp, n1: T, n2: T) -> Value {
+        Value::Int(match op {
+            CompareOp::Equal => n1 == n2,
+            CompareOp::NotEq => n1 != n2,
+            CompareOp::Lt => n1 < n2,
+            CompareOp::Lte => n1 <= n2,
+            CompareOp::Gt => n1 > n2,
+            CompareOp::Gte => n1 >= n2,
+        } as i64)
+    }
+
+    Ok(match (v1, v2) {
+        (Value::Int(n1), Value::Int(n2)) => cmp(op, n1, n2),
+        (Value::Ptr(a1), Value::Ptr(a2)) => {
+            let ordered = !matches!(op, CompareOp::Equal | CompareOp::NotEq);
+            match (a1.object(), a2.object()) {
+                (Some(o1), Some(o2)) if ordered && o1 != o2 => {
+                    err("cannot order pointers into different objects".into())?
+                }
+                _ => cmp(op, a1, a2),
+            }
+        }
+        (Value::Ptr(a1), Value::Int(0)) => cmp(op, a1, Address::Nil),
+        (Value::Int(0), Value::Ptr(a2)) => cmp(op, Address::Nil, a2),
+        (Value::FnPtr(f1), Value::FnPtr(f2)) => cmp(op, f1, f2),
+        (Value::FnPtr(_), Value::Ptr(Address::Nil)) => {
+            Value::Int(matches!(op, CompareOp::Gt | CompareOp::Gte) as i64)
+        }
+        (Value::Ptr(Address::Nil), Value::FnPtr(_)) => {
+            Value::Int(matches!(op, CompareOp::Lt | CompareOp::Lte) as i64)
+        }
+        (v1, v2) => err(format!("comparison is allowed only between ints or between pointers, or between function pointers and nil.\nThe arguments are {v1:?}, {v2:?}"))?,
+    })
+}
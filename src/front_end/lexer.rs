@@ -84,6 +84,10 @@ pub enum TokenKind {
     #[display(fmt = "new")]
     New,
 
+    #[regex("delete")]
+    #[display(fmt = "delete")]
+    Delete,
+
     #[regex("let")]
     #[display(fmt = "let")]
     Let,
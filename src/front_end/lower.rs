@@ -1,6 +1,7 @@
 // lower the AST to Lir. assumes the AST is valid; may panic if it is not.
 
-use std::mem::swap;
+use std::collections::VecDeque;
+use std::mem::{swap, take};
 
 use super::*;
 use crate::middle_end::lir::{self, bb_id, field_id, func_id, struct_id, var_id, LirOp};
@@ -8,21 +9,36 @@ use crate::middle_end::lir::{self, bb_id, field_id, func_id, struct_id, var_id,
 // SECTION: public interface
 
 pub fn lower(ast: &Valid<Program>) -> lir::Program {
+    lower_program(ast, &mut Lowering::new())
+}
+
+// Like `lower`, but also returns the source map of the lowered program (for
+// coverage), given where the statements of the program start.
+pub fn lower_with_source_map(
+    ast: &Valid<Program>,
+    positions: &SourcePositions,
+) -> (lir::Program, SourceMap) {
+    let mut info = Lowering::new();
+    info.source = Some(SourceRecorder::new(positions));
+    let program = lower_program(ast, &mut info);
+    (program, info.source.unwrap().map)
+}
+
+fn lower_program(ast: &Valid<Program>, info: &mut Lowering) -> lir::Program {
     // initialize the variable information data structure with non-function-specific
     // info; everything else will be filled in per-function by lower_functions().
-    let mut info = Lowering::new();
     info.externs = lower_externs(&ast.0.externs);
     info.structs = lower_structs(&ast.0.typedefs);
     info.globals = lower_globals(&ast.0.globals);
 
     // fills in more info.globals info too, so this needs to come before copying
     // info.globals into the Program.
-    let functions = lower_functions(&ast.0.functions, &mut info);
+    let functions = lower_functions(&ast.0.functions, info);
 
     lir::Program {
-        structs: info.structs,
-        globals: info.globals,
-        externs: info.externs,
+        structs: take(&mut info.structs),
+        globals: take(&mut info.globals),
+        externs: take(&mut info.externs),
         functions,
     }
 }
@@ -41,6 +57,7 @@ struct Lowering {
     loop_info: Vec<(lir::BbId, lir::BbId)>,         // stack of loop header and loop exit blocks.
     tmp_ctr: u32,                                   // for generating fresh temporary variables
     bb_ctr: u32,                                    // for generating fresh basic blocks
+    source: Option<SourceRecorder>,                 // for producing a source map
 }
 
 impl Lowering {
@@ -56,6 +73,7 @@ impl Lowering {
             loop_info: vec![],
             tmp_ctr: 0,
             bb_ctr: 0,
+            source: None,
         }
     }
 
@@ -137,6 +155,115 @@ impl Lowering {
     }
 }
 
+// records which blocks the code of each statement goes to, to produce a source
+// map. the code of a statement is what was added to the function's blocks
+// while lowering it, minus the code of the statements nested in it.
+#[derive(Clone, Debug)]
+struct SourceRecorder {
+    positions: Map<String, Vec<StmtPosition>>,
+    // the positions of the current function's statements that haven't been
+    // lowered yet
+    pending: VecDeque<StmtPosition>,
+    // the blocks of the current function that have code of a statement
+    claimed: Set<lir::BbId>,
+    map: SourceMap,
+}
+
+// the number of instructions and the terminal of each block when a statement
+// started being lowered.
+type BlockSizes = Map<lir::BbId, (usize, lir::Terminal)>;
+
+impl SourceRecorder {
+    fn new(positions: &SourcePositions) -> Self {
+        SourceRecorder {
+            positions: positions.functions.clone(),
+            pending: VecDeque::new(),
+            claimed: Set::new(),
+            map: SourceMap::default(),
+        }
+    }
+
+    fn start_function(&mut self, func: &str) {
+        self.pending = self.positions.get(func).cloned().unwrap_or_default().into();
+        self.claimed.clear();
+    }
+
+    // call after lowering the function's body. the parser and the lowering
+    // must agree on the statements, otherwise the map would be shifted.
+    fn end_function(&self, func: &str) {
+        assert!(
+            self.pending.is_empty(),
+            "{} source positions of {func} weren't matched to statements",
+            self.pending.len()
+        );
+    }
+
+    // call instead of lowering statements that are unreachable because an
+    // earlier statement ended the block.
+    fn skip_stmts(&mut self, stmts: &[Stmt]) {
+        let count = stmts.iter().map(count_stmts).sum::<usize>();
+        self.pending.drain(..count.min(self.pending.len()));
+    }
+
+    // call before lowering a statement.
+    fn start_stmt(
+        &mut self,
+        body: &Map<lir::BbId, lir::BasicBlock>,
+    ) -> (StmtPosition, BlockSizes, Set<lir::BbId>) {
+        let sizes = body
+            .iter()
+            .map(|(id, bb)| (id.clone(), (bb.insts.len(), bb.term.clone())))
+            .collect();
+        let position = self
+            .pending
+            .pop_front()
+            .expect("there are more statements than source positions");
+        (position, sizes, self.claimed.clone())
+    }
+
+    // call after lowering the statement with what start_stmt returned.
+    fn end_stmt(
+        &mut self,
+        func: &str,
+        body: &Map<lir::BbId, lir::BasicBlock>,
+        (position, sizes, claimed): (StmtPosition, BlockSizes, Set<lir::BbId>),
+    ) {
+        let changed = body
+            .iter()
+            .filter(|(id, bb)| match sizes.get(*id) {
+                Some((len, term)) => bb.insts.len() > *len || bb.term != *term,
+                None => !bb.insts.is_empty() || !is_sentinel(&bb.term),
+            })
+            .collect::<Vec<_>>();
+
+        // the blocks claimed since the statement started are those of the
+        // statements nested in it.
+        let own = changed
+            .iter()
+            .filter(|(id, _)| claimed.contains(*id) || !self.claimed.contains(*id));
+        for (id, bb) in own {
+            let source = self
+                .map
+                .blocks
+                .entry(func.to_string())
+                .or_default()
+                .entry(id.to_string())
+                .or_default();
+            if !source.stmts.contains(&position.stmt) {
+                source.stmts.push(position.stmt);
+            }
+            if matches!(bb.term, lir::Terminal::Branch { .. }) {
+                source.guard = source.guard.or(position.guard);
+            }
+        }
+        self.claimed.extend(changed.into_iter().map(|(id, _)| id.clone()));
+    }
+}
+
+fn is_sentinel(term: &lir::Terminal) -> bool {
+    matches!(term, lir::Terminal::Jump(bb) if bb.name() == "_SENTINEL")
+}
+
 // add an instruction to the end of the curr_bb basic block.
 fn add_inst(
     body: &mut Map<lir::BbId, lir::BasicBlock>,
@@ -235,6 +362,9 @@ fn lower_functions(functions: &[Function], info: &mut Lowering) -> Map<lir::Func
 
             // initialize info with function-specific information.
             info.curr_func = Some(id.clone());
+            if let Some(source) = &mut info.source {
+                source.start_function(&func.name);
+            }
             info.params = lower_params(&func.params, id.clone());
             info.locals = lower_locals(&func.body.decls, id.clone());
 
@@ -246,6 +376,9 @@ fn lower_functions(functions: &[Function], info: &mut Lowering) -> Map<lir::Func
             let mut body = Map::new();
             let fin = lower_stmts(&stmts, &mut body, bb_id("entry"), info);
             assert!(fin.is_none());
+            if let Some(source) = &info.source {
+                source.end_function(&func.name);
+            }
 
             // guarantee there is a single return statement.
             eliminate_multiple_ret(&mut body, &func.rettyp, info);
@@ -305,19 +438,19 @@ fn lower_stmts(
         },
     );
 
-    // lower each statement in turn.
-    for stmt in stmts {
-        match stmt {
-            Stmt::If { guard, tt, ff } => match lower_if(guard, tt, ff, body, &curr_bb, info) {
-                Some(bb) => curr_bb = bb,
-                None => return None,
-            },
+    // lower each statement in turn. `next` is the basic block to continue in, or
+    // None if the statement ends curr_bb.
+    for (i, stmt) in stmts.iter().enumerate() {
+        let source = info.source.as_mut().map(|source| source.start_stmt(body));
+        let next = match stmt {
+            Stmt::If { guard, tt, ff } => lower_if(guard, tt, ff, body, &curr_bb, info),
             Stmt::While {
                 guard,
                 body: while_body,
-            } => curr_bb = lower_while(guard, while_body, body, &curr_bb, info),
-            Stmt::Assign { lhs, rhs } => curr_bb = lower_assign(lhs, rhs, body, &curr_bb, info),
-            Stmt::Call { callee, args } => curr_bb = lower_call(callee, args, body, &curr_bb, info),
+            } => Some(lower_while(guard, while_body, body, &curr_bb, info)),
+            Stmt::Assign { lhs, rhs } => Some(lower_assign(lhs, rhs, body, &curr_bb, info)),
+            Stmt::Call { callee, args } => Some(lower_call(callee, args, body, &curr_bb, info)),
+            Stmt::Delete(exp) => Some(lower_delete(exp, body, &curr_bb, info)),
             Stmt::Break => {
                 todo!()
             }
@@ -335,9 +468,21 @@ fn lower_stmts(
                         set_terminal(body, &curr_bb, lir::Terminal::Ret(None));
                     }
                 }
-                return None;
+                None
             }
+        };
+        if let (Some(source), Some(recorder)) = (source, &mut info.source) {
+            let func = info.curr_func.as_ref().unwrap().name();
+            recorder.end_stmt(func, body, source);
         }
+        let Some(next) = next else {
+            // the rest of the statements are dead code.
+            if let Some(source) = &mut info.source {
+                source.skip_stmts(&stmts[i + 1..]);
+            }
+            return None;
+        };
+        curr_bb = next;
     }
 
     Some(curr_bb)
@@ -444,6 +589,21 @@ fn lower_call(
     next_bb
 }
 
+// returns the basic block ending the evaluation of the pointer. deleting nil
+// does nothing, so it doesn't need a $free.
+fn lower_delete(
+    exp: &Exp,
+    body: &mut Map<lir::BbId, lir::BasicBlock>,
+    curr_bb: &lir::BbId,
+    info: &mut Lowering,
+) -> lir::BbId {
+    let (op, curr_bb) = lower_exp_to_operand(exp, body, curr_bb, info);
+    if let lir::Operand::Var(src) = op {
+        add_inst(body, &curr_bb, lir::Instruction::Free { src });
+    }
+    curr_bb
+}
+
 // evaluating an expression may require multiple basic blocks if the expression
 // contains a call; we return the final basic block from evaluating the
 // expression along with the final operand.
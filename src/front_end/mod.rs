@@ -3,11 +3,15 @@
 use std::collections::{BTreeMap as Map, BTreeSet as Set};
 
 pub mod ast;
+pub mod coverage;
+pub mod evaluate;
 pub mod lexer;
 pub mod lower;
 pub mod parser;
 
 pub use self::ast::*;
+pub use self::coverage::*;
+pub use self::evaluate::*;
 pub use self::lexer::*;
 pub use self::lower::*;
 pub use self::parser::*;
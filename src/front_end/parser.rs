@@ -15,6 +15,15 @@ pub fn parse(code: &str) -> Result<Program, ParseError> {
     program_r(&mut parser)
 }
 
+// Like `parse`, but also returns where the statements of each function start
+// (for coverage; see `lower_with_source_map`).
+pub fn parse_with_positions(code: &str) -> Result<(Program, SourcePositions), ParseError> {
+    let mut parser = Parser::new(code)?;
+    let program = program_r(&mut parser)?;
+    let positions = SourcePositions::split(parser.positions, &program);
+    Ok((program, positions))
+}
+
 // A parse error with explanatory message.
 #[derive(Clone, Debug, Display, Eq, PartialEq)]
 pub struct ParseError(pub String);
@@ -24,9 +33,10 @@ impl std::error::Error for ParseError {}
 
 #[derive(Clone, Debug)]
 struct Parser<'a> {
-    code: &'a str,      // the source code being parsed
-    tokens: Vec<Token>, // the token stream
-    pos: usize,         // the position in the token stream
+    code: &'a str,                // the source code being parsed
+    tokens: Vec<Token>,           // the token stream
+    pos: usize,                   // the position in the token stream
+    positions: Vec<StmtPosition>, // where the statements parsed so far start
 }
 
 // utility functions for traversing the token stream and creating error
@@ -42,6 +52,7 @@ impl<'a> Parser<'a> {
                 code,
                 tokens,
                 pos: 0,
+                positions: vec![],
             })
         }
     }
@@ -104,6 +115,62 @@ impl<'a> Parser<'a> {
         self.pos >= self.tokens.len()
     }
 
+    // parses a statement with parse_stmt and records where it starts (and
+    // where its guard starts, for `if` and `while`), before the statements
+    // nested in it, in the order described in `SourcePositions`.
+    fn record_stmt(
+        &mut self,
+        parse_stmt: fn(&mut Parser<'a>) -> Result<Stmt, ParseError>,
+    ) -> Result<Stmt, ParseError> {
+        let (start, index) = (self.pos, self.positions.len());
+        let stmt = parse_stmt(self)?;
+        let guard = match stmt {
+            // the guard follows the keyword and the opening parenthesis.
+            Stmt::If { .. } | Stmt::While { .. } => Some(self.source_pos(start + 2)),
+            _ => None,
+        };
+        let position = StmtPosition {
+            stmt: self.source_pos(start),
+            guard,
+        };
+        self.positions.insert(index, position);
+        Ok(stmt)
+    }
+
+    // parses declarations with parse_let and records where each initialized
+    // one starts, as initializations count as statements.
+    #[allow(clippy::type_complexity)]
+    fn record_inits(
+        &mut self,
+        parse_let: fn(&mut Parser<'a>) -> Result<Vec<(Decl, Option<Exp>)>, ParseError>,
+    ) -> Result<Vec<(Decl, Option<Exp>)>, ParseError> {
+        let mut pos = self.pos;
+        let decls = parse_let(self)?;
+        for (decl, init) in &decls {
+            // each declaration starts with its name, followed by a colon.
+            while !(self.tokens[pos].kind == Id
+                && self.code[self.tokens[pos].span.clone()] == decl.name
+                && self.tokens[pos + 1].kind == Colon)
+            {
+                pos += 1;
+            }
+            if init.is_some() {
+                let position = StmtPosition {
+                    stmt: self.source_pos(pos),
+                    guard: None,
+                };
+                self.positions.push(position);
+            }
+            pos += 1;
+        }
+        Ok(decls)
+    }
+
+    // returns the source position of the token at index pos in the token stream.
+    fn source_pos(&self, pos: usize) -> SourcePos {
+        SourcePos::at(self.code, self.tokens[pos].span.start)
+    }
+
     // returns the lexeme of the token immediately prior to the current token.
     fn slice_prev(&self) -> &str {
         &self.code[self.tokens[self.pos - 1].span.clone()]
@@ -249,14 +316,35 @@ fn fundef_r(parser: &mut Parser) -> Result<Function, ParseError> {
 
 // internal variable declaration and possibly initialization.
 fn let_r(parser: &mut Parser) -> Result<Vec<(Decl, Option<Exp>)>, ParseError> {
+    parser.record_inits(unrecorded_let_r)
+}
+
+// let_r, without recording where the initializations start.
+fn unrecorded_let_r(parser: &mut Parser) -> Result<Vec<(Decl, Option<Exp>)>, ParseError> {
     todo!()
 }
 
 // statement.
 fn stmt_r(parser: &mut Parser) -> Result<Stmt, ParseError> {
+    parser.record_stmt(unrecorded_stmt_r)
+}
+
+// stmt_r, without recording where the statement starts; nested statements
+// must be parsed with stmt_r.
+//
+// TODO: dispatch on Delete (see delete_r).
+fn unrecorded_stmt_r(parser: &mut Parser) -> Result<Stmt, ParseError> {
     todo!()
 }
 
+// deallocation statement.
+fn delete_r(parser: &mut Parser) -> Result<Stmt, ParseError> {
+    parser.expect(Delete)?;
+    let exp = exp_r(parser)?;
+    parser.expect(Semicolon)?;
+    Ok(Stmt::Delete(exp))
+}
+
 // conditional statement.
 fn cond_r(parser: &mut Parser) -> Result<Stmt, ParseError> {
     todo!()
@@ -2,6 +2,9 @@
 
 use super::*;
 
+mod arbitrary_tests;
+mod coverage_tests;
+mod differential_tests;
 mod lex_tests;
 mod lower_tests;
 mod parse_tests;
@@ -0,0 +1,31 @@
+// property tests for the random program generator.
+
+use super::*;
+use crate::middle_end::lir;
+use arbitrary::Arbitrary;
+
+#[test]
+fn pretty_print_roundtrip() {
+    arbtest::builder().run(|u| {
+        let program = Program::arbitrary(u)?;
+        let code = program.pretty_print();
+        match parse(&code) {
+            Ok(parsed) => assert_eq!(parsed, program, "Input:\n{code}"),
+            Err(ParseError(err)) => panic!("Parse error:\n{err}\nInput:\n{code}"),
+        }
+        Ok(())
+    });
+}
+
+#[test]
+fn lowering_is_valid() {
+    arbtest::builder().run(|u| {
+        let program = Program::arbitrary(u)?;
+        let code = program.pretty_print();
+        let lowered = lower(&skip_validation(program));
+        if let Err(err) = lir::validate(&lowered) {
+            panic!("The generated LIR program is not valid: {err:?}\nInput:\n{code}");
+        }
+        Ok(())
+    });
+}
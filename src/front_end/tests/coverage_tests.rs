@@ -0,0 +1,370 @@
+// source-level coverage tests.
+
+use super::*;
+use crate::interpreter::{ExternRegistry, Interpreter};
+use crate::middle_end::lir;
+
+const SOURCE: &str = "extern input: () -> int;
+
+fn main() -> int {
+  let n: int, r: int;
+  n = input();
+  if (n < 0) {
+    r = 0 - n;
+  } else {
+    r = n;
+  }
+  while (r > 10) {
+    r = r - 10;
+  }
+  return r;
+}
+";
+
+// SOURCE, lowered by hand.
+const LOWERED: &str = "extern input:() -> int
+
+fn main() -> int {
+let n:int, r:int, _t1:int, _t2:int
+entry:
+  n = $call_ext input()
+  _t1 = $cmp lt n 0
+  $branch _t1 bb1 bb2
+bb1:
+  r = $arith sub 0 n
+  $jump bb3
+bb2:
+  r = $copy n
+  $jump bb3
+bb3:
+  $jump bb4
+bb4:
+  _t2 = $cmp gt r 10
+  $branch _t2 bb5 bb6
+bb5:
+  r = $arith sub r 10
+  $jump bb4
+bb6:
+  $ret r
+}
+";
+
+const SOURCE_MAP: &str = r#"{"blocks": {"main": {
+  "entry": {"stmts": [{"line": 5, "column": 3}, {"line": 6, "column": 3}],
+            "guard": {"line": 6, "column": 7}},
+  "bb1": {"stmts": [{"line": 7, "column": 5}]},
+  "bb2": {"stmts": [{"line": 9, "column": 5}]},
+  "bb4": {"stmts": [{"line": 11, "column": 3}], "guard": {"line": 11, "column": 10}},
+  "bb5": {"stmts": [{"line": 12, "column": 5}]},
+  "bb6": {"stmts": [{"line": 14, "column": 3}]}
+}}}"#;
+
+fn lowered() -> lir::Program {
+    LOWERED.parse().unwrap()
+}
+
+// the coverage of running the program once for each input.
+fn coverage(inputs: &[i64]) -> Coverage {
+    let map = SourceMap::from_json(SOURCE_MAP).unwrap();
+    let mut coverage = Coverage::new(&lowered(), map).unwrap();
+    for &input in inputs {
+        let mut externs = ExternRegistry::new();
+        externs.register("input", func_ty(Some(int_ty()), vec![]), move |_, _| {
+            Ok(Some(input))
+        });
+        let report = Interpreter::new().externs(externs).profile().run(lowered());
+        assert!(report.result.is_ok());
+        coverage.add(&report.profile.unwrap());
+    }
+    coverage
+}
+
+fn pos(line: u32, column: u32) -> SourcePos {
+    SourcePos { line, column }
+}
+
+#[test]
+fn covers_lines_statements_and_branches() {
+    let coverage = coverage(&[5]);
+    assert_eq!(coverage.runs(), 1);
+    assert_eq!(coverage.statements()[&pos(7, 5)], 0);
+    assert_eq!(coverage.statements()[&pos(9, 5)], 1);
+    assert_eq!(
+        coverage.lines().into_iter().collect::<Vec<_>>(),
+        [(5, 1), (6, 1), (7, 0), (9, 1), (11, 1), (12, 0), (14, 1)]
+    );
+    assert_eq!(
+        coverage.branches(),
+        [
+            BranchCoverage {
+                guard: pos(6, 7),
+                func: "main".into(),
+                block: "entry".into(),
+                taken: 0,
+                not_taken: 1,
+            },
+            BranchCoverage {
+                guard: pos(11, 10),
+                func: "main".into(),
+                block: "bb4".into(),
+                taken: 0,
+                not_taken: 1,
+            },
+        ]
+    );
+    assert_eq!(
+        coverage.partial_guards(),
+        Set::from([pos(6, 7), pos(11, 10)])
+    );
+    assert_eq!(
+        coverage.summary(),
+        "lines: 5/7 (71.4%)\nstatements: 5/7 (71.4%)\nbranches: 2/4 (50.0%)\n"
+    );
+}
+
+#[test]
+fn aggregates_runs() {
+    let coverage = coverage(&[5, -25]);
+    assert_eq!(coverage.runs(), 2);
+    assert_eq!(coverage.partial_guards(), Set::new());
+    assert_eq!(
+        coverage.annotate(SOURCE),
+        "        -:    1: extern input: () -> int;
+        -:    2: 
+        -:    3: fn main() -> int {
+        -:    4:   let n: int, r: int;
+        2:    5:   n = input();
+        2:    6:   if (n < 0) {
+                 branch 0 taken 1, not taken 1
+        1:    7:     r = 0 - n;
+        -:    8:   } else {
+        1:    9:     r = n;
+        -:   10:   }
+        4:   11:   while (r > 10) {
+                 branch 0 taken 2, not taken 2
+        2:   12:     r = r - 10;
+        -:   13:   }
+        2:   14:   return r;
+        -:   15: }
+
+lines: 7/7 (100.0%)
+statements: 7/7 (100.0%)
+branches: 4/4 (100.0%)
+"
+    );
+    assert_eq!(
+        coverage.to_lcov("abs.cf"),
+        "TN:
+SF:abs.cf
+FN:5,main
+FNDA:2,main
+FNF:1
+FNH:1
+BRDA:6,0,0,1
+BRDA:6,0,1,1
+BRDA:11,1,0,2
+BRDA:11,1,1,2
+BRF:4
+BRH:4
+DA:5,2
+DA:6,2
+DA:7,1
+DA:9,1
+DA:11,4
+DA:12,2
+DA:14,2
+LF:7
+LH:7
+end_of_record
+"
+    );
+}
+
+#[test]
+fn aggregates_saved_counts() {
+    let counts = CoverageCounts::from_json(&coverage(&[5]).counts().to_json()).unwrap();
+    let mut aggregated = coverage(&[-25]);
+    aggregated.add_counts(&counts).unwrap();
+    assert_eq!(aggregated.runs(), 2);
+    assert_eq!(aggregated.counts(), coverage(&[5, -25]).counts());
+    assert_eq!(
+        aggregated.annotate(SOURCE),
+        coverage(&[5, -25]).annotate(SOURCE)
+    );
+
+    let mut other = CoverageCounts::default();
+    other
+        .blocks
+        .insert("main".into(), Map::from([("bb9".into(), 1)]));
+    assert_eq!(
+        aggregated.add_counts(&other),
+        Err("the coverage counts refer to an unknown block main:bb9".into())
+    );
+}
+
+#[test]
+fn unexecuted_branches_have_no_counts_in_lcov() {
+    let map = SourceMap::from_json(SOURCE_MAP).unwrap();
+    let coverage = Coverage::new(&lowered(), map).unwrap();
+    assert!(coverage
+        .to_lcov("abs.cf")
+        .contains("BRDA:6,0,0,-\nBRDA:6,0,1,-\n"));
+    assert!(coverage.summary().starts_with("lines: 0/7 (0.0%)\n"));
+}
+
+#[test]
+fn source_maps_are_checked() {
+    let check = |json: &str| Coverage::new(&lowered(), SourceMap::from_json(json).unwrap()).err();
+    assert_eq!(
+        check(r#"{"blocks": {"f": {}}}"#),
+        Some("the source map refers to an unknown function f".into())
+    );
+    assert_eq!(
+        check(r#"{"blocks": {"main": {"bb9": {}}}}"#),
+        Some("the source map refers to an unknown block main:bb9".into())
+    );
+    assert_eq!(
+        check(r#"{"blocks": {"main": {"bb1": {"guard": {"line": 6, "column": 7}}}}}"#),
+        Some("the guard at 6:7 is mapped to main:bb1, which ends with $jump bb3 instead of a $branch".into())
+    );
+    assert!(SourceMap::from_json("[]").is_err());
+}
+
+#[test]
+fn positions_count_lines_and_characters_from_one() {
+    let pos = |s: &str| SourcePos::at(SOURCE, SOURCE.find(s).unwrap()).to_string();
+    assert_eq!(pos("extern"), "1:1");
+    assert_eq!(pos("if"), "6:3");
+    assert_eq!(pos("(r > 10)"), "11:9");
+    // columns count characters, not bytes.
+    assert_eq!(SourcePos::at("é = 1;\n  x", 3).to_string(), "1:3");
+    assert_eq!(SourcePos::at("é = 1;\n  x", 10).to_string(), "2:3");
+}
+
+// fn f(p: &int) {
+//   delete p;
+//   return;
+// }
+// fn main() -> int {
+//   return 0;
+// }
+fn deleting() -> Program {
+    let p = Decl {
+        name: "p".into(),
+        typ: ptr_ty(int_ty()),
+    };
+    let function = |name: &str, params, rettyp, stmts| Function {
+        name: name.into(),
+        params,
+        rettyp,
+        body: Body {
+            decls: vec![],
+            stmts,
+        },
+    };
+    Program {
+        globals: vec![],
+        typedefs: vec![],
+        externs: vec![],
+        functions: vec![
+            function(
+                "f",
+                vec![p],
+                None,
+                vec![Stmt::Delete(Exp::Id("p".into())), Stmt::Return(None)],
+            ),
+            function(
+                "main",
+                vec![],
+                Some(int_ty()),
+                vec![Stmt::Return(Some(Exp::Num(0)))],
+            ),
+        ],
+    }
+}
+
+#[test]
+fn lowering_produces_source_maps() {
+    let positions = SourcePositions::from_json(
+        r#"{"functions": {
+  "f": [{"stmt": {"line": 2, "column": 3}}, {"stmt": {"line": 3, "column": 3}}],
+  "main": [{"stmt": {"line": 6, "column": 3}}]
+}}"#,
+    )
+    .unwrap();
+    let (program, map) = lower_with_source_map(&skip_validation(deleting()), &positions);
+    assert_eq!(program, lower(&skip_validation(deleting())));
+    assert_eq!(
+        map.to_json(),
+        SourceMap::from_json(
+            r#"{"blocks": {
+  "f": {"entry": {"stmts": [{"line": 2, "column": 3}, {"line": 3, "column": 3}]}},
+  "main": {"entry": {"stmts": [{"line": 6, "column": 3}]}}
+}}"#
+        )
+        .unwrap()
+        .to_json()
+    );
+
+    let mut coverage = Coverage::new(&program, map).unwrap();
+    coverage.add(&Interpreter::new().profile().run(program).profile.unwrap());
+    assert_eq!(coverage.lines(), Map::from([(2, 0), (3, 0), (6, 1)]));
+}
+
+#[test]
+fn lowering_skips_the_positions_of_dead_code() {
+    let mut program = deleting();
+    let dead = vec![
+        Stmt::While {
+            guard: Exp::Id("p".into()),
+            body: vec![Stmt::Delete(Exp::Id("p".into()))],
+        },
+        Stmt::Delete(Exp::Id("p".into())),
+    ];
+    program.functions[0].body.stmts.extend(dead);
+    program.functions[1]
+        .body
+        .stmts
+        .push(Stmt::Return(Some(Exp::Num(1))));
+    let positions = SourcePositions::from_json(
+        r#"{"functions": {
+  "f": [
+    {"stmt": {"line": 2, "column": 3}},
+    {"stmt": {"line": 3, "column": 3}},
+    {"stmt": {"line": 4, "column": 3}, "guard": {"line": 4, "column": 10}},
+    {"stmt": {"line": 5, "column": 5}},
+    {"stmt": {"line": 7, "column": 3}}
+  ],
+  "main": [{"stmt": {"line": 10, "column": 3}}, {"stmt": {"line": 11, "column": 3}}]
+}}"#,
+    )
+    .unwrap();
+    let (_, map) = lower_with_source_map(&skip_validation(program), &positions);
+    assert_eq!(
+        map.to_json(),
+        SourceMap::from_json(
+            r#"{"blocks": {
+  "f": {"entry": {"stmts": [{"line": 2, "column": 3}, {"line": 3, "column": 3}]}},
+  "main": {"entry": {"stmts": [{"line": 10, "column": 3}]}}
+}}"#
+        )
+        .unwrap()
+        .to_json()
+    );
+}
+
+#[test]
+#[should_panic(expected = "1 source positions of f weren't matched to statements")]
+fn lowering_rejects_positions_of_other_statements() {
+    let positions = SourcePositions::from_json(
+        r#"{"functions": {
+  "f": [
+    {"stmt": {"line": 2, "column": 3}},
+    {"stmt": {"line": 3, "column": 3}},
+    {"stmt": {"line": 4, "column": 3}}
+  ]
+}}"#,
+    )
+    .unwrap();
+    lower_with_source_map(&skip_validation(deleting()), &positions);
+}
@@ -0,0 +1,94 @@
+// differential tests: the AST evaluator and the LIR interpreter running the
+// lowered program must agree on the return value (or the kind of runtime
+// error) and the printed output.
+
+use super::*;
+use crate::commons::skip_validation;
+use crate::interpreter::{interpret_with_output, ExternRegistry, RuntimeError};
+use crate::middle_end::lir;
+use arbitrary::Arbitrary;
+
+// run the program with both interpreters and panic if they disagree. returns
+// what the LIR interpreter returned.
+pub fn run_both(program: Program) -> (Result<i64, RuntimeError>, Vec<String>) {
+    let code = program.pretty_print();
+    let program = skip_validation(program);
+
+    let expected = evaluate_with_output(&program, ExternRegistry::default());
+
+    let lowered = lower(&program);
+    if let Err(err) = lir::validate(&lowered) {
+        panic!("The generated LIR program is not valid: {err:?}\nInput:\n{code}");
+    }
+    let actual = interpret_with_output(lowered, ExternRegistry::default());
+
+    // the engines word their errors differently.
+    let normalize = |(result, output): (Result<i64, RuntimeError>, Vec<String>)| {
+        (result.map_err(|e| (e.kind, category(&e))), output)
+    };
+    assert_eq!(
+        normalize(actual.clone()),
+        normalize(expected),
+        "The lowered program (left) disagrees with the source program (right).\nInput:\n{code}"
+    );
+    actual
+}
+
+// what went wrong in a failed run, in terms both engines' messages share.
+fn category(e: &RuntimeError) -> &'static str {
+    const CATEGORIES: &[(&str, &str)] = &[
+        ("use after", "use after free"),
+        ("double", "double free"),
+        ("invalid free", "invalid free"),
+        ("invalid delete", "invalid free"),
+        ("out-of-bounds", "out of bounds"),
+        ("out of range", "out of bounds"),
+        ("null pointer", "null pointer"),
+        ("division by zero", "division by zero"),
+        ("overflow", "overflow"),
+        ("cannot allocate", "allocation size"),
+        ("cannot order", "pointer order"),
+    ];
+    CATEGORIES
+        .iter()
+        .find(|(needle, _)| e.message.contains(needle))
+        .map_or("other", |(_, category)| category)
+}
+
+#[test]
+fn errors_are_compared_by_category() {
+    let error = |message: &str| RuntimeError::new(message.into());
+    assert_eq!(
+        category(&error("use after delete")),
+        category(&error("use after free"))
+    );
+    assert_eq!(
+        category(&error("array index -1 is out of range")),
+        category(&error("pointer arithmetic out of range: index -1"))
+    );
+    assert_ne!(
+        category(&error("double delete")),
+        category(&error("use after free"))
+    );
+    assert_eq!(category(&error("assertion failed")), "other");
+}
+
+#[test]
+fn generated_programs_evaluate() {
+    // the generator only produces terminating programs without runtime errors.
+    arbtest::builder().run(|u| {
+        let program = Program::arbitrary(u)?;
+        if let (Err(err), _) = evaluate_with_output(&skip_validation(program.clone()), ExternRegistry::default()) {
+            panic!("Runtime error: {err}\nInput:\n{}", program.pretty_print());
+        }
+        Ok(())
+    });
+}
+
+#[test]
+fn generated_programs_agree() {
+    arbtest::builder().run(|u| {
+        let _ = run_both(Program::arbitrary(u)?);
+        Ok(())
+    });
+}
@@ -138,6 +138,15 @@ fn lex_single_token() {
         },]
     );
 
+    let code = "delete";
+    assert_eq!(
+        lex(code),
+        [Token {
+            kind: Delete,
+            span: 0..6
+        },]
+    );
+
     let code = "let";
     assert_eq!(
         lex(code),
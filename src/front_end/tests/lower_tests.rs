@@ -2,8 +2,7 @@
 
 use super::*;
 use crate::commons::skip_validation;
-use crate::middle_end::lir;
-use crate::interpreter::{interpret, RuntimeError};
+use crate::interpreter::RuntimeError;
 
 mod part1_basic;
 mod part1_second_point;
@@ -16,12 +15,11 @@ fn parse_and_validate(code: &str) -> Result<Valid<Program>, String> {
 }
 
 // Parse given program, skip validation, lower to LIR, validate, run and return
-// what `main` returns.
+// what `main` returns.  The result is checked against the AST evaluator.
 fn lower_and_run(code: &str) -> Result<i64, String> {
     let program = parse(code).map_err(|err| err.0)?;
 
-    let lowered = lower(&skip_validation(program));
-    lir::validate(&lowered).expect("The generated LIR program is not valid.");
+    let (result, _) = super::differential_tests::run_both(program);
 
-    interpret(lowered).map_err(|RuntimeError(s)| format!("runtime error: {s}"))
+    result.map_err(|RuntimeError { message, .. }| format!("runtime error: {message}"))
 }
@@ -138,6 +138,20 @@ fn assign() {
     );
 }
 
+#[test]
+fn delete() {
+    parse_and_prettify(
+        r"fn main() -> _ {
+  delete x;
+  delete x.foo[i];
+  delete nil;
+}
+",
+    );
+    assert!(fails_to_parse("fn main() -> _ {\n  delete;\n}\n"));
+    assert!(fails_to_parse("fn main() -> _ {\n  delete x\n}\n"));
+}
+
 #[test]
 fn call() {
     parse_and_prettify(
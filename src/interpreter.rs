@@ -1,31 +1,303 @@
-// LIR interpreter, support everything except external functions.
+// LIR interpreter. external functions are provided by an `ExternRegistry`.
 
 use crate::middle_end::lir::*;
 use derive_more::Display;
-use std::{collections::BTreeMap as Map, mem};
-use Address::ToHeap;
-
-// Interpret given program, return the return value of `main`.
-pub fn interpret(program: Program) -> Result<i64, RuntimeError> {
-    let mut s = State::new(program);
-    loop {
-        if let Some(r) = s.step()? {
-            return Ok(r);
+use std::{collections::BTreeMap as Map, fmt, io, mem, rc::Rc};
+use Address::{ToHeap, ToVar};
+
+mod arithmetic;
+pub mod debugger;
+mod execution;
+mod externs;
+mod gc;
+mod heap;
+mod profile;
+mod trace;
+
+pub use self::arithmetic::*;
+pub use self::execution::*;
+pub use self::externs::*;
+pub use self::gc::*;
+pub use self::heap::*;
+pub use self::profile::*;
+pub use self::trace::*;
+
+// Interpret given program, return the return value of `main`. the program's
+// output goes to stdout.
+pub fn interpret(program: Program, externs: ExternRegistry) -> Result<i64, RuntimeError> {
+    Interpreter::new()
+        .externs(externs)
+        .sink(Sink::Stdout)
+        .run(program)
+        .result
+}
+
+// Interpret given program without printing anything, return the return value of
+// `main` along with the lines printed by the program.
+pub fn interpret_with_output(
+    program: Program,
+    externs: ExternRegistry,
+) -> (Result<i64, RuntimeError>, Vec<String>) {
+    let report = Interpreter::new()
+        .externs(externs)
+        .sink(Sink::Capture)
+        .run(program);
+    (report.result, report.output)
+}
+
+// A configurable interpreter. By default, it uses the standard library of
+// externs and discards the program's output.
+#[derive(Debug, Default)]
+pub struct Interpreter {
+    externs: ExternRegistry,
+    sinks: Vec<Sink>,
+    limits: Limits,
+    arithmetic: ArithmeticMode,
+    tracer: Option<Tracer>,
+    profile: bool,
+    gc_threshold: Option<u64>,
+}
+
+// Where the program's output goes.
+pub enum Sink {
+    // collect the output lines in the execution report
+    Capture,
+    Stdout,
+    Writer(Box<dyn io::Write>),
+}
+
+impl fmt::Debug for Sink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Sink::Capture => write!(f, "Capture"),
+            Sink::Stdout => write!(f, "Stdout"),
+            Sink::Writer(_) => write!(f, "Writer"),
+        }
+    }
+}
+
+// What happened during a run.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExecutionReport {
+    // the return value of `main`, or the error that stopped the program
+    pub result: Result<i64, RuntimeError>,
+    // the output lines, if there is a `Sink::Capture`
+    pub output: Vec<String>,
+    // the number of instructions and terminals executed
+    pub steps: u64,
+    // the largest number of heap cells allocated at any point
+    pub peak_heap_cells: u64,
+    // the objects that were never freed, if `main` returned
+    pub leaks: Vec<Leak>,
+    // the largest number of active function calls, counting `main`
+    pub max_call_depth: usize,
+    // the profile, if profiling was enabled
+    pub profile: Option<Profile>,
+    // what the garbage collector did, if it was enabled
+    pub gc: Option<GcStats>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter::default()
+    }
+
+    // Use the given externs instead of the standard library.
+    pub fn externs(mut self, externs: ExternRegistry) -> Self {
+        self.externs = externs;
+        self
+    }
+
+    // Stop the program with an error when it exceeds the given limits.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    // Use the given integer semantics instead of unbounded 64-bit integers.
+    pub fn arithmetic(mut self, mode: ArithmeticMode) -> Self {
+        self.arithmetic = mode;
+        self
+    }
+
+    // Log the execution with `tracer`.
+    pub fn trace(mut self, tracer: Tracer) -> Self {
+        self.tracer = Some(tracer);
+        self
+    }
+
+    // Collect a profile of the execution.
+    pub fn profile(mut self) -> Self {
+        self.profile = true;
+        self
+    }
+
+    // Collect the heap objects the program can no longer reach whenever an
+    // allocation would take the heap above `threshold` cells.
+    pub fn gc(mut self, threshold: u64) -> Self {
+        self.gc_threshold = Some(threshold);
+        self
+    }
+
+    // Also send the program's output to `sink`.
+    pub fn sink(mut self, sink: Sink) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    pub fn run(self, program: Program) -> ExecutionReport {
+        match self.start(program) {
+            Ok(execution) => execution.finish(),
+            Err(e) => ExecutionReport {
+                result: Err(e),
+                output: vec![],
+                steps: 0,
+                peak_heap_cells: 0,
+                leaks: vec![],
+                max_call_depth: 0,
+                profile: None,
+                gc: None,
+            },
         }
     }
+
+    // Prepare to execute the program step by step. Fails if the program's
+    // externs don't match the registry.
+    pub fn start(self, program: Program) -> Result<Execution, RuntimeError> {
+        self.externs.check(&program.externs)?;
+        let mut s = State::new(program, self.externs, Host::new(self.sinks));
+        s.limits = self.limits;
+        s.arithmetic = self.arithmetic;
+        s.tracer = self.tracer;
+        s.profiler = self.profile.then(Profiler::default);
+        s.gc = self.gc_threshold.map(Collector::new);
+        s.trace_start()?;
+        Ok(Execution::new(s))
+    }
 }
 
 // A runtime error with explanatory message.
-#[derive(Clone, Debug, Display, Eq, PartialEq)]
-pub struct RuntimeError(pub String);
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RuntimeError {
+    pub kind: RuntimeErrorKind,
+    pub message: String,
+    // where the heap objects the error is about were allocated
+    pub allocations: Vec<AllocSite>,
+}
 impl std::error::Error for RuntimeError {}
 
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        match &self.allocations[..] {
+            [] => Ok(()),
+            [site] => write!(f, " (the object was allocated by {site})"),
+            sites => {
+                let sites = sites.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+                write!(f, " (the objects were allocated by {})", sites.join(" and "))
+            }
+        }
+    }
+}
+
+// The `$alloc` instruction that created a heap object, and where it is.
+#[derive(Clone, Debug, Display, Eq, PartialEq)]
+#[display(fmt = "`{inst}` at {at}")]
+pub struct AllocSite {
+    pub inst: Instruction,
+    pub at: Location,
+}
+
+impl AllocSite {
+    // The allocation-site id of the instruction (e.g., `_a1`).
+    pub fn id(&self) -> &VarId {
+        match &self.inst {
+            Instruction::Alloc { id, .. } => id,
+            inst => unreachable!("{inst} is not an $alloc"),
+        }
+    }
+}
+
+// The objects allocated by one `$alloc` that were still allocated when `main`
+// returned.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Leak {
+    pub site: AllocSite,
+    pub objects: u64,
+    pub cells: u64,
+}
+
+impl fmt::Display for Leak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let objects = match self.objects {
+            1 => "1 object".to_string(),
+            n => format!("{n} objects"),
+        };
+        write!(
+            f,
+            "{objects} ({} cells) allocated by {} never freed",
+            self.cells, self.site
+        )
+    }
+}
+
+// What kind of runtime error happened. The limits are reported as distinct
+// kinds so that callers can tell a diverging program from a faulty one.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RuntimeErrorKind {
+    // the program did something wrong
+    Fault,
+    // the program ran for more steps than allowed
+    StepLimit,
+    // the call stack grew deeper than allowed
+    DepthLimit,
+    // the program allocated more heap cells than allowed
+    HeapLimit,
+}
+
+impl RuntimeError {
+    pub fn new(message: String) -> Self {
+        RuntimeError {
+            kind: RuntimeErrorKind::Fault,
+            message,
+            allocations: vec![],
+        }
+    }
+
+    fn limit(kind: RuntimeErrorKind, message: String) -> Self {
+        RuntimeError {
+            kind,
+            message,
+            allocations: vec![],
+        }
+    }
+
+    fn allocated_by(mut self, site: &AllocSite) -> Self {
+        self.allocations.push(site.clone());
+        self
+    }
+}
+
+// Resource limits for a run; `None` means unlimited.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Limits {
+    // the number of instructions and terminals that may be executed
+    pub max_steps: Option<u64>,
+    // the number of function calls that may be active at once, counting `main`
+    pub max_depth: Option<usize>,
+    // the number of heap cells that may be allocated at once
+    pub max_heap_cells: Option<u64>,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 enum Address {
     // null pointer
     Nil,
-    // addresses to arrays on the heap
-    ToHeap(u32),
+    // the element at an offset in a heap object
+    ToHeap(u32, u32),
+    // a variable at an offset: a local of the call with the given frame number,
+    // or a global. variables hold a single element.
+    ToVar(Option<u64>, VarId, u32),
     // addresses to a field of an object
     Field(Box<Address>, FieldId),
 }
@@ -40,13 +312,26 @@ enum Value {
     Struct(Map<FieldId, Box<Value>>),
 }
 
+// heap objects, each created by an `$alloc`. the type of the elements is the
+// type of the allocation-site id. freed objects are kept (without their cells)
+// to detect accesses through dangling pointers.
+#[derive(Debug)]
+struct Object {
+    cells: Vec<Value>,
+    site: Rc<AllocSite>,
+    freed: bool,
+}
+
 // call sites for returning
 #[derive(Debug)]
 struct CallSite {
+    // the block that made the call
+    from: BbId,
     next: BasicBlock,
     dst: Option<VarId>,
     env: Map<VarId, Value>,
     func: FuncId,
+    frame: u64,
 }
 
 // Interpreter state. This is a CESK machine
@@ -56,26 +341,55 @@ struct State {
     program: Program,
     // current basic block
     control: BasicBlock,
+    // index of the next instruction in the current basic block; the terminal
+    // comes after the instructions
+    pc: usize,
     // current function
     func: FuncId,
+    // the frame number of the current call; pointers to locals refer to it
+    frame: u64,
+    // next available frame number
+    next_frame: u64,
     // current environment
     env: Map<VarId, Value>,
     // global environment
     glob: Map<VarId, Value>,
-    // store/heap
-    store: Map<u32, Value>,
+    // store/heap, by object
+    store: Map<u32, Object>,
     // call stack
     stack: Vec<CallSite>,
-    // next available heap address
-    next_address: u32,
+    // next available heap object
+    next_object: u32,
+    // external functions
+    externs: ExternRegistry,
+    // printed output and other state shared with external functions
+    host: Host,
+    // number of instructions and terminals executed so far
+    steps: u64,
+    // number of heap cells currently allocated
+    heap_cells: u64,
+    // the largest number of heap cells allocated at any point
+    peak_heap_cells: u64,
+    // the deepest the call stack has been, counting `main`
+    max_depth: usize,
+    // resource limits
+    limits: Limits,
+    // integer semantics
+    arithmetic: ArithmeticMode,
+    // where the execution is logged, if anywhere
+    tracer: Option<Tracer>,
+    // collects the profile, if profiling
+    profiler: Option<Profiler>,
+    // the garbage collector, if enabled
+    gc: Option<Collector>,
 }
 
 fn err<T>(msg: String) -> Result<T, RuntimeError> {
-    Err(RuntimeError(msg))
+    Err(RuntimeError::new(msg))
 }
 
 impl State {
-    pub fn new(program: Program) -> State {
+    pub fn new(program: Program, externs: ExternRegistry, host: Host) -> State {
         let main = func_id("main");
         let control = program.functions[&main].body[&bb_id("entry")].clone();
 
@@ -94,13 +408,27 @@ impl State {
 
         let mut state = State {
             control,
+            pc: 0,
             program,
             env: Map::new(),
             glob: Map::new(),
             store: Map::new(),
             stack: vec![],
             func: func_id("main"),
-            next_address: 1,
+            frame: 0,
+            next_frame: 1,
+            next_object: 1,
+            externs,
+            host,
+            steps: 0,
+            heap_cells: 0,
+            peak_heap_cells: 0,
+            max_depth: 1,
+            limits: Limits::default(),
+            arithmetic: ArithmeticMode::default(),
+            tracer: None,
+            profiler: None,
+            gc: None,
         };
 
         let globals = state
@@ -125,27 +453,118 @@ impl State {
             .collect()
     }
 
-    // Take a step: execute a whole basic block.  Returns Some(main's return
+    // Execute the next instruction or terminal.  Returns Some(main's return
     // value) if this is the final step.
-    pub fn step(&mut self) -> Result<Option<i64>, RuntimeError> {
-        for inst in self.control.insts.clone() {
-            self.execute_inst(inst)?;
+    pub fn step_inst(&mut self) -> Result<Option<i64>, RuntimeError> {
+        self.count_step()?;
+        self.profile_step();
+        if self.tracer.is_some() {
+            return self.step_traced();
         }
+        self.execute_next()
+    }
 
-        self.execute_terminal()
+    fn execute_next(&mut self) -> Result<Option<i64>, RuntimeError> {
+        match self.control.insts.get(self.pc).cloned() {
+            Some(inst) => {
+                self.execute_inst(inst)?;
+                self.pc += 1;
+                Ok(self.host.exit)
+            }
+            None => {
+                let r = self.execute_terminal()?;
+                self.pc = 0;
+                Ok(r)
+            }
+        }
     }
 
-    fn alloc_array(&mut self, n: u32, typ: &Type) -> Address {
-        let a = self.next_address;
-        self.next_address += n.max(1); // make sure that each address is unique.
+    fn count_step(&mut self) -> Result<(), RuntimeError> {
+        if let Some(max) = self.limits.max_steps.filter(|max| self.steps >= *max) {
+            return Err(RuntimeError::limit(
+                RuntimeErrorKind::StepLimit,
+                format!("exceeded the limit of {max} steps"),
+            ));
+        }
+        self.steps += 1;
+        Ok(())
+    }
+
+    fn alloc_array(
+        &mut self,
+        n: u32,
+        typ: &Type,
+        site: AllocSite,
+    ) -> Result<Address, RuntimeError> {
+        self.maybe_collect(n);
+        if let Some(max) = self.limits.max_heap_cells {
+            if self.heap_cells + n as u64 > max {
+                return Err(RuntimeError::limit(
+                    RuntimeErrorKind::HeapLimit,
+                    format!(
+                        "exceeded the limit of {max} heap cells: allocating {n} cells with {} cells already allocated",
+                        self.heap_cells
+                    ),
+                ));
+            }
+        }
+        let object = self.next_object;
+        self.next_object += 1;
+        self.heap_cells += n as u64;
+        self.peak_heap_cells = self.peak_heap_cells.max(self.heap_cells);
 
         let zero_initialized_value = self.zero_init(typ);
+        self.store.insert(
+            object,
+            Object {
+                cells: vec![zero_initialized_value; n as usize],
+                site: Rc::new(site),
+                freed: false,
+            },
+        );
+
+        Ok(ToHeap(object, 0))
+    }
 
-        for i in a..(a + n) {
-            self.store.insert(i, zero_initialized_value.clone());
+    // free the heap object `address` is the start of. freeing nil does nothing.
+    fn free(&mut self, address: Address) -> Result<(), RuntimeError> {
+        let object = match address {
+            Address::Nil => return Ok(()),
+            ToHeap(object, 0) => object,
+            ToHeap(object, _) => {
+                let message = "invalid free: the pointer points into the middle of an object";
+                return Err(self.object_error(object, message.into()));
+            }
+            _ => return err("invalid free: the pointer doesn't point to a heap object".into()),
+        };
+        let Some(o) = self.store.get_mut(&object) else {
+            return err(format!("invalid address: there is no heap object {object}"));
+        };
+        if o.freed {
+            return Err(RuntimeError::new("double free".into()).allocated_by(&o.site));
         }
+        o.freed = true;
+        self.heap_cells -= mem::take(&mut o.cells).len() as u64;
+        Ok(())
+    }
 
-        ToHeap(a)
+    // the objects that haven't been freed, grouped by allocation site. this
+    // includes the ones the garbage collector collected.
+    fn leaks(&self) -> Vec<Leak> {
+        let mut leaks = match &self.gc {
+            Some(gc) => gc.leaks.clone(),
+            None => Map::new(),
+        };
+        for object in self.store.values().filter(|o| !o.freed) {
+            let leak = leaks.entry(object.site.id().clone()).or_insert_with(|| Leak {
+                site: (*object.site).clone(),
+                objects: 0,
+                cells: 0,
+            });
+            leak.objects += 1;
+            leak.cells += object.cells.len() as u64;
+        }
+        leaks.into_values().collect()
     }
 
     fn bind(&mut self, x: VarId, v: Value) -> Result<(), RuntimeError> {
@@ -173,60 +592,56 @@ impl State {
         use Value::Ptr;
 
         match inst {
-            AddrOf { .. } => {
-                unimplemented!("cs414 subset of C♭ doesn't need to emit $addrof")
+            AddrOf { lhs, rhs } => {
+                let frame = if self.env.contains_key(&rhs) {
+                    Some(self.frame)
+                } else if self.glob.contains_key(&rhs) {
+                    None
+                } else {
+                    return err(format!("undefined variable {rhs}"));
+                };
+                self.bind(lhs, Ptr(ToVar(frame, rhs, 0)))?;
             }
             Alloc { lhs, num, id } => {
                 let n = match self.eval_to_int(&num)? {
-                    n if n >= 0 => n as u32,
-                    _ => return err("cannot allocate a negative number of elements".into()),
+                    n if n < 0 => return err("cannot allocate a negative number of elements".into()),
+                    n => u32::try_from(n).or_else(|_| err(format!("cannot allocate {n} elements")))?,
                 };
-                let a = self.alloc_array(n, &id.typ());
+                let site = AllocSite {
+                    at: self.location(),
+                    inst: Alloc {
+                        lhs: lhs.clone(),
+                        num,
+                        id: id.clone(),
+                    },
+                };
+                let a = self.alloc_array(n, &id.typ(), site)?;
                 self.bind(lhs, Ptr(a))?;
             }
             Arith { lhs, aop, op1, op2 } => {
                 let (n1, n2) = (self.eval_to_int(&op1)?, self.eval_to_int(&op2)?);
-                self.bind(
-                    lhs,
-                    Value::Int(match aop {
-                        ArithmeticOp::Add => n1 + n2,
-                        ArithmeticOp::Subtract => n1 - n2,
-                        ArithmeticOp::Multiply => n1 * n2,
-                        ArithmeticOp::Divide if n2 == 0 => {
-                            return err("division by zero".into())
-                        }
-                        ArithmeticOp::Divide => n1 / n2,
-                    }),
-                )?;
+                self.bind(lhs, Value::Int(self.arithmetic.arith(aop, n1, n2)?))?;
             }
             CallExt {
                 lhs,
                 ext_callee,
                 args,
             } => {
-                let int_args = args
-                    .iter()
-                    .map(|a| self.eval_to_int(a))
-                    .collect::<Result<Vec<_>, _>>();
                 let args = args
                     .iter()
-                    .map(|a| self.eval(a))
+                    .map(|a| self.eval_to_int(a))
                     .collect::<Result<Vec<_>, _>>()?;
-                match (ext_callee.name(), args.len(), lhs.is_some()) {
-                ("print", 1, false) => {
-                    println!("{}", int_args?[0]);
-                },
-                ("isPythagorean", 3, true) => {
-                    let x = int_args?;
-                    self.bind(lhs.unwrap(), Value::Int((x[0] * x[0] + x[1] * x[1] == x[2] * x[2]) as i64))?;
-                },
-                _ => err("the interpreter supports only these external functions: print: (int) -> _, isPythagorean: (int, int, int) -> int".into())?,
-            }
+                let result = self.externs.call(ext_callee.name(), &mut self.host, &args)?;
+                if let (Some(lhs), Some(n)) = (lhs, result) {
+                    self.bind(lhs, Value::Int(self.arithmetic.int(n)?))?;
+                }
             }
             Cmp { lhs, rop, op1, op2 } => {
                 let result = match (self.eval(&op1)?, self.eval(&op2)?) {
-                    (Value::Int(n1), Value::Int(n2)) => compare(rop, n1, n2),
-                    (Value::Ptr(a1), Value::Ptr(a2)) => compare(rop, a1, a2),
+                    (Value::Int(n1), Value::Int(n2)) => {
+                        compare(rop, self.arithmetic.int(n1)?, self.arithmetic.int(n2)?)
+                    }
+                    (Value::Ptr(a1), Value::Ptr(a2)) => self.compare_pointers(rop, a1, a2)?,
                     (Value::Ptr(a1), Value::Int(0)) => compare(rop, a1, Address::Nil),
                     (Value::Int(0), Value::Ptr(a2)) => compare(rop, Address::Nil, a2),
                     (Value::FnPtr(a1), Value::FnPtr(a2)) => compare(rop, a1, a2),
@@ -239,13 +654,27 @@ impl State {
             Copy { lhs, op } => {
                 self.bind(lhs, self.eval(&op)?)?;
             }
+            Free { src } => match self.lookup(&src)? {
+                Ptr(address) => self.free(address)?,
+                v => err(format!("expected a pointer in $free, got {v:?}"))?,
+            },
             Gep { lhs, src, idx } => {
                 let i = self.eval_to_int(&idx)?;
                 match self.lookup(&src)? {
-                    Ptr(ToHeap(address)) => {
-                        self.bind(lhs, Ptr(ToHeap(address + i as u32)))?;
+                    Ptr(ToHeap(object, offset)) => {
+                        let offset = u32::try_from(offset as i64 + i).map_err(|_| {
+                            let message = format!("pointer arithmetic out of range: index {i}");
+                            self.object_error(object, message)
+                        })?;
+                        self.bind(lhs, Ptr(ToHeap(object, offset)))?;
                     }
-                    v => err(format!("src in $gep must be a heap pointer, got {v:?}"))?,
+                    Ptr(ToVar(frame, x, offset)) => {
+                        let offset = u32::try_from(offset as i64 + i).or_else(|_| {
+                            err(format!("pointer arithmetic out of range: index {i}"))
+                        })?;
+                        self.bind(lhs, Ptr(ToVar(frame, x, offset)))?;
+                    }
+                    v => err(format!("src in $gep must be a heap or variable pointer, got {v:?}"))?,
                 }
             }
             Gfp { lhs, src, field } => match self.lookup(&src)? {
@@ -312,14 +741,17 @@ impl State {
                     dst,
                     env,
                     func,
+                    frame,
+                    ..
                 } = self
                     .stack
                     .pop()
-                    .ok_or(RuntimeError("there is no callee to return to".to_owned()))?;
+                    .ok_or(RuntimeError::new("there is no callee to return to".to_owned()))?;
                 assert!(dst.is_none());
                 self.control = next;
                 self.env = env;
                 self.func = func;
+                self.frame = frame;
                 Ok(None)
             }
             Terminal::Ret(Some(e)) => {
@@ -329,10 +761,13 @@ impl State {
                     dst,
                     env,
                     func,
+                    frame,
+                    ..
                 }) = self.stack.pop()
                 {
                     self.env = env;
                     self.func = func;
+                    self.frame = frame;
                     self.control = next;
                     if let Some(dst) = dst {
                         self.bind(dst, v)?;
@@ -353,7 +788,7 @@ impl State {
 
     fn eval_to_int(&self, op: &Operand) -> Result<i64, RuntimeError> {
         match self.eval(op)? {
-            Value::Int(n) => Ok(n),
+            Value::Int(n) => self.arithmetic.int(n),
             v => err(format!("Expected int when evaluating {op:?}, got {v:?}")),
         }
     }
@@ -370,7 +805,7 @@ impl State {
             .get(x)
             .or_else(|| self.glob.get(x))
             .cloned()
-            .ok_or_else(|| RuntimeError(format!("undefined variable {x}")))
+            .ok_or_else(|| RuntimeError::new(format!("undefined variable {x}")))
     }
 
     fn zero_init(&self, typ: &Type) -> Value {
@@ -395,10 +830,43 @@ impl State {
 
     fn value_ref(&mut self, address: &Address) -> Result<&mut Value, RuntimeError> {
         match address {
-            ToHeap(a) => self.store.get_mut(a).ok_or_else(|| RuntimeError("out-of-bounds access".into())),
+            ToHeap(object, offset) => {
+                let Some(object) = self.store.get_mut(object) else {
+                    return err(format!("invalid address: there is no heap object {object}"));
+                };
+                if object.freed {
+                    let e = RuntimeError::new("use after free".into());
+                    return Err(e.allocated_by(&object.site));
+                }
+                if *offset as usize >= object.cells.len() {
+                    let e = RuntimeError::new("out-of-bounds access".into());
+                    return Err(e.allocated_by(&object.site));
+                }
+                Ok(&mut object.cells[*offset as usize])
+            }
+            ToVar(frame, x, offset) => {
+                let env = match frame {
+                    None => &mut self.glob,
+                    Some(frame) if *frame == self.frame => &mut self.env,
+                    Some(frame) => match self.stack.iter_mut().find(|site| site.frame == *frame) {
+                        Some(site) => &mut site.env,
+                        None => {
+                            let func = x.scope().map_or("?".to_string(), |f| f.to_string());
+                            return err(format!(
+                                "dangling pointer: {x} is a local of a call to {func} that has returned"
+                            ));
+                        }
+                    },
+                };
+                if *offset != 0 {
+                    return err(format!("out-of-bounds access: {x} is a variable, not an array"));
+                }
+                env.get_mut(x)
+                    .ok_or_else(|| RuntimeError::new(format!("undefined variable {x}")))
+            }
             Address::Field(base, field) => match self.value_ref(base)? {
                 Value::Struct(strukt) => strukt.get_mut(field).map(Box::as_mut).ok_or_else(|| {
-                    RuntimeError(format!(
+                    RuntimeError::new(format!(
                         "invalid address: the struct at {base:?} does not have the field {field}"
                     ))
                 }),
@@ -410,6 +878,47 @@ impl State {
         }
     }
 
+    // an error about the heap object `object`, naming the instruction that
+    // allocated it.
+    fn object_error(&self, object: u32, message: String) -> RuntimeError {
+        let e = RuntimeError::new(message);
+        match self.store.get(&object) {
+            Some(object) => e.allocated_by(&object.site),
+            None => e,
+        }
+    }
+
+    // pointers into different objects can be compared for equality, but not
+    // ordered.
+    fn compare_pointers(
+        &self,
+        rop: ComparisonOp,
+        a1: Address,
+        a2: Address,
+    ) -> Result<Value, RuntimeError> {
+        let ordered = !matches!(rop, ComparisonOp::Eq | ComparisonOp::Neq);
+        match (a1.base(), a2.base()) {
+            (Some(b1), Some(b2)) if ordered && b1 != b2 => {
+                let mut e = RuntimeError::new("cannot order pointers into different objects".into());
+                for base in [b1, b2] {
+                    if let Some(object) = base.object().and_then(|o| self.store.get(&o)) {
+                        e = e.allocated_by(&object.site);
+                    }
+                }
+                Err(e)
+            }
+            _ => Ok(compare(rop, a1, a2)),
+        }
+    }
+
+    fn location(&self) -> Location {
+        Location {
+            func: self.func.clone(),
+            bb: self.control.id.clone(),
+            index: self.pc,
+        }
+    }
+
     fn call(
         &mut self,
         lhs: &Option<VarId>,
@@ -417,6 +926,13 @@ impl State {
         args: &[Operand],
         next_bb: &BbId,
     ) -> Result<Option<i64>, RuntimeError> {
+        let depth = self.stack.len() + 2;
+        if let Some(max) = self.limits.max_depth.filter(|max| depth > *max) {
+            return Err(RuntimeError::limit(
+                RuntimeErrorKind::DepthLimit,
+                format!("exceeded the limit of {max} active calls when calling {callee}"),
+            ));
+        }
         let mut new_env = self.new_env(callee);
         // initialize the arguments
         let params = &self.program.functions[&callee].params;
@@ -426,16 +942,40 @@ impl State {
         }
 
         self.stack.push(CallSite {
+            from: self.control.id.clone(),
             next: self.program.functions[&self.func].body[next_bb].clone(),
             dst: lhs.clone(),
             env: mem::replace(&mut self.env, new_env),
             func: mem::replace(&mut self.func, callee.clone()),
+            frame: mem::replace(&mut self.frame, self.next_frame),
         });
+        self.next_frame += 1;
+        self.max_depth = self.max_depth.max(depth);
         self.control = self.program.functions[callee].body[&bb_id("entry")].clone();
         Ok(None)
     }
 }
 
+impl Address {
+    // the start of the heap object or variable the address is in, if any.
+    fn base(&self) -> Option<Address> {
+        match self {
+            Address::Nil => None,
+            ToHeap(object, _) => Some(ToHeap(*object, 0)),
+            ToVar(frame, x, _) => Some(ToVar(*frame, x.clone(), 0)),
+            Address::Field(base, _) => base.base(),
+        }
+    }
+
+    // the heap object the address is in, if any.
+    fn object(&self) -> Option<u32> {
+        match self.base()? {
+            ToHeap(object, _) => Some(object),
+            _ => None,
+        }
+    }
+}
+
 fn compare<T: Ord>(rop: ComparisonOp, n1: T, n2: T) -> Value {
     Value::Int(match rop {
         ComparisonOp::Eq => n1 == n2,
@@ -446,3 +986,6 @@ fn compare<T: Ord>(rop: ComparisonOp, n1: T, n2: T) -> Value {
         ComparisonOp::GreaterEq => n1 >= n2,
     } as i64)
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,88 @@
+// integer semantics of the interpreter.
+//
+// C♭ and LIR integer constants are 32 bits wide, but the interpreter stores
+// integers as i64. the arithmetic mode decides what happens when a result
+// doesn't fit in 32 bits: it can wrap around like a native backend would, be
+// reported as a runtime error, or be kept as is (up to 64 bits).
+
+use super::*;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Default, Display, Eq, PartialEq)]
+pub enum ArithmeticMode {
+    // two's-complement 32-bit integers that wrap around on overflow
+    #[display(fmt = "wrapping")]
+    Wrapping,
+    // 32-bit integers; overflow is a runtime error
+    #[display(fmt = "trapping")]
+    Trapping,
+    // 64-bit integers; overflow is a runtime error
+    #[default]
+    #[display(fmt = "unbounded")]
+    Unbounded,
+}
+
+impl FromStr for ArithmeticMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wrapping" => Ok(ArithmeticMode::Wrapping),
+            "trapping" => Ok(ArithmeticMode::Trapping),
+            "unbounded" => Ok(ArithmeticMode::Unbounded),
+            _ => Err(format!(
+                "unknown arithmetic mode {s}; expected wrapping, trapping or unbounded"
+            )),
+        }
+    }
+}
+
+impl ArithmeticMode {
+    // bring an integer coming from outside the mode's arithmetic (e.g., an
+    // extern's result) into the mode's range.
+    pub fn int(self, n: i64) -> Result<i64, RuntimeError> {
+        match self {
+            ArithmeticMode::Wrapping => Ok(n as i32 as i64),
+            ArithmeticMode::Trapping => i32::try_from(n)
+                .map(i64::from)
+                .or_else(|_| err(format!("integer overflow: {n} doesn't fit in 32 bits"))),
+            ArithmeticMode::Unbounded => Ok(n),
+        }
+    }
+
+    pub fn arith(self, aop: ArithmeticOp, n1: i64, n2: i64) -> Result<i64, RuntimeError> {
+        use ArithmeticOp::*;
+
+        if aop == Divide && n2 == 0 {
+            return err("division by zero".into());
+        }
+        let result = match self {
+            ArithmeticMode::Wrapping => {
+                let (n1, n2) = (n1 as i32, n2 as i32);
+                Some(match aop {
+                    Add => n1.wrapping_add(n2),
+                    Subtract => n1.wrapping_sub(n2),
+                    Multiply => n1.wrapping_mul(n2),
+                    Divide => n1.wrapping_div(n2),
+                } as i64)
+            }
+            ArithmeticMode::Trapping => {
+                let (n1, n2) = (self.int(n1)? as i32, self.int(n2)? as i32);
+                match aop {
+                    Add => n1.checked_add(n2),
+                    Subtract => n1.checked_sub(n2),
+                    Multiply => n1.checked_mul(n2),
+                    Divide => n1.checked_div(n2),
+                }
+                .map(i64::from)
+            }
+            ArithmeticMode::Unbounded => match aop {
+                Add => n1.checked_add(n2),
+                Subtract => n1.checked_sub(n2),
+                Multiply => n1.checked_mul(n2),
+                Divide => n1.checked_div(n2),
+            },
+        };
+        result.ok_or_else(|| RuntimeError::new(format!("integer overflow: $arith {aop} {n1} {n2}")))
+    }
+}
@@ -0,0 +1,190 @@
+// an interactive debugger for LIR programs, driven by commands read line by
+// line. the program's own output goes to its sinks, the debugger's to `out`.
+
+use super::*;
+use std::collections::BTreeSet as Set;
+use std::io::{BufRead, Write};
+
+const HELP: &str = "\
+break <func>[:<bb>]     (b)  stop when entering a function or a block
+delete <func>[:<bb>]         remove a breakpoint
+step                    (s)  execute one instruction
+next                    (n)  execute one instruction, stepping over calls
+finish                  (f)  run until the current function returns
+continue                (c)  run until a breakpoint or the end
+locals                  (l)  print the local variables
+globals                 (g)  print the global variables
+print <var>             (p)  print a variable
+deref <var> [<count>]   (d)  print the heap cells a pointer points to
+heap <file> [json|dot]       write the reachable heap objects to a file
+backtrace               (bt) print the active calls
+where                   (w)  print the current location
+help                    (h)  print this message
+quit                    (q)  stop debugging";
+
+// Debug the program, reading commands from `input` until it runs out or the
+// user quits.
+pub fn debug(execution: &mut Execution, input: impl BufRead, out: impl Write) -> io::Result<()> {
+    let mut debugger = Debugger {
+        execution,
+        breakpoints: Set::new(),
+        out,
+    };
+    debugger.show_location()?;
+    write!(debugger.out, "(liri) ")?;
+    debugger.out.flush()?;
+    for line in input.lines() {
+        let line = line?;
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        if let ["quit" | "q"] = words[..] {
+            break;
+        }
+        debugger.command(&words)?;
+        write!(debugger.out, "(liri) ")?;
+        debugger.out.flush()?;
+    }
+    writeln!(debugger.out)
+}
+
+struct Debugger<'a, W> {
+    execution: &'a mut Execution,
+    // (function, block) pairs
+    breakpoints: Set<(String, String)>,
+    out: W,
+}
+
+impl<W: Write> Debugger<'_, W> {
+    fn command(&mut self, words: &[&str]) -> io::Result<()> {
+        match words {
+            [] => Ok(()),
+            ["break" | "b", at] => {
+                let at = breakpoint(at);
+                writeln!(self.out, "breakpoint at {}:{}", at.0, at.1)?;
+                self.breakpoints.insert(at);
+                Ok(())
+            }
+            ["delete", at] => {
+                if !self.breakpoints.remove(&breakpoint(at)) {
+                    writeln!(self.out, "there is no breakpoint at {at}")?;
+                }
+                Ok(())
+            }
+            ["step" | "s"] => self.run_until(|_| true),
+            ["next" | "n"] => {
+                let depth = self.execution.depth();
+                self.run_until(|e| e.depth() <= depth)
+            }
+            ["finish" | "f"] => {
+                let depth = self.execution.depth();
+                self.run_until(|e| e.depth() < depth)
+            }
+            ["continue" | "c"] => self.run_until(|_| false),
+            ["locals" | "l"] => self.show_vars(self.execution.locals()),
+            ["globals" | "g"] => self.show_vars(self.execution.globals()),
+            ["print" | "p", name] => match self.execution.value(name) {
+                Some(v) => writeln!(self.out, "{name} = {v}"),
+                None => writeln!(self.out, "undefined variable {name}"),
+            },
+            ["deref" | "d", name, rest @ ..] => {
+                let count = match rest {
+                    [] => Ok(1),
+                    [n] => n.parse::<usize>(),
+                    _ => return self.unknown(words),
+                };
+                let Ok(count) = count else {
+                    return self.unknown(words);
+                };
+                match self.execution.deref(name, count) {
+                    Ok(values) => writeln!(self.out, "*{name} = {}", values.join(", ")),
+                    Err(e) => writeln!(self.out, "{e}"),
+                }
+            }
+            ["heap", file, rest @ ..] => {
+                let format = match rest {
+                    [] => Ok(HeapFormat::Json),
+                    [format] => format.parse::<HeapFormat>(),
+                    _ => return self.unknown(words),
+                };
+                let Ok(format) = format else {
+                    return self.unknown(words);
+                };
+                let snapshot = self.execution.heap_snapshot().render(format);
+                match std::fs::write(file, snapshot) {
+                    Ok(()) => writeln!(self.out, "wrote the heap to {file}"),
+                    Err(e) => writeln!(self.out, "could not write {file}: {e}"),
+                }
+            }
+            ["backtrace" | "bt"] => {
+                for (i, location) in self.execution.backtrace().iter().enumerate() {
+                    writeln!(self.out, "#{i} {location}")?;
+                }
+                Ok(())
+            }
+            ["where" | "w"] => self.show_location(),
+            ["help" | "h"] => writeln!(self.out, "{HELP}"),
+            _ => self.unknown(words),
+        }
+    }
+
+    fn unknown(&mut self, words: &[&str]) -> io::Result<()> {
+        writeln!(
+            self.out,
+            "unknown command: {}; type help for a list of commands",
+            words.join(" ")
+        )
+    }
+
+    // step at least once, then until `stop` holds, a breakpoint is reached or
+    // the program finishes.
+    fn run_until(&mut self, stop: impl Fn(&Execution) -> bool) -> io::Result<()> {
+        if self.execution.outcome().is_some() {
+            return writeln!(self.out, "the program has finished");
+        }
+        loop {
+            if self.execution.step().is_some() {
+                return self.show_location();
+            }
+            let location = self.execution.location();
+            let at = (location.func.to_string(), location.bb.to_string());
+            if location.index == 0 && self.breakpoints.contains(&at) {
+                writeln!(self.out, "hit breakpoint at {}:{}", at.0, at.1)?;
+                return self.show_location();
+            }
+            if stop(self.execution) {
+                return self.show_location();
+            }
+        }
+    }
+
+    fn show_location(&mut self) -> io::Result<()> {
+        match self.execution.outcome() {
+            Some(Ok(n)) => writeln!(self.out, "main returned {n}"),
+            Some(Err(e)) => writeln!(
+                self.out,
+                "runtime error at {}: {e}",
+                self.execution.location()
+            ),
+            None => writeln!(
+                self.out,
+                "{}  {}",
+                self.execution.location(),
+                self.execution.code()
+            ),
+        }
+    }
+
+    fn show_vars(&mut self, vars: Vec<(VarId, String)>) -> io::Result<()> {
+        for (x, v) in vars {
+            writeln!(self.out, "{x} = {v}")?;
+        }
+        Ok(())
+    }
+}
+
+// `func` means the entry block of `func`.
+fn breakpoint(at: &str) -> (String, String) {
+    match at.split_once(':') {
+        Some((func, bb)) => (func.to_string(), bb.to_string()),
+        None => (at.to_string(), "entry".to_string()),
+    }
+}
@@ -0,0 +1,198 @@
+// executing a program one instruction at a time, and observing its state in
+// between. this is what the debugger is built on.
+
+use super::*;
+
+// A program in the middle of its execution.
+#[derive(Debug)]
+pub struct Execution {
+    state: State,
+    // how the program finished, if it did
+    outcome: Option<Result<i64, RuntimeError>>,
+}
+
+// A position in the program: the instruction at `index` in the block `bb` of
+// `func`. The terminal's index is the number of instructions in the block.
+#[derive(Clone, Debug, Display, Eq, PartialEq)]
+#[display(fmt = "{func}:{bb}:{index}")]
+pub struct Location {
+    pub func: FuncId,
+    pub bb: BbId,
+    pub index: usize,
+}
+
+// The instruction or terminal at a location.
+#[derive(Clone, Copy, Debug, Display, Eq, PartialEq)]
+pub enum Code<'a> {
+    Instruction(&'a Instruction),
+    Terminal(&'a Terminal),
+}
+
+impl Execution {
+    pub(super) fn new(state: State) -> Self {
+        Execution {
+            state,
+            outcome: None,
+        }
+    }
+
+    // Execute the next instruction or terminal. Returns how the program
+    // finished if this step (or an earlier one) finished it.
+    pub fn step(&mut self) -> Option<&Result<i64, RuntimeError>> {
+        if self.outcome.is_none() {
+            let result = match self.state.step_inst() {
+                Ok(None) => return None,
+                Ok(Some(r)) => Ok(r),
+                Err(e) => Err(e),
+            };
+            let flushed = self.state.host.flush().and_then(|_| self.state.flush_trace());
+            self.outcome = Some(result.and_then(|r| flushed.map(|_| r)));
+        }
+        self.outcome.as_ref()
+    }
+
+    pub fn outcome(&self) -> Option<&Result<i64, RuntimeError>> {
+        self.outcome.as_ref()
+    }
+
+    // Run the program to the end.
+    pub fn finish(mut self) -> ExecutionReport {
+        while self.step().is_none() {}
+        let s = &mut self.state;
+        let result = self.outcome.unwrap();
+        let leaks = match result {
+            Ok(_) => s.leaks(),
+            Err(_) => vec![],
+        };
+        ExecutionReport {
+            result,
+            output: mem::take(&mut s.host.output),
+            steps: s.steps,
+            peak_heap_cells: s.peak_heap_cells,
+            leaks,
+            max_call_depth: s.max_depth,
+            profile: s.profiler.take().map(Profiler::finish),
+            gc: s.gc.as_ref().map(|gc| gc.stats),
+        }
+    }
+
+    // Where the program is: the next instruction or terminal to execute.
+    pub fn location(&self) -> Location {
+        self.state.location()
+    }
+
+    // The next instruction or terminal to execute.
+    pub fn code(&self) -> Code<'_> {
+        let control = &self.state.control;
+        match control.insts.get(self.state.pc) {
+            Some(inst) => Code::Instruction(inst),
+            None => Code::Terminal(&control.term),
+        }
+    }
+
+    // The number of active calls, counting `main`.
+    pub fn depth(&self) -> usize {
+        self.state.stack.len() + 1
+    }
+
+    // The current location followed by the locations of the calls that are
+    // still active, innermost first.
+    pub fn backtrace(&self) -> Vec<Location> {
+        let s = &self.state;
+        let calls = s.stack.iter().rev().map(|site| {
+            let bb = &s.program.functions[&site.func].body[&site.from];
+            Location {
+                func: site.func.clone(),
+                bb: site.from.clone(),
+                index: bb.insts.len(),
+            }
+        });
+        std::iter::once(self.location()).chain(calls).collect()
+    }
+
+    // The local variables of the current function and their values.
+    pub fn locals(&self) -> Vec<(VarId, String)> {
+        show_env(&self.state.env)
+    }
+
+    // The objects reachable from the variables, and the variables pointing to
+    // them.
+    pub fn heap_snapshot(&self) -> HeapSnapshot {
+        self.state.heap_snapshot()
+    }
+
+    pub fn globals(&self) -> Vec<(VarId, String)> {
+        show_env(&self.state.glob)
+    }
+
+    // The value of the variable with the given name; locals shadow globals.
+    pub fn value(&self, name: &str) -> Option<String> {
+        self.lookup(name).map(|v| v.to_string())
+    }
+
+    // The values of the `count` heap cells starting at where the pointer
+    // variable `name` points to.
+    pub fn deref(&mut self, name: &str, count: usize) -> Result<Vec<String>, RuntimeError> {
+        let address = match self.lookup(name) {
+            Some(Value::Ptr(address)) => address,
+            Some(v) => return err(format!("{name} is not a pointer: {v}")),
+            None => return err(format!("undefined variable {name}")),
+        };
+        (0..count)
+            .map(|i| {
+                let address = match (&address, i) {
+                    (_, 0) => address.clone(),
+                    (ToHeap(object, offset), _) => ToHeap(*object, offset + i as u32),
+                    (ToVar(frame, x, offset), _) => ToVar(*frame, x.clone(), offset + i as u32),
+                    _ => return err(format!("{address} is not the address of an array")),
+                };
+                Ok(self.state.value_ref(&address)?.to_string())
+            })
+            .collect()
+    }
+
+    fn lookup(&self, name: &str) -> Option<Value> {
+        let find = |env: &Map<VarId, Value>| {
+            env.iter()
+                .find(|(x, _)| x.name() == name)
+                .map(|(_, v)| v.clone())
+        };
+        find(&self.state.env).or_else(|| find(&self.state.glob))
+    }
+}
+
+fn show_env(env: &Map<VarId, Value>) -> Vec<(VarId, String)> {
+    env.iter()
+        .map(|(x, v)| (x.clone(), v.to_string()))
+        .collect()
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Address::Nil => write!(f, "nil"),
+            ToHeap(object, offset) => write!(f, "heap{object}[{offset}]"),
+            ToVar(_, x, 0) => write!(f, "{x}"),
+            ToVar(_, x, offset) => write!(f, "{x}[{offset}]"),
+            Address::Field(base, field) => write!(f, "{base}.{field}"),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::FnPtr(func) => write!(f, "{func}"),
+            Value::Ptr(Address::Nil) => write!(f, "nil"),
+            Value::Ptr(address) => write!(f, "&{address}"),
+            Value::Int(n) => write!(f, "{n}"),
+            Value::Struct(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(field, v)| format!("{field}: {v}"))
+                    .collect::<Vec<_>>();
+                write!(f, "{{{}}}", fields.join(", "))
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+// external functions available to interpreted programs.
+//
+// an extern is a rust closure registered under a name together with its C♭
+// type. the interpreter checks the program's extern declarations against the
+// registry before running it, so a program that declares an extern the host
+// doesn't provide (or provides with a different type) fails up front rather
+// than when the call is reached. externs only exchange ints with the program.
+
+use super::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt;
+use std::io::Write;
+
+// the signature of an extern's implementation: it receives the host and the
+// (int) arguments, and returns the result if the extern has one.
+pub type ExternFn = Box<dyn FnMut(&mut Host, &[i64]) -> Result<Option<i64>, RuntimeError>>;
+
+// the part of the interpreter that externs interact with.
+#[derive(Debug, Default)]
+pub struct Host {
+    // lines captured so far
+    pub output: Vec<String>,
+    // the current line, if it isn't finished yet
+    partial: String,
+    // where printed text goes
+    sinks: Vec<Sink>,
+    // the exit code, if the program asked to stop
+    pub exit: Option<i64>,
+}
+
+impl Host {
+    pub fn new(sinks: Vec<Sink>) -> Self {
+        Host {
+            sinks,
+            ..Host::default()
+        }
+    }
+
+    // print some text; newlines separate output lines.
+    pub fn write(&mut self, text: &str) -> Result<(), RuntimeError> {
+        let mut capture = false;
+        for sink in &mut self.sinks {
+            let result = match sink {
+                Sink::Capture => {
+                    capture = true;
+                    Ok(())
+                }
+                // print! rather than the stdout handle so that tests capture
+                // the output.
+                Sink::Stdout => {
+                    print!("{text}");
+                    Ok(())
+                }
+                Sink::Writer(w) => w.write_all(text.as_bytes()),
+            };
+            result.or_else(|e| err(format!("could not write the output: {e}")))?;
+        }
+        if capture {
+            let mut lines = text.split('\n');
+            self.partial.push_str(lines.next().unwrap());
+            for line in lines {
+                self.output
+                    .push(mem::replace(&mut self.partial, line.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    // print a whole line.
+    pub fn writeln(&mut self, line: &str) -> Result<(), RuntimeError> {
+        self.write(line)?;
+        self.write("\n")
+    }
+
+    // stop the program as if `main` returned `code`.
+    pub fn exit(&mut self, code: i64) {
+        self.exit = Some(code);
+    }
+
+    // finish the last line if the program didn't, and flush the sinks.
+    pub fn flush(&mut self) -> Result<(), RuntimeError> {
+        if !self.partial.is_empty() {
+            self.writeln("")?;
+        }
+        for sink in &mut self.sinks {
+            let result = match sink {
+                Sink::Capture => Ok(()),
+                Sink::Stdout => std::io::stdout().flush(),
+                Sink::Writer(w) => w.flush(),
+            };
+            result.or_else(|e| err(format!("could not write the output: {e}")))?;
+        }
+        Ok(())
+    }
+}
+
+pub struct ExternRegistry {
+    externs: Map<String, (Type, ExternFn)>,
+}
+
+impl fmt::Debug for ExternRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.externs.keys()).finish()
+    }
+}
+
+// the standard library with seed 0.
+impl Default for ExternRegistry {
+    fn default() -> Self {
+        ExternRegistry::stdlib(0)
+    }
+}
+
+impl ExternRegistry {
+    // a registry without any externs.
+    pub fn new() -> Self {
+        ExternRegistry {
+            externs: Map::new(),
+        }
+    }
+
+    // register `imp` as the extern `name` of type `typ`, replacing any previous
+    // extern with that name. panics if `typ` isn't a function type over ints.
+    pub fn register<F>(&mut self, name: &str, typ: Type, imp: F) -> &mut Self
+    where
+        F: FnMut(&mut Host, &[i64]) -> Result<Option<i64>, RuntimeError> + 'static,
+    {
+        match &*typ.0 {
+            LirType::Function { ret_ty, param_ty }
+                if ret_ty.iter().chain(param_ty).all(|t| t.is_int()) => {}
+            _ => panic!("extern {name} must have a function type over ints, got {typ}"),
+        }
+        self.externs.insert(name.to_string(), (typ, Box::new(imp)));
+        self
+    }
+
+    // the standard library. `rand` is seeded with `seed`.
+    //
+    // print: (int) -> _          print the number on its own line
+    // print_int: (int) -> _      print the number without a newline
+    // print_char: (int) -> _     print the character with the given code point
+    // print_newline: () -> _     end the current line
+    // abs: (int) -> int
+    // min, max: (int, int) -> int
+    // rand: (int) -> int         a random number in [0, n)
+    // assert: (int) -> _         fail if the argument is 0
+    // exit: (int) -> _           stop as if main returned the argument
+    // isPythagorean: (int, int, int) -> int
+    pub fn stdlib(seed: u64) -> Self {
+        let unary = || func_ty(Some(int_ty()), vec![int_ty()]);
+        let binary = || func_ty(Some(int_ty()), vec![int_ty(), int_ty()]);
+        let effect = || func_ty(None, vec![int_ty()]);
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let mut registry = ExternRegistry::new();
+        registry
+            .register("print", effect(), |host, args| {
+                host.writeln(&args[0].to_string())?;
+                Ok(None)
+            })
+            .register("print_int", effect(), |host, args| {
+                host.write(&args[0].to_string())?;
+                Ok(None)
+            })
+            .register("print_char", effect(), |host, args| {
+                match u32::try_from(args[0]).ok().and_then(char::from_u32) {
+                    Some(c) => host.write(&c.to_string())?,
+                    None => return err(format!("print_char: {} is not a character", args[0])),
+                }
+                Ok(None)
+            })
+            .register("print_newline", func_ty(None, vec![]), |host, _| {
+                host.write("\n")?;
+                Ok(None)
+            })
+            .register("abs", unary(), |_, args| match args[0].checked_abs() {
+                Some(n) => Ok(Some(n)),
+                None => err(format!("abs: {} has no absolute value", args[0])),
+            })
+            .register("min", binary(), |_, args| Ok(Some(args[0].min(args[1]))))
+            .register("max", binary(), |_, args| Ok(Some(args[0].max(args[1]))))
+            .register("rand", unary(), move |_, args| {
+                if args[0] <= 0 {
+                    return err(format!("rand: the bound must be positive, got {}", args[0]));
+                }
+                Ok(Some(rng.gen_range(0..args[0])))
+            })
+            .register("assert", effect(), |_, args| {
+                if args[0] == 0 {
+                    return err("assertion failed".into());
+                }
+                Ok(None)
+            })
+            .register("exit", effect(), |host, args| {
+                host.exit(args[0]);
+                Ok(None)
+            })
+            .register(
+                "isPythagorean",
+                func_ty(Some(int_ty()), vec![int_ty(), int_ty(), int_ty()]),
+                |_, x| Ok(Some((x[0] * x[0] + x[1] * x[1] == x[2] * x[2]) as i64)),
+            );
+        registry
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.externs.contains_key(name)
+    }
+
+    // check that the extern `name`, declared with type `typ`, is available.
+    pub fn check_declaration(&self, name: &str, typ: &Type) -> Result<(), RuntimeError> {
+        match self.externs.get(name) {
+            Some((t, _)) if t == typ => Ok(()),
+            Some((t, _)) => err(format!(
+                "extern {name} is declared with type {typ} but the interpreter provides it with type {t}"
+            )),
+            None => err(format!(
+                "the interpreter doesn't provide the extern {name}; the available externs are: {}",
+                self.externs.keys().cloned().collect::<Vec<_>>().join(", ")
+            )),
+        }
+    }
+
+    // check all extern declarations of a program.
+    pub fn check(&self, externs: &Map<FuncId, Type>) -> Result<(), RuntimeError> {
+        externs
+            .iter()
+            .try_for_each(|(name, typ)| self.check_declaration(name.name(), typ))
+    }
+
+    // call the extern `name`.
+    pub fn call(
+        &mut self,
+        name: &str,
+        host: &mut Host,
+        args: &[i64],
+    ) -> Result<Option<i64>, RuntimeError> {
+        let Some((typ, imp)) = self.externs.get_mut(name) else {
+            return err(format!("the interpreter doesn't provide the extern {name}"));
+        };
+        let LirType::Function { ret_ty, param_ty } = &*typ.0 else {
+            unreachable!("externs have function types")
+        };
+        if param_ty.len() != args.len() {
+            return err(format!(
+                "extern {name} takes {} arguments, got {}",
+                param_ty.len(),
+                args.len()
+            ));
+        }
+        let result = imp(host, args)?;
+        if result.is_some() != ret_ty.is_some() && host.exit.is_none() {
+            return err(format!(
+                "extern {name} returned {result:?}, which doesn't match its type {typ}"
+            ));
+        }
+        Ok(result)
+    }
+}
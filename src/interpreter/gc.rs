@@ -0,0 +1,149 @@
+// garbage collection. when enabled, the interpreter runs a mark-sweep collector
+// before an allocation would take the heap above a threshold.
+//
+// the roots are the variables: the current environment, the globals and the
+// environments of the calls on the stack. marking follows pointers through
+// variables, struct fields and heap cells; sweeping removes the objects that
+// weren't marked. since nothing can refer to them, collecting them doesn't
+// change what the program does. this also goes for freed objects, which are
+// otherwise kept to detect uses after `$free`.
+//
+// objects that are collected without having been freed are still leaks, so the
+// collector keeps them (by allocation site) for the report at the end.
+//
+// after a collection, the threshold becomes twice the number of cells that are
+// still allocated (but no less than the configured threshold), so that programs
+// with a lot of live data don't collect on every allocation.
+
+use super::*;
+use std::collections::BTreeSet as Set;
+
+// What the garbage collector did during a run.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct GcStats {
+    pub collections: u64,
+    // the objects and cells that were collected, not counting freed objects
+    pub objects_collected: u64,
+    pub cells_collected: u64,
+}
+
+impl fmt::Display for GcStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} collections, {} objects ({} cells) collected",
+            self.collections, self.objects_collected, self.cells_collected
+        )
+    }
+}
+
+#[derive(Debug)]
+pub(super) struct Collector {
+    // the configured threshold
+    min_threshold: u64,
+    // collect when an allocation would take the heap above this many cells
+    threshold: u64,
+    pub(super) stats: GcStats,
+    // the collected objects that were never freed, by allocation site
+    pub(super) leaks: Map<VarId, Leak>,
+}
+
+impl Collector {
+    pub(super) fn new(threshold: u64) -> Self {
+        Collector {
+            min_threshold: threshold,
+            threshold,
+            stats: GcStats::default(),
+            leaks: Map::new(),
+        }
+    }
+}
+
+impl State {
+    // collect garbage if allocating `n` more cells would take the heap above
+    // the threshold.
+    pub(super) fn maybe_collect(&mut self, n: u32) {
+        let Some(gc) = &self.gc else {
+            return;
+        };
+        if self.heap_cells + n as u64 > gc.threshold {
+            self.collect_garbage();
+        }
+    }
+
+    fn collect_garbage(&mut self) {
+        let marked = self.reachable_objects();
+        let mut stats = GcStats::default();
+        let mut leaked = vec![];
+        self.store.retain(|id, object| {
+            if marked.contains(id) {
+                return true;
+            }
+            if !object.freed {
+                stats.objects_collected += 1;
+                stats.cells_collected += object.cells.len() as u64;
+                leaked.push((object.site.clone(), object.cells.len() as u64));
+            }
+            false
+        });
+        self.heap_cells -= stats.cells_collected;
+
+        let gc = self
+            .gc
+            .as_mut()
+            .expect("only called when collecting garbage");
+        for (site, cells) in leaked {
+            let leak = gc.leaks.entry(site.id().clone()).or_insert_with(|| Leak {
+                site: (*site).clone(),
+                objects: 0,
+                cells: 0,
+            });
+            leak.objects += 1;
+            leak.cells += cells;
+        }
+        gc.stats.collections += 1;
+        gc.stats.objects_collected += stats.objects_collected;
+        gc.stats.cells_collected += stats.cells_collected;
+        gc.threshold = gc.min_threshold.max(2 * self.heap_cells);
+    }
+
+    // the heap objects that can be reached from the variables.
+    pub(super) fn reachable_objects(&self) -> Set<u32> {
+        let mut marked = Set::new();
+        let mut pending = vec![];
+        let envs = [&self.env, &self.glob]
+            .into_iter()
+            .chain(self.stack.iter().map(|site| &site.env));
+        for v in envs.flat_map(Map::values) {
+            mark_value(v, &mut marked, &mut pending);
+        }
+        while let Some(object) = pending.pop() {
+            if let Some(object) = self.store.get(&object) {
+                for v in &object.cells {
+                    mark_value(v, &mut marked, &mut pending);
+                }
+            }
+        }
+        marked
+    }
+}
+
+// mark the objects `v` points to directly, adding the ones that weren't
+// marked yet to `pending`.
+fn mark_value(v: &Value, marked: &mut Set<u32>, pending: &mut Vec<u32>) {
+    match v {
+        Value::Ptr(address) => {
+            if let Some(object) = address.object() {
+                if marked.insert(object) {
+                    pending.push(object);
+                }
+            }
+        }
+        Value::Struct(fields) => {
+            for v in fields.values() {
+                mark_value(v, marked, pending);
+            }
+        }
+        Value::FnPtr(_) | Value::Int(_) => {}
+    }
+}
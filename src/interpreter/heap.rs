@@ -0,0 +1,294 @@
+// snapshots of the heap for debugging pointer-heavy programs. a snapshot has
+// the objects that can be reached from the variables (as in garbage
+// collection), their cells, and the roots: the variables of the globals and of
+// the active calls that point into the heap.
+//
+// snapshots can be exported as JSON, or as a Graphviz graph with a node per
+// root and object and an edge per pointer between them. render the graph with
+// e.g. `dot -Tsvg heap.dot > heap.svg`.
+
+use super::*;
+use crate::middle_end::dot::{digraph, quote};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Default, Display, Eq, PartialEq)]
+pub enum HeapFormat {
+    #[default]
+    #[display(fmt = "json")]
+    Json,
+    #[display(fmt = "dot")]
+    Dot,
+}
+
+impl FromStr for HeapFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(HeapFormat::Json),
+            "dot" => Ok(HeapFormat::Dot),
+            _ => Err(format!("unknown heap format {s}; expected json or dot")),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct HeapSnapshot {
+    // the globals first, then the variables of each active call from `main`
+    pub roots: Vec<HeapRoot>,
+    // by id
+    pub objects: Vec<HeapObject>,
+}
+
+// A variable that points into the heap, directly or through struct fields.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct HeapRoot {
+    // the function of the call the variable belongs to; `None` for globals
+    pub func: Option<String>,
+    // the number of the call counting from `main`, which is 1; 0 for globals
+    pub depth: usize,
+    pub var: String,
+    pub value: HeapValue,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct HeapObject {
+    pub id: u32,
+    // the allocation-site id of the `$alloc` that created the object and where
+    // the `$alloc` is
+    pub site: String,
+    pub at: String,
+    // the type of the cells
+    pub typ: String,
+    // freed objects can still be reached through dangling pointers; they
+    // don't have cells anymore.
+    pub freed: bool,
+    pub cells: Vec<HeapValue>,
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeapValue {
+    Int(i64),
+    Function(String),
+    Pointer(HeapPointer),
+    Struct(Map<String, HeapValue>),
+}
+
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct HeapPointer {
+    // what the pointer points to, e.g., `heap3[1].next`, `x` or `nil`
+    pub target: String,
+    // the object and the cell the pointer points into, if it points into the
+    // heap
+    pub object: Option<u32>,
+    pub index: Option<u32>,
+}
+
+impl HeapSnapshot {
+    pub fn render(&self, format: HeapFormat) -> String {
+        match format {
+            HeapFormat::Json => self.to_json(),
+            HeapFormat::Dot => self.to_dot(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("heap snapshots can be serialized")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("invalid heap snapshot: {e}"))
+    }
+
+    // Roots are drawn as plain text and objects as records with a field per
+    // cell. Edges go from the cell (or root) holding a pointer to the cell it
+    // points to, labeled with the struct fields the pointer is in.
+    pub fn to_dot(&self) -> String {
+        let mut lines = vec!["node [shape=record, fontname=monospace]".to_string()];
+        let mut edges = vec![];
+
+        for root in &self.roots {
+            let name = root_node(root);
+            let label = match &root.func {
+                Some(func) => format!("{func}#{}: {}", root.depth, root.var),
+                None => root.var.clone(),
+            };
+            lines.push(format!("{name} [shape=plaintext, label={}]", quote(&label)));
+            for (path, ptr) in pointers(&root.value) {
+                edges.push(edge(&name, &path, ptr));
+            }
+        }
+
+        for object in &self.objects {
+            let name = quote(&format!("heap{}", object.id));
+            let mut title = format!("heap{}: {} at {}", object.id, object.site, object.at);
+            if object.freed {
+                title += " (freed)";
+            }
+            let cells = object
+                .cells
+                .iter()
+                .enumerate()
+                .map(|(i, v)| format!("<c{i}> {}", record_escape(&v.to_string())))
+                .collect::<Vec<_>>();
+            let label = if cells.is_empty() {
+                record_escape(&title)
+            } else {
+                format!("{} | {{{}}}", record_escape(&title), cells.join(" | "))
+            };
+            lines.push(format!("{name} [label=\"{label}\"]"));
+            for (i, v) in object.cells.iter().enumerate() {
+                for (path, ptr) in pointers(v) {
+                    edges.push(edge(&format!("{name}:c{i}"), &path, ptr));
+                }
+            }
+        }
+
+        lines.extend(edges);
+        digraph("heap", &lines)
+    }
+}
+
+impl fmt::Display for HeapValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeapValue::Int(n) => write!(f, "{n}"),
+            HeapValue::Function(func) => write!(f, "{func}"),
+            HeapValue::Pointer(ptr) if ptr.target == "nil" => write!(f, "nil"),
+            HeapValue::Pointer(ptr) => write!(f, "&{}", ptr.target),
+            HeapValue::Struct(fields) => {
+                let fields = fields
+                    .iter()
+                    .map(|(field, v)| format!("{field}: {v}"))
+                    .collect::<Vec<_>>();
+                write!(f, "{{{}}}", fields.join(", "))
+            }
+        }
+    }
+}
+
+fn root_node(root: &HeapRoot) -> String {
+    match &root.func {
+        Some(func) => quote(&format!("{func}#{}.{}", root.depth, root.var)),
+        None => quote(&root.var),
+    }
+}
+
+// the pointers into the heap in `v`, with the struct fields they're in.
+fn pointers(v: &HeapValue) -> Vec<(String, &HeapPointer)> {
+    match v {
+        HeapValue::Pointer(ptr) if ptr.object.is_some() => vec![(String::new(), ptr)],
+        HeapValue::Struct(fields) => fields
+            .iter()
+            .flat_map(|(field, v)| {
+                pointers(v)
+                    .into_iter()
+                    .map(move |(path, ptr)| match &path[..] {
+                        "" => (field.clone(), ptr),
+                        _ => (format!("{field}.{path}"), ptr),
+                    })
+            })
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn edge(from: &str, path: &str, ptr: &HeapPointer) -> String {
+    let to = quote(&format!("heap{}", ptr.object.unwrap()));
+    let port = ptr.index.map_or(String::new(), |i| format!(":c{i}"));
+    match path {
+        "" => format!("{from} -> {to}{port}"),
+        _ => format!("{from} -> {to}{port} [label={}]", quote(path)),
+    }
+}
+
+// escape the characters that have a meaning in (quoted) record labels.
+fn record_escape(s: &str) -> String {
+    s.chars()
+        .flat_map(|c| match c {
+            '{' | '}' | '|' | '<' | '>' | '"' | '\\' => vec!['\\', c],
+            _ => vec![c],
+        })
+        .collect()
+}
+
+// the cell of the heap object the address points into, if any.
+fn cell(address: &Address) -> Option<u32> {
+    match address {
+        ToHeap(_, offset) => Some(*offset),
+        Address::Field(base, _) => cell(base),
+        _ => None,
+    }
+}
+
+impl State {
+    pub(super) fn heap_snapshot(&self) -> HeapSnapshot {
+        let frames = self
+            .stack
+            .iter()
+            .map(|site| (&site.func, &site.env))
+            .chain(std::iter::once((&self.func, &self.env)));
+        let envs = std::iter::once((None, 0, &self.glob)).chain(
+            frames
+                .enumerate()
+                .map(|(i, (func, env))| (Some(func.to_string()), i + 1, env)),
+        );
+
+        let mut roots = vec![];
+        for (func, depth, env) in envs {
+            for (x, v) in env {
+                let value = self.snapshot_value(v);
+                if !pointers(&value).is_empty() {
+                    roots.push(HeapRoot {
+                        func: func.clone(),
+                        depth,
+                        var: x.to_string(),
+                        value,
+                    });
+                }
+            }
+        }
+
+        let objects = self
+            .reachable_objects()
+            .into_iter()
+            .filter_map(|id| {
+                let object = self.store.get(&id)?;
+                Some(HeapObject {
+                    id,
+                    site: object.site.id().to_string(),
+                    at: object.site.at.to_string(),
+                    typ: object.site.id().typ().to_string(),
+                    freed: object.freed,
+                    cells: object
+                        .cells
+                        .iter()
+                        .map(|v| self.snapshot_value(v))
+                        .collect(),
+                })
+            })
+            .collect();
+
+        HeapSnapshot { roots, objects }
+    }
+
+    fn snapshot_value(&self, v: &Value) -> HeapValue {
+        match v {
+            Value::Int(n) => HeapValue::Int(*n),
+            Value::FnPtr(func) => HeapValue::Function(func.to_string()),
+            Value::Ptr(address) => HeapValue::Pointer(HeapPointer {
+                target: address.to_string(),
+                object: address.object(),
+                index: cell(address),
+            }),
+            Value::Struct(fields) => HeapValue::Struct(
+                fields
+                    .iter()
+                    .map(|(field, v)| (field.name.to_string(), self.snapshot_value(v)))
+                    .collect(),
+            ),
+        }
+    }
+}
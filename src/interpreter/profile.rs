@@ -0,0 +1,320 @@
+// profiling executions. the profiler counts how many times each function, basic
+// block, CFG edge and call site is executed, how many instructions of each kind
+// each function executes, and how the steps are distributed over the call tree.
+//
+// a profile can be reported as a flat table of functions or as a call tree, and
+// exported as JSON for optimizations that want to know which blocks, edges and
+// calls are hot (e.g., profile-guided inlining or block layout). functions and
+// blocks are named as in the LIR program.
+
+use super::*;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Profile {
+    // the number of instructions and terminals executed
+    pub steps: u64,
+    pub functions: Map<String, FunctionProfile>,
+    pub call_sites: Vec<CallSiteProfile>,
+    // the call of `main`
+    pub call_tree: CallTree,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct FunctionProfile {
+    pub calls: u64,
+    // the steps executed in the function itself
+    pub self_steps: u64,
+    // the steps executed in the function and the functions it calls, counting
+    // recursive calls only once
+    pub total_steps: u64,
+    // how many times each block was entered
+    pub blocks: Map<String, u64>,
+    // how many times control went from one block to another, by source and
+    // target. a call goes from the calling block to the block it returns to.
+    pub edges: Map<String, Map<String, u64>>,
+    // how many instructions and terminals were executed, by opcode
+    pub opcodes: Map<String, u64>,
+}
+
+// The calls made by the terminal of `block` in `caller` to `callee`.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CallSiteProfile {
+    pub caller: String,
+    pub block: String,
+    pub callee: String,
+    pub calls: u64,
+}
+
+// The calls of a function along one path of calls from `main`.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct CallTree {
+    pub func: String,
+    pub calls: u64,
+    pub self_steps: u64,
+    pub total_steps: u64,
+    pub children: Vec<CallTree>,
+}
+
+impl Profile {
+    pub fn block_count(&self, func: &str, bb: &str) -> u64 {
+        self.functions
+            .get(func)
+            .and_then(|f| f.blocks.get(bb))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn edge_count(&self, func: &str, from: &str, to: &str) -> u64 {
+        self.functions
+            .get(func)
+            .and_then(|f| f.edges.get(from)?.get(to))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn call_site_count(&self, caller: &str, block: &str, callee: &str) -> u64 {
+        self.call_sites
+            .iter()
+            .find(|site| site.caller == caller && site.block == block && site.callee == callee)
+            .map_or(0, |site| site.calls)
+    }
+
+    // The functions, the ones that executed the most steps themselves first,
+    // followed by the number of instructions and terminals executed by opcode.
+    pub fn flat_report(&self) -> String {
+        let mut functions = self.functions.iter().collect::<Vec<_>>();
+        functions.sort_by_key(|(name, f)| (std::cmp::Reverse(f.self_steps), *name));
+
+        let mut report = format!(
+            "{:>8} {:>10} {:>10} {:>8}  function\n",
+            "self %", "self", "total", "calls"
+        );
+        for (name, f) in functions {
+            let percent = 100.0 * f.self_steps as f64 / self.steps.max(1) as f64;
+            report += &format!(
+                "{percent:>7.2}% {:>10} {:>10} {:>8}  {name}\n",
+                f.self_steps, f.total_steps, f.calls
+            );
+        }
+
+        let mut opcodes = Map::<&str, u64>::new();
+        for (opcode, n) in self.functions.values().flat_map(|f| &f.opcodes) {
+            *opcodes.entry(opcode).or_default() += n;
+        }
+        report += &format!("\n{:>10}  opcode\n", "count");
+        for (opcode, n) in opcodes {
+            report += &format!("{n:>10}  {opcode}\n");
+        }
+        report
+    }
+
+    // The call tree, each call path indented under the one it extends.
+    pub fn call_tree_report(&self) -> String {
+        fn node(report: &mut String, tree: &CallTree, depth: usize) {
+            *report += &format!(
+                "{:>10} {:>10} {:>8}  {:indent$}{}\n",
+                tree.total_steps,
+                tree.self_steps,
+                tree.calls,
+                "",
+                tree.func,
+                indent = 2 * depth
+            );
+            for child in &tree.children {
+                node(report, child, depth + 1);
+            }
+        }
+
+        let mut report = format!("{:>10} {:>10} {:>8}  function\n", "total", "self", "calls");
+        if self.call_tree.calls > 0 {
+            node(&mut report, &self.call_tree, 0);
+        }
+        report
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("profiles can be serialized")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|e| format!("invalid profile: {e}"))
+    }
+}
+
+// collects a profile while the program runs.
+#[derive(Debug, Default)]
+pub(super) struct Profiler {
+    steps: u64,
+    functions: Map<String, FunctionProfile>,
+    // (caller, block, callee)
+    call_sites: Map<(String, String, String), u64>,
+    // the nodes of the call tree; the first one is `main`
+    nodes: Vec<Node>,
+    // the active calls, mirroring the interpreter's stack
+    frames: Vec<Frame>,
+}
+
+#[derive(Debug)]
+struct Node {
+    func: String,
+    calls: u64,
+    self_steps: u64,
+    children: Map<String, usize>,
+}
+
+#[derive(Debug)]
+struct Frame {
+    func: String,
+    // the call tree node of this call
+    node: usize,
+    // the block the call is in, once it has entered one
+    block: Option<String>,
+}
+
+impl Profiler {
+    // count the step the interpreter is about to execute. calls and returns
+    // are noticed when the first step after them is counted.
+    fn count(&mut self, s: &State) {
+        let depth = s.stack.len() + 1;
+        self.frames.truncate(depth);
+        if self.frames.len() < depth {
+            let func = s.func.to_string();
+            let node = match self.frames.last() {
+                Some(caller) => {
+                    let block = caller.block.clone().expect("calls are made from blocks");
+                    let site = (caller.func.clone(), block, func.clone());
+                    *self.call_sites.entry(site).or_default() += 1;
+                    let parent = caller.node;
+                    self.child(parent, &func)
+                }
+                None => {
+                    self.nodes.push(Node::new(&func));
+                    0
+                }
+            };
+            self.nodes[node].calls += 1;
+            self.function(&func).calls += 1;
+            self.frames.push(Frame {
+                func,
+                node,
+                block: None,
+            });
+        }
+
+        let frame = self.frames.last_mut().unwrap();
+        let f = self.functions.get_mut(&frame.func).unwrap();
+        if s.pc == 0 {
+            let bb = s.control.id.to_string();
+            bump(&mut f.blocks, &bb);
+            if let Some(from) = frame.block.replace(bb.clone()) {
+                bump(f.edges.entry(from).or_default(), &bb);
+            }
+        }
+        let opcode = match s.control.insts.get(s.pc) {
+            Some(inst) => inst.opcode(),
+            None => s.control.term.opcode(),
+        };
+        bump(&mut f.opcodes, opcode);
+        f.self_steps += 1;
+        self.nodes[frame.node].self_steps += 1;
+        self.steps += 1;
+    }
+
+    fn function(&mut self, func: &str) -> &mut FunctionProfile {
+        self.functions.entry(func.to_string()).or_default()
+    }
+
+    fn child(&mut self, parent: usize, func: &str) -> usize {
+        if let Some(&node) = self.nodes[parent].children.get(func) {
+            return node;
+        }
+        self.nodes.push(Node::new(func));
+        let node = self.nodes.len() - 1;
+        self.nodes[parent].children.insert(func.to_string(), node);
+        node
+    }
+
+    pub(super) fn finish(mut self) -> Profile {
+        let call_tree = if self.nodes.is_empty() {
+            CallTree::default()
+        } else {
+            self.tree(0, &mut vec![])
+        };
+        Profile {
+            steps: self.steps,
+            functions: self.functions,
+            call_sites: self
+                .call_sites
+                .into_iter()
+                .map(|((caller, block, callee), calls)| CallSiteProfile {
+                    caller,
+                    block,
+                    callee,
+                    calls,
+                })
+                .collect(),
+            call_tree,
+        }
+    }
+
+    // build the call tree under `node`, adding its steps to the total steps of
+    // its function unless the function is one of its `callers`.
+    fn tree(&mut self, node: usize, callers: &mut Vec<String>) -> CallTree {
+        let func = self.nodes[node].func.clone();
+        callers.push(func.clone());
+        let children = self.nodes[node]
+            .children
+            .values()
+            .copied()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|child| self.tree(child, callers))
+            .collect::<Vec<_>>();
+        callers.pop();
+
+        let Node {
+            calls, self_steps, ..
+        } = self.nodes[node];
+        let total_steps = self_steps + children.iter().map(|c| c.total_steps).sum::<u64>();
+        if !callers.contains(&func) {
+            self.function(&func).total_steps += total_steps;
+        }
+        CallTree {
+            func,
+            calls,
+            self_steps,
+            total_steps,
+            children,
+        }
+    }
+}
+
+impl Node {
+    fn new(func: &str) -> Self {
+        Node {
+            func: func.to_string(),
+            calls: 0,
+            self_steps: 0,
+            children: Map::new(),
+        }
+    }
+}
+
+fn bump(counts: &mut Map<String, u64>, key: &str) {
+    match counts.get_mut(key) {
+        Some(n) => *n += 1,
+        None => {
+            counts.insert(key.to_string(), 1);
+        }
+    }
+}
+
+impl State {
+    pub(super) fn profile_step(&mut self) {
+        if let Some(mut profiler) = self.profiler.take() {
+            profiler.count(self);
+            self.profiler = Some(profiler);
+        }
+    }
+}
@@ -0,0 +1,1464 @@
+// interpreter tests.
+
+use super::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+fn parse(code: &str) -> Program {
+    let program: Program = code.parse().expect("Failed to parse LIR code");
+    validate(&program).expect("The test program is not valid.");
+    program
+}
+
+fn run(code: &str) -> (Result<i64, RuntimeError>, Vec<String>) {
+    interpret_with_output(parse(code), ExternRegistry::default())
+}
+
+// SECTION: externs
+
+#[test]
+fn stdlib_arithmetic() {
+    let (result, output) = run(r"extern abs:(int) -> int
+extern max:(int,int) -> int
+extern min:(int,int) -> int
+extern print:(int) -> _
+
+fn main() -> int {
+let a:int, b:int, c:int
+entry:
+  a = $call_ext abs(-7)
+  b = $call_ext min(a, 3)
+  c = $call_ext max(a, 3)
+  $call_ext print(a)
+  $call_ext print(b)
+  $call_ext print(c)
+  $ret 0
+}
+");
+    assert_eq!(result, Ok(0));
+    assert_eq!(output, vec!["7", "3", "7"]);
+}
+
+#[test]
+fn stdlib_printing() {
+    let (result, output) = run(r"extern print_char:(int) -> _
+extern print_int:(int) -> _
+extern print_newline:() -> _
+
+fn main() -> int {
+entry:
+  $call_ext print_int(4)
+  $call_ext print_char(50)
+  $call_ext print_newline()
+  $call_ext print_char(111)
+  $call_ext print_char(107)
+  $ret 0
+}
+");
+    assert_eq!(result, Ok(0));
+    // the unfinished last line is flushed at the end.
+    assert_eq!(output, vec!["42", "ok"]);
+}
+
+const RAND: &str = r"extern print:(int) -> _
+extern rand:(int) -> int
+
+fn main() -> int {
+let i:int, r:int, _t1:int
+entry:
+  $jump loop
+loop:
+  _t1 = $cmp lt i 20
+  $branch _t1 body exit
+body:
+  r = $call_ext rand(6)
+  $call_ext print(r)
+  i = $arith add i 1
+  $jump loop
+exit:
+  $ret 0
+}
+";
+
+#[test]
+fn stdlib_rand_is_seeded() {
+    let run_with = |seed| interpret_with_output(parse(RAND), ExternRegistry::stdlib(seed)).1;
+    let numbers = run_with(1);
+    assert_eq!(numbers.len(), 20);
+    assert!(numbers
+        .iter()
+        .all(|n| (0..6).contains(&n.parse::<i64>().unwrap())));
+    assert_eq!(numbers, run_with(1));
+    assert_ne!(numbers, run_with(2));
+}
+
+#[test]
+fn stdlib_assert_and_exit() {
+    let program = r"extern assert:(int) -> _
+extern exit:(int) -> _
+extern print:(int) -> _
+
+fn main() -> int {
+entry:
+  $call_ext assert(1)
+  $call_ext print(1)
+  $call_ext exit(3)
+  $call_ext print(2)
+  $ret 0
+}
+";
+    assert_eq!(run(program), (Ok(3), vec!["1".to_string()]));
+
+    let (result, _) = run(&program.replace("assert(1)", "assert(0)"));
+    assert_eq!(result, Err(RuntimeError::new("assertion failed".into())));
+}
+
+#[test]
+fn declarations_are_checked_up_front() {
+    // the mismatch is reported even though the extern is never called.
+    let (result, _) = run(r"extern print:(int,int) -> _
+
+fn main() -> int {
+entry:
+  $ret 0
+}
+");
+    assert_eq!(
+        result,
+        Err(RuntimeError::new(
+            "extern print is declared with type (int,int) -> _ but the interpreter provides it with type (int) -> _".into()
+        ))
+    );
+
+    let (result, _) = run(r"extern launch:() -> _
+
+fn main() -> int {
+entry:
+  $ret 0
+}
+");
+    assert!(result
+        .unwrap_err()
+        .message
+        .starts_with("the interpreter doesn't provide the extern launch"));
+}
+
+#[test]
+fn custom_externs() {
+    let calls = Rc::new(RefCell::new(vec![]));
+    let mut externs = ExternRegistry::new();
+    let log = calls.clone();
+    externs.register(
+        "square",
+        func_ty(Some(int_ty()), vec![int_ty()]),
+        move |_, args| {
+            log.borrow_mut().push(args[0]);
+            Ok(Some(args[0] * args[0]))
+        },
+    );
+
+    let program = parse(
+        r"extern square:(int) -> int
+
+fn main() -> int {
+let x:int
+entry:
+  x = $call_ext square(3)
+  x = $call_ext square(x)
+  $ret x
+}
+",
+    );
+    assert_eq!(interpret(program, externs), Ok(81));
+    assert_eq!(*calls.borrow(), vec![3, 9]);
+}
+
+// SECTION: execution reports
+
+// a writer that shares its buffer with the test.
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl io::Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+const COUNTDOWN: &str = r"extern print:(int) -> _
+
+fn f(n:int) -> int {
+let r:int, m:int, _t1:int
+entry:
+  $call_ext print(n)
+  _t1 = $cmp lte n 0
+  $branch _t1 done rec
+done:
+  r = $copy 0
+  $jump ret
+rec:
+  m = $arith sub n 1
+  r = $call_dir f(m) then ret
+ret:
+  $ret r
+}
+
+fn main() -> int {
+let p:&int, x:int
+entry:
+  p = $alloc 5 [_a1]
+  x = $call_dir f(2) then bb1
+bb1:
+  $ret x
+}
+";
+
+#[test]
+fn reports_statistics() {
+    let program = parse(COUNTDOWN);
+    let report = Interpreter::new().sink(Sink::Capture).run(program.clone());
+    assert_eq!(
+        report,
+        ExecutionReport {
+            result: Ok(0),
+            output: vec!["2".into(), "1".into(), "0".into()],
+            // 3 in main and 6 in each call of f.
+            steps: 3 + 3 * 6,
+            peak_heap_cells: 5,
+            leaks: vec![Leak {
+                site: AllocSite {
+                    inst: program.functions[&func_id("main")].body[&bb_id("entry")].insts[0]
+                        .clone(),
+                    at: location("main", "entry", 0),
+                },
+                objects: 1,
+                cells: 5,
+            }],
+            max_call_depth: 4,
+            profile: None,
+            gc: None,
+        }
+    );
+}
+
+#[test]
+fn output_goes_to_the_sinks() {
+    // without sinks, nothing is captured.
+    let report = Interpreter::new().run(parse(COUNTDOWN));
+    assert_eq!(report.result, Ok(0));
+    assert!(report.output.is_empty());
+
+    let buffer = SharedBuffer::default();
+    let report = Interpreter::new()
+        .sink(Sink::Writer(Box::new(buffer.clone())))
+        .run(parse(COUNTDOWN));
+    assert!(report.output.is_empty());
+    assert_eq!(&*buffer.0.borrow(), b"2\n1\n0\n");
+}
+
+#[test]
+fn reports_failed_runs() {
+    let report = Interpreter::new().sink(Sink::Capture).run(parse(
+        &COUNTDOWN.replace("done:\n", "done:\n  r = $arith div 1 n\n"),
+    ));
+    // output printed before the error is kept.
+    assert_eq!(report.output, vec!["2", "1", "0"]);
+    assert_eq!(
+        report.result,
+        Err(RuntimeError::new("division by zero".into()))
+    );
+}
+
+// SECTION: limits
+
+fn run_with_limits(code: &str, limits: Limits) -> ExecutionReport {
+    Interpreter::new().limits(limits).run(parse(code))
+}
+
+fn error_kind(report: &ExecutionReport) -> RuntimeErrorKind {
+    report.result.as_ref().unwrap_err().kind
+}
+
+#[test]
+fn limits_are_exact() {
+    let limits = Limits {
+        max_steps: Some(21),
+        max_depth: Some(4),
+        max_heap_cells: Some(5),
+    };
+    assert_eq!(run_with_limits(COUNTDOWN, limits).result, Ok(0));
+
+    let report = run_with_limits(
+        COUNTDOWN,
+        Limits {
+            max_steps: Some(20),
+            ..limits
+        },
+    );
+    assert_eq!(error_kind(&report), RuntimeErrorKind::StepLimit);
+    assert_eq!(report.steps, 20);
+
+    let report = run_with_limits(
+        COUNTDOWN,
+        Limits {
+            max_depth: Some(3),
+            ..limits
+        },
+    );
+    assert_eq!(error_kind(&report), RuntimeErrorKind::DepthLimit);
+
+    let report = run_with_limits(
+        COUNTDOWN,
+        Limits {
+            max_heap_cells: Some(4),
+            ..limits
+        },
+    );
+    assert_eq!(error_kind(&report), RuntimeErrorKind::HeapLimit);
+    assert_eq!(report.peak_heap_cells, 0);
+}
+
+#[test]
+fn infinite_loops_run_out_of_steps() {
+    let report = run_with_limits(
+        r"fn main() -> int {
+let i:int, _t1:int
+entry:
+  $jump loop
+loop:
+  i = $arith add i 1
+  _t1 = $cmp gt i 0
+  $branch _t1 loop exit
+exit:
+  $ret i
+}
+",
+        Limits {
+            max_steps: Some(1000),
+            ..Limits::default()
+        },
+    );
+    assert_eq!(
+        report.result,
+        Err(RuntimeError {
+            kind: RuntimeErrorKind::StepLimit,
+            message: "exceeded the limit of 1000 steps".into(),
+            allocations: vec![],
+        })
+    );
+}
+
+#[test]
+fn infinite_recursion_runs_out_of_stack() {
+    let report = run_with_limits(
+        r"fn f(n:int) -> int {
+let r:int, m:int
+entry:
+  m = $arith add n 1
+  r = $call_dir f(m) then exit
+exit:
+  $ret r
+}
+
+fn main() -> int {
+let x:int
+entry:
+  x = $call_dir f(0) then exit
+exit:
+  $ret x
+}
+",
+        Limits {
+            max_depth: Some(100),
+            ..Limits::default()
+        },
+    );
+    assert_eq!(error_kind(&report), RuntimeErrorKind::DepthLimit);
+    assert_eq!(report.max_call_depth, 100);
+}
+
+#[test]
+fn allocation_loops_run_out_of_heap() {
+    let report = run_with_limits(
+        r"fn main() -> int {
+let p:&int, _t1:int
+entry:
+  $jump loop
+loop:
+  p = $alloc 1000 [_a1]
+  _t1 = $cmp neq p 0
+  $branch _t1 loop exit
+exit:
+  $ret 0
+}
+",
+        Limits {
+            max_heap_cells: Some(100_000),
+            ..Limits::default()
+        },
+    );
+    assert_eq!(error_kind(&report), RuntimeErrorKind::HeapLimit);
+    assert_eq!(report.peak_heap_cells, 100_000);
+}
+
+// SECTION: arithmetic modes
+
+// run `main` with the given locals and body (which must set r) in each mode.
+fn run_in_modes(locals: &str, body: &str) -> [Result<i64, RuntimeError>; 3] {
+    let decls = match locals {
+        "" => "r:int".to_string(),
+        _ => format!("r:int, {locals}"),
+    };
+    let code = format!("fn main() -> int {{\nlet {decls}\nentry:\n{body}  $ret r\n}}\n");
+    [
+        ArithmeticMode::Wrapping,
+        ArithmeticMode::Trapping,
+        ArithmeticMode::Unbounded,
+    ]
+    .map(|mode| {
+        Interpreter::new()
+            .arithmetic(mode)
+            .limits(Limits {
+                max_heap_cells: Some(1000),
+                ..Limits::default()
+            })
+            .run(parse(&code))
+            .result
+    })
+}
+
+// i32::MIN, computed because it can't be written as a literal
+const MIN: &str = "  min = $arith sub -2147483647 1\n";
+
+#[test]
+fn min_divided_by_minus_one() {
+    let [wrapping, trapping, unbounded] =
+        run_in_modes("min:int", &format!("{MIN}  r = $arith div min -1\n"));
+    assert_eq!(wrapping, Ok(i32::MIN as i64));
+    assert_eq!(
+        trapping,
+        Err(RuntimeError::new(
+            "integer overflow: $arith div -2147483648 -1".into()
+        ))
+    );
+    assert_eq!(unbounded, Ok(-(i32::MIN as i64)));
+}
+
+#[test]
+fn overflow_wraps_or_traps() {
+    let [wrapping, trapping, unbounded] = run_in_modes("", "  r = $arith add 2147483647 1\n");
+    assert_eq!(wrapping, Ok(i32::MIN as i64));
+    assert!(trapping.is_err());
+    assert_eq!(unbounded, Ok(i32::MAX as i64 + 1));
+
+    // comparisons see the wrapped value.
+    let [wrapping, _, unbounded] =
+        run_in_modes("x:int", "  x = $arith mul 65536 32768\n  r = $cmp lt x 0\n");
+    assert_eq!(wrapping, Ok(1));
+    assert_eq!(unbounded, Ok(0));
+
+    // so do allocations.
+    let [wrapping, trapping, unbounded] = run_in_modes(
+        "x:int, p:&int",
+        "  x = $arith mul 65536 32768\n  p = $alloc x [_a1]\n",
+    );
+    let message = |r: Result<i64, RuntimeError>| r.unwrap_err().message;
+    assert_eq!(
+        message(wrapping),
+        "cannot allocate a negative number of elements"
+    );
+    assert!(message(trapping).starts_with("integer overflow"));
+    assert_eq!(unbounded.unwrap_err().kind, RuntimeErrorKind::HeapLimit);
+}
+
+// SECTION: heap objects
+
+// run `main` with the given locals and body, which allocates `p` and `q`.
+fn run_with_objects(locals: &str, body: &str) -> Result<i64, RuntimeError> {
+    let code = format!(
+        "fn main() -> int {{
+let p:&int, q:&int, r:&int, x:int{locals}
+entry:
+  p = $alloc 2 [_a1]
+  q = $alloc 2 [_a2]
+{body}  $ret x
+}}
+"
+    );
+    run(&code).0
+}
+
+#[test]
+fn objects_have_bounds() {
+    assert_eq!(
+        run_with_objects("", "  r = $gep p 1\n  x = $load r\n"),
+        Ok(0)
+    );
+    // one past the end is fine as long as it isn't dereferenced.
+    assert_eq!(run_with_objects("", "  r = $gep p 2\n"), Ok(0));
+
+    let e = run_with_objects("", "  r = $gep p 2\n  $store r 1\n").unwrap_err();
+    assert_eq!(e.message, "out-of-bounds access");
+    assert_eq!(e.allocations.len(), 1);
+    assert_eq!(e.allocations[0].id().name(), "_a1");
+    assert_eq!(e.allocations[0].at, location("main", "entry", 0));
+    assert_eq!(
+        e.to_string(),
+        "out-of-bounds access (the object was allocated by `p = $alloc 2 [_a1]` at main:entry:0)"
+    );
+
+    let e = run_with_objects("", "  r = $gep q -1\n").unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "pointer arithmetic out of range: index -1 (the object was allocated by `q = $alloc 2 [_a2]` at main:entry:1)"
+    );
+    // going back into the object is fine.
+    assert_eq!(
+        run_with_objects("", "  r = $gep q 1\n  r = $gep r -1\n  x = $load r\n"),
+        Ok(0)
+    );
+}
+
+#[test]
+fn pointers_into_different_objects_are_unordered() {
+    assert_eq!(run_with_objects("", "  x = $cmp eq p q\n"), Ok(0));
+    assert_eq!(run_with_objects("", "  x = $cmp neq p q\n"), Ok(1));
+    assert_eq!(
+        run_with_objects("", "  r = $gep p 1\n  x = $cmp lt p r\n"),
+        Ok(1)
+    );
+    assert_eq!(
+        run_with_objects("", "  r = $copy 0\n  x = $cmp lt r p\n"),
+        Ok(1)
+    );
+    assert_eq!(
+        run_with_objects("", "  x = $cmp lt p q\n").unwrap_err().to_string(),
+        "cannot order pointers into different objects (the objects were allocated by `p = $alloc 2 [_a1]` at main:entry:0 and `q = $alloc 2 [_a2]` at main:entry:1)"
+    );
+}
+
+#[test]
+fn empty_objects_are_distinct() {
+    let body = "  p = $alloc 0 [_a3]\n  q = $alloc 0 [_a4]\n  x = $cmp eq p q\n";
+    assert_eq!(run_with_objects("", body), Ok(0));
+    let e = run_with_objects("", "  p = $alloc 0 [_a3]\n  x = $load p\n").unwrap_err();
+    assert_eq!(e.message, "out-of-bounds access");
+    assert_eq!(e.allocations[0].at, location("main", "entry", 2));
+}
+
+#[test]
+fn freed_objects_cannot_be_used() {
+    assert_eq!(run_with_objects("", "  $free p\n  $free q\n"), Ok(0));
+    let e = run_with_objects("", "  $free p\n  x = $load p\n").unwrap_err();
+    assert_eq!(
+        e.to_string(),
+        "use after free (the object was allocated by `p = $alloc 2 [_a1]` at main:entry:0)"
+    );
+    let e = run_with_objects("", "  r = $gep q 1\n  $free q\n  $store r 3\n").unwrap_err();
+    assert_eq!(e.message, "use after free");
+    assert_eq!(e.allocations[0].id().name(), "_a2");
+
+    let e = run_with_objects("", "  r = $copy p\n  $free p\n  $free r\n").unwrap_err();
+    assert_eq!(e.message, "double free");
+    assert_eq!(e.allocations[0].id().name(), "_a1");
+
+    // freeing nil does nothing.
+    assert_eq!(run_with_objects("", "  r = $copy 0\n  $free r\n"), Ok(0));
+    let e = run_with_objects("", "  r = $gep p 1\n  $free r\n").unwrap_err();
+    assert_eq!(
+        e.message,
+        "invalid free: the pointer points into the middle of an object"
+    );
+    let e = run_with_objects("", "  r = $addrof x\n  $free r\n").unwrap_err();
+    assert_eq!(
+        e.message,
+        "invalid free: the pointer doesn't point to a heap object"
+    );
+}
+
+#[test]
+fn reports_leaks_by_allocation_site() {
+    let code = "fn main() -> int {
+let p:&int, q:&int, i:int, c:int
+entry:
+  $jump loop
+loop:
+  p = $alloc 3 [_a1]
+  q = $alloc 1 [_a2]
+  $free q
+  i = $arith add i 1
+  c = $cmp lt i 4
+  $branch c loop exit
+exit:
+  q = $alloc 2 [_a3]
+  $ret 0
+}
+";
+    let report = Interpreter::new().run(parse(code));
+    assert_eq!(report.result, Ok(0));
+    assert_eq!(report.peak_heap_cells, 4 * 3 + 2);
+    let leaks = report
+        .leaks
+        .iter()
+        .map(|leak| leak.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        leaks,
+        [
+            "4 objects (12 cells) allocated by `p = $alloc 3 [_a1]` at main:loop:0 never freed",
+            "1 object (2 cells) allocated by `q = $alloc 2 [_a3]` at main:exit:0 never freed",
+        ]
+    );
+
+    // only programs that finish normally leak.
+    let code = code.replace("$ret 0", "$free p\n  $free p\n  $ret 0");
+    let report = Interpreter::new().run(parse(&code));
+    assert_eq!(report.result.unwrap_err().message, "double free");
+    assert!(report.leaks.is_empty());
+}
+
+#[test]
+fn freed_cells_count_against_the_limit_no_more() {
+    let report = run_with_limits(
+        r"fn main() -> int {
+let p:&int, i:int, c:int
+entry:
+  $jump loop
+loop:
+  p = $alloc 1000 [_a1]
+  $free p
+  i = $arith add i 1
+  c = $cmp lt i 1000
+  $branch c loop exit
+exit:
+  $ret i
+}
+",
+        Limits {
+            max_heap_cells: Some(1000),
+            ..Limits::default()
+        },
+    );
+    assert_eq!(report.result, Ok(1000));
+    assert_eq!(report.peak_heap_cells, 1000);
+    assert!(report.leaks.is_empty());
+}
+
+// SECTION: addressable variables
+
+#[test]
+fn addresses_of_locals_and_globals() {
+    let code = "struct pair {
+  fst:int
+  snd:int
+}
+
+g:int
+
+fn main() -> int {
+let x:int, s:pair, p:&int, q:&pair, r:int, t:int
+entry:
+  p = $addrof x
+  $store p 5
+  r = $load p
+  r = $arith add r x
+  p = $addrof g
+  $store p 20
+  r = $arith add r g
+  q = $addrof s
+  p = $gfp q snd
+  $store p 100
+  t = $load p
+  r = $arith add r t
+  $ret r
+}
+";
+    assert_eq!(run(code).0, Ok(5 + 5 + 20 + 100));
+}
+
+// `f(p, n)` stores 100 through `p` after `n` calls that each pass down the
+// address of their own `x`.
+const FRAMES: &str = "fn f(p:&int, n:int) -> int {
+let x:int, q:&int, r:int, c:int
+entry:
+  x = $copy n
+  c = $cmp eq n 0
+  $branch c base rec
+base:
+  $store p 100
+  r = $copy 0
+  $jump ret
+rec:
+  q = $addrof x
+  n = $arith sub n 1
+  r = $call_dir f(q, n) then rec2
+rec2:
+  r = $copy x
+  $jump ret
+ret:
+  $ret r
+}
+
+fn main() -> int {
+let x:int, p:&int, r:int
+entry:
+  p = $addrof x
+  r = $call_dir f(p, N) then bb1
+bb1:
+  r = $arith add r x
+  $ret r
+}
+";
+
+#[test]
+fn pointers_refer_to_the_variables_of_their_call() {
+    // main's x
+    assert_eq!(run(&FRAMES.replace('N', "0")).0, Ok(100));
+    // the x of the first call of f
+    assert_eq!(run(&FRAMES.replace('N', "1")).0, Ok(100));
+    // the x of the second call of f; the first one returns its own x
+    assert_eq!(run(&FRAMES.replace('N', "2")).0, Ok(2));
+}
+
+#[test]
+fn dangling_pointers_are_detected() {
+    let code = "fn f() -> &int {
+let x:int, p:&int
+entry:
+  p = $addrof x
+  $ret p
+}
+
+fn main() -> int {
+let p:&int, r:int
+entry:
+  p = $call_dir f() then bb1
+bb1:
+  r = $load p
+  $ret r
+}
+";
+    assert_eq!(
+        run(code).0.unwrap_err().message,
+        "dangling pointer: x is a local of a call to f that has returned"
+    );
+}
+
+#[test]
+fn variables_hold_one_element() {
+    let run_main = |body: &str| {
+        run(&format!(
+            "fn main() -> int {{
+let x:int, y:int, p:&int, q:&int, r:int
+entry:
+  p = $addrof x
+  q = $addrof y
+{body}  $ret r
+}}
+"
+        ))
+        .0
+        .map_err(|e| e.message)
+    };
+    assert_eq!(run_main("  q = $gep p 1\n  r = $cmp lt p q\n"), Ok(1));
+    assert_eq!(
+        run_main("  q = $gep p 1\n  r = $load q\n"),
+        Err("out-of-bounds access: x is a variable, not an array".into())
+    );
+    assert_eq!(
+        run_main("  q = $gep p -1\n"),
+        Err("pointer arithmetic out of range: index -1".into())
+    );
+    assert_eq!(run_main("  r = $cmp eq p q\n"), Ok(0));
+    assert_eq!(
+        run_main("  r = $cmp lt p q\n"),
+        Err("cannot order pointers into different objects".into())
+    );
+}
+
+// SECTION: garbage collection
+
+// builds a list of N nodes, allocating garbage along the way, and prints the
+// sum of the list with `f`, which allocates more garbage. while `f` runs, the
+// nodes are only reachable through a field of `h` in the caller and `keep` only
+// through a pointer into its middle.
+const LISTS: &str = "struct node {
+  val:int
+  next:&node
+}
+
+struct holder {
+  head:&node
+}
+
+extern print:(int) -> _
+
+fn f(p:&node) -> int {
+let s:int, g:&int, v:&int, w:&&node, c:int
+entry:
+  $jump loop
+loop:
+  c = $cmp eq p 0
+  $branch c done body
+body:
+  g = $alloc 3 [_a4]
+  v = $gfp p val
+  c = $load v
+  s = $arith add s c
+  w = $gfp p next
+  p = $load w
+  $jump loop
+done:
+  $call_ext print(s)
+  $ret s
+}
+
+fn main() -> int {
+let h:holder, a:&holder, hd:&&node, l:&node, n:&node, keep:&&node, k:&&node, g:&int, i:int, c:int, v:&int, q:&&node, r:int
+entry:
+  a = $addrof h
+  hd = $gfp a head
+  keep = $alloc 4 [_a1]
+  $jump loop
+loop:
+  c = $cmp lt i N
+  $branch c body done
+body:
+  g = $alloc 5 [_a2]
+  n = $alloc 1 [_a3]
+  v = $gfp n val
+  $store v i
+  q = $gfp n next
+  l = $load hd
+  $store q l
+  $store hd n
+  i = $arith add i 1
+  $jump loop
+done:
+  k = $gep keep 2
+  keep = $copy 0
+  l = $load hd
+  hd = $copy 0
+  n = $copy 0
+  v = $copy 0
+  q = $copy 0
+  r = $call_dir f(l) then exit
+exit:
+  $ret r
+}
+";
+
+#[test]
+fn garbage_collection_preserves_behavior() {
+    let program = parse(&LISTS.replace(" N\n", " 100\n"));
+    let report = |gc: Option<u64>| {
+        let mut interpreter = Interpreter::new().sink(Sink::Capture);
+        if let Some(threshold) = gc {
+            interpreter = interpreter.gc(threshold);
+        }
+        interpreter.run(program.clone())
+    };
+
+    let without = report(None);
+    assert_eq!(without.result, Ok(99 * 100 / 2));
+    assert_eq!(without.output, ["4950"]);
+    assert_eq!(without.gc, None);
+    assert_eq!(without.peak_heap_cells, 4 + 100 * 6 + 100 * 3);
+    // nothing is freed, so every allocation leaks, whether collected or not.
+    assert_eq!(
+        without
+            .leaks
+            .iter()
+            .map(|leak| (leak.site.id().name(), leak.objects, leak.cells))
+            .collect::<Vec<_>>(),
+        vec![
+            ("_a1", 1, 4),
+            ("_a2", 100, 500),
+            ("_a3", 100, 100),
+            ("_a4", 100, 300)
+        ]
+    );
+
+    for threshold in [0, 1, 10, 100] {
+        let with = report(Some(threshold));
+        assert_eq!(
+            (&with.result, &with.output, &with.leaks),
+            (&without.result, &without.output, &without.leaks)
+        );
+        let stats = with.gc.unwrap();
+        assert!(stats.collections > 0);
+        // only garbage arrays are collected.
+        assert!(stats.objects_collected > 0 && stats.objects_collected <= 200);
+        assert!(with.peak_heap_cells < without.peak_heap_cells);
+    }
+}
+
+#[test]
+fn garbage_collection_keeps_programs_within_the_heap_limit() {
+    let program = parse(&LISTS.replace(" N\n", " 1000\n"));
+    let limits = Limits {
+        max_heap_cells: Some(3000),
+        ..Limits::default()
+    };
+    let report = Interpreter::new().limits(limits).run(program.clone());
+    assert_eq!(error_kind(&report), RuntimeErrorKind::HeapLimit);
+
+    let report = Interpreter::new().limits(limits).gc(100).run(program);
+    assert_eq!(report.result, Ok(999 * 1000 / 2));
+    let stats = report.gc.unwrap();
+    assert_eq!(
+        stats.to_string(),
+        format!(
+            "{} collections, {} objects ({} cells) collected",
+            stats.collections, stats.objects_collected, stats.cells_collected
+        )
+    );
+    // most of the 2000 garbage arrays were collected.
+    assert!(stats.objects_collected > 1000);
+}
+
+#[test]
+fn garbage_collection_removes_freed_objects() {
+    let code = "fn main() -> int {
+let p:&int, i:int, c:int
+entry:
+  $jump loop
+loop:
+  p = $alloc 10 [_a1]
+  $free p
+  i = $arith add i 1
+  c = $cmp lt i 100
+  $branch c loop exit
+exit:
+  $ret 0
+}
+";
+    let report = Interpreter::new().gc(0).run(parse(code));
+    assert_eq!(report.result, Ok(0));
+    // freed objects aren't counted as collected.
+    assert_eq!(report.gc.unwrap().cells_collected, 0);
+    assert!(report.leaks.is_empty());
+}
+
+// SECTION: stepping and debugging
+
+fn start(code: &str) -> Execution {
+    Interpreter::new()
+        .sink(Sink::Capture)
+        .start(parse(code))
+        .unwrap()
+}
+
+fn location(func: &str, bb: &str, index: usize) -> Location {
+    Location {
+        func: func_id(func),
+        bb: bb_id(bb),
+        index,
+    }
+}
+
+#[test]
+fn steps_one_instruction_at_a_time() {
+    let mut e = start(COUNTDOWN);
+    assert_eq!(e.location(), location("main", "entry", 0));
+    assert_eq!(e.code().to_string(), "p = $alloc 5 [_a1]");
+
+    assert_eq!(e.step(), None);
+    assert_eq!(e.value("p").unwrap(), "&heap1[0]");
+    assert_eq!(e.deref("p", 3).unwrap(), vec!["0", "0", "0"]);
+    assert_eq!(e.location(), location("main", "entry", 1));
+    assert!(matches!(
+        e.code(),
+        Code::Terminal(Terminal::CallDirect { .. })
+    ));
+
+    // the call enters f.
+    assert_eq!(e.step(), None);
+    assert_eq!(e.location(), location("f", "entry", 0));
+    assert_eq!(e.depth(), 2);
+    assert_eq!(e.value("n").unwrap(), "2");
+    assert_eq!(
+        e.backtrace(),
+        vec![location("f", "entry", 0), location("main", "entry", 1)]
+    );
+    assert_eq!(
+        e.locals()
+            .into_iter()
+            .map(|(x, v)| format!("{x} = {v}"))
+            .collect::<Vec<_>>(),
+        vec!["_t1 = 0", "m = 0", "n = 2", "r = 0"]
+    );
+
+    let steps = std::iter::from_fn(|| Some(e.step().cloned()))
+        .position(|outcome| outcome.is_some())
+        .unwrap();
+    // 19 more steps, the last of which finishes the program.
+    assert_eq!(steps, 18);
+    assert_eq!(e.outcome(), Some(&Ok(0)));
+    assert_eq!(e.step(), Some(&Ok(0)));
+
+    let report = e.finish();
+    assert_eq!(report.steps, 21);
+    assert_eq!(report.output, vec!["2", "1", "0"]);
+}
+
+#[test]
+fn stops_where_errors_happen() {
+    let mut e = start(&COUNTDOWN.replace("done:\n", "done:\n  r = $arith div 1 n\n"));
+    while e.step().is_none() {}
+    assert_eq!(
+        e.outcome(),
+        Some(&Err(RuntimeError::new("division by zero".into())))
+    );
+    assert_eq!(e.location(), location("f", "done", 0));
+    assert_eq!(e.depth(), 4);
+}
+
+fn debug_session(code: &str, commands: &str) -> String {
+    let mut e = start(code);
+    let mut out = vec![];
+    debugger::debug(&mut e, commands.as_bytes(), &mut out).unwrap();
+    String::from_utf8(out).unwrap().replace("(liri) ", "")
+}
+
+#[test]
+fn debugger_sessions() {
+    let transcript = debug_session(
+        COUNTDOWN,
+        "break f:done\ncontinue\nbacktrace\nprint n\nfinish\nfinish\nnext\nfinish\nprint x\ncontinue\n",
+    );
+    assert_eq!(
+        transcript,
+        "main:entry:0  p = $alloc 5 [_a1]
+breakpoint at f:done
+hit breakpoint at f:done
+f:done:0  r = $copy 0
+#0 f:done:0
+#1 f:rec:1
+#2 f:rec:1
+#3 main:entry:1
+n = 0
+f:ret:0  $ret r
+f:ret:0  $ret r
+main:bb1:0  $ret x
+main returned 0
+x = 0
+the program has finished
+
+"
+    );
+
+    // stepping over a call doesn't stop inside it, unless there is a
+    // breakpoint.
+    let transcript = debug_session(COUNTDOWN, "s\nn\nd p 2\nq\n");
+    assert_eq!(
+        transcript,
+        "main:entry:0  p = $alloc 5 [_a1]
+main:entry:1  x = $call_dir f(2) then bb1
+main:bb1:0  $ret x
+*p = 0, 0
+
+"
+    );
+    let transcript = debug_session(COUNTDOWN, "s\nb f:rec\nn\nwhere\nfoo\n");
+    assert_eq!(
+        transcript,
+        "main:entry:0  p = $alloc 5 [_a1]
+main:entry:1  x = $call_dir f(2) then bb1
+breakpoint at f:rec
+hit breakpoint at f:rec
+f:rec:0  m = $arith sub n 1
+f:rec:0  m = $arith sub n 1
+unknown command: foo; type help for a list of commands
+
+"
+    );
+}
+
+// SECTION: tracing
+
+fn trace(code: &str, tracer: impl FnOnce(SharedBuffer) -> Tracer) -> String {
+    let buffer = SharedBuffer::default();
+    let report = Interpreter::new()
+        .trace(tracer(buffer.clone()))
+        .run(parse(code));
+    assert_eq!(report.result, Ok(0));
+    let trace = buffer.0.borrow().clone();
+    String::from_utf8(trace).unwrap()
+}
+
+#[test]
+fn traces_reads_and_writes() {
+    let code = COUNTDOWN
+        .replace("(2)", "(1)")
+        .replace("[_a1]\n", "[_a1]\n  $store p 7\n  x = $load p\n");
+    let trace = trace(&code, |out| Tracer::new(out, TraceFormat::Text));
+    assert_eq!(
+        trace,
+        "enter main()
+  main:entry:0  p = $alloc 5 [_a1] -> p = &heap1[0]
+  main:entry:1  $store p 7  (p = &heap1[0]) -> *p = 7
+  main:entry:2  x = $load p  (p = &heap1[0], *p = 7) -> x = 7
+  main:entry:3  x = $call_dir f(1) then bb1
+  enter f(n = 1)
+    f:entry:0  $call_ext print(n)  (n = 1)
+    f:entry:1  _t1 = $cmp lte n 0  (n = 1) -> _t1 = 0
+    f:entry:2  $branch _t1 done rec  (_t1 = 0)
+    f:rec:0  m = $arith sub n 1  (n = 1) -> m = 0
+    f:rec:1  r = $call_dir f(m) then ret  (m = 0)
+    enter f(n = 0)
+      f:entry:0  $call_ext print(n)  (n = 0)
+      f:entry:1  _t1 = $cmp lte n 0  (n = 0) -> _t1 = 1
+      f:entry:2  $branch _t1 done rec  (_t1 = 1)
+      f:done:0  r = $copy 0 -> r = 0
+      f:done:1  $jump ret
+      f:ret:0  $ret r  (r = 0)
+    exit f -> 0
+    f:ret:0  $ret r  (r = 0)
+  exit f -> 0
+  main:bb1:0  $ret x  (x = 0)
+exit main -> 0
+"
+    );
+}
+
+#[test]
+fn traces_filtered_functions_as_json() {
+    let trace = trace(COUNTDOWN, |out| {
+        Tracer::new(out, TraceFormat::JsonLines).only(["f"])
+    });
+    let events = trace
+        .lines()
+        .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+        .collect::<Vec<_>>();
+    // 3 calls of f, each with an entry, 6 steps and an exit.
+    assert_eq!(events.len(), 3 * 8);
+    assert!(events
+        .iter()
+        .all(|e| e["at"].as_str().is_none_or(|at| at.starts_with("f:"))));
+    assert_eq!(
+        events[0],
+        serde_json::json!({
+            "depth": 2,
+            "event": "enter",
+            "func": "f",
+            "args": [{"var": "n", "value": "2"}],
+        })
+    );
+    assert_eq!(
+        events[2],
+        serde_json::json!({
+            "depth": 2,
+            "event": "step",
+            "at": "f:entry:1",
+            "code": "_t1 = $cmp lte n 0",
+            "reads": [{"var": "n", "value": "2"}],
+            "writes": [{"var": "_t1", "value": "0"}],
+        })
+    );
+    assert_eq!(
+        events.last().unwrap(),
+        &serde_json::json!({"depth": 2, "event": "exit", "func": "f", "value": "0"})
+    );
+}
+
+// SECTION: profiling
+
+#[test]
+fn profiles_blocks_edges_and_calls() {
+    let report = Interpreter::new().profile().run(parse(COUNTDOWN));
+    let profile = report.profile.unwrap();
+    assert_eq!(profile.steps, report.steps);
+
+    assert_eq!(profile.functions["f"].calls, 3);
+    assert_eq!(profile.functions["f"].self_steps, 18);
+    assert_eq!(profile.functions["f"].total_steps, 18);
+    assert_eq!(profile.functions["main"].total_steps, 21);
+    assert_eq!(profile.block_count("f", "entry"), 3);
+    assert_eq!(profile.block_count("f", "rec"), 2);
+    assert_eq!(profile.block_count("f", "nowhere"), 0);
+    assert_eq!(profile.edge_count("f", "entry", "rec"), 2);
+    assert_eq!(profile.edge_count("f", "entry", "done"), 1);
+    // the calls return to `ret`
+    assert_eq!(profile.edge_count("f", "rec", "ret"), 2);
+    assert_eq!(profile.edge_count("main", "entry", "bb1"), 1);
+    assert_eq!(profile.call_site_count("main", "entry", "f"), 1);
+    assert_eq!(profile.call_site_count("f", "rec", "f"), 2);
+    assert_eq!(profile.functions["f"].opcodes["$call_ext"], 3);
+    assert_eq!(profile.functions["main"].opcodes["$alloc"], 1);
+
+    assert_eq!(
+        profile.flat_report(),
+        "  self %       self      total    calls  function
+  85.71%         18         18        3  f
+  14.29%          3         21        1  main
+
+     count  opcode
+         1  $alloc
+         2  $arith
+         3  $branch
+         3  $call_dir
+         3  $call_ext
+         3  $cmp
+         1  $copy
+         1  $jump
+         4  $ret
+"
+    );
+    assert_eq!(
+        profile.call_tree_report(),
+        "     total       self    calls  function
+        21          3        1  main
+        18          6        1    f
+        12          6        1      f
+         6          6        1        f
+"
+    );
+
+    assert_eq!(Profile::from_json(&profile.to_json()), Ok(profile));
+    assert!(Profile::from_json("{}").is_err());
+}
+
+#[test]
+fn profiling_is_opt_in() {
+    assert_eq!(Interpreter::new().run(parse(COUNTDOWN)).profile, None);
+}
+
+// SECTION: heap snapshots
+
+const LIST: &str = r"struct node {
+  val:int
+  next:&node
+}
+
+head:&node
+
+fn len(p:&node) -> int {
+let r:int, w:&&node, q:&node, c:int
+entry:
+  c = $cmp eq p 0
+  $branch c exit rec
+rec:
+  w = $gfp p next
+  q = $load w
+  r = $call_dir len(q) then back
+back:
+  r = $arith add r 1
+  $jump exit
+exit:
+  $ret r
+}
+
+fn main() -> int {
+let n:&node, v:&int, q:&&node, l:&node, i:int, c:int
+entry:
+  $jump loop
+loop:
+  c = $cmp lt i 3
+  $branch c body done
+body:
+  n = $alloc 1 [_a1]
+  v = $gfp n val
+  $store v i
+  q = $gfp n next
+  l = $copy head
+  $store q l
+  head = $copy n
+  i = $arith add i 1
+  $jump loop
+done:
+  q = $copy 0
+  v = $copy 0
+  n = $copy 0
+  l = $copy 0
+  i = $call_dir len(head) then exit
+exit:
+  $ret i
+}
+";
+
+#[test]
+fn heap_snapshots_show_the_reachable_objects() {
+    let mut e = start(LIST);
+    // the innermost call of `len`, which gets nil.
+    while e.location() != location("len", "exit", 0) {
+        assert_eq!(e.step(), None);
+    }
+    assert_eq!(e.depth(), 5);
+    let snapshot = e.heap_snapshot();
+
+    // the variables of `main` don't point into the heap anymore.
+    assert_eq!(
+        snapshot
+            .roots
+            .iter()
+            .map(|root| match &root.func {
+                Some(func) => format!("{func}#{}: {} = {}", root.depth, root.var, root.value),
+                None => format!("{} = {}", root.var, root.value),
+            })
+            .collect::<Vec<_>>(),
+        vec![
+            "head = &heap3[0]",
+            "len#2: p = &heap3[0]",
+            "len#2: q = &heap2[0]",
+            "len#2: w = &heap3[0].next",
+            "len#3: p = &heap2[0]",
+            "len#3: q = &heap1[0]",
+            "len#3: w = &heap2[0].next",
+            "len#4: p = &heap1[0]",
+            "len#4: w = &heap1[0].next",
+        ]
+    );
+    assert_eq!(
+        snapshot.objects[1],
+        HeapObject {
+            id: 2,
+            site: "_a1".into(),
+            at: "main:body:0".into(),
+            typ: "node".into(),
+            freed: false,
+            cells: vec![HeapValue::Struct(
+                [
+                    (
+                        "next".to_string(),
+                        HeapValue::Pointer(HeapPointer {
+                            target: "heap1[0]".into(),
+                            object: Some(1),
+                            index: Some(0),
+                        })
+                    ),
+                    ("val".to_string(), HeapValue::Int(1)),
+                ]
+                .into_iter()
+                .collect()
+            )],
+        }
+    );
+
+    assert_eq!(
+        snapshot.to_dot(),
+        r#"digraph "heap" {
+  node [shape=record, fontname=monospace]
+  "head" [shape=plaintext, label="head"]
+  "len#2.p" [shape=plaintext, label="len#2: p"]
+  "len#2.q" [shape=plaintext, label="len#2: q"]
+  "len#2.w" [shape=plaintext, label="len#2: w"]
+  "len#3.p" [shape=plaintext, label="len#3: p"]
+  "len#3.q" [shape=plaintext, label="len#3: q"]
+  "len#3.w" [shape=plaintext, label="len#3: w"]
+  "len#4.p" [shape=plaintext, label="len#4: p"]
+  "len#4.w" [shape=plaintext, label="len#4: w"]
+  "heap1" [label="heap1: _a1 at main:body:0 | {<c0> \{next: nil, val: 0\}}"]
+  "heap2" [label="heap2: _a1 at main:body:0 | {<c0> \{next: &heap1[0], val: 1\}}"]
+  "heap3" [label="heap3: _a1 at main:body:0 | {<c0> \{next: &heap2[0], val: 2\}}"]
+  "head" -> "heap3":c0
+  "len#2.p" -> "heap3":c0
+  "len#2.q" -> "heap2":c0
+  "len#2.w" -> "heap3":c0
+  "len#3.p" -> "heap2":c0
+  "len#3.q" -> "heap1":c0
+  "len#3.w" -> "heap2":c0
+  "len#4.p" -> "heap1":c0
+  "len#4.w" -> "heap1":c0
+  "heap2":c0 -> "heap1":c0 [label="next"]
+  "heap3":c0 -> "heap2":c0 [label="next"]
+}
+"#
+    );
+    assert_eq!(snapshot.render(HeapFormat::Dot), snapshot.to_dot());
+
+    assert_eq!(
+        HeapSnapshot::from_json(&snapshot.render(HeapFormat::Json)),
+        Ok(snapshot)
+    );
+    assert!(HeapSnapshot::from_json("[]").is_err());
+}
+
+#[test]
+fn heap_snapshots_leave_out_unreachable_objects() {
+    // the second node is dropped from the list, then the head of the list is
+    // freed. it's still reachable, but the rest of the list isn't anymore.
+    let code = LIST.replace(
+        "  i = $call_dir len(head) then exit",
+        "  l = $copy head
+  q = $gfp l next
+  n = $load q
+  w = $gfp n next
+  n = $load w
+  $store q n
+  n = $copy 0
+  w = $copy 0
+  i = $call_dir len(head) then exit",
+    );
+    let code = code.replace(
+        "  $ret i\n}",
+        "  l = $copy head\n  head = $copy 0\n  $free l\n  head = $copy l\n  $ret i\n}",
+    );
+    let code = code.replace("l:&node, i:int", "l:&node, w:&&node, i:int");
+    let mut e = start(&code);
+    while e.step().is_none() {}
+    assert_eq!(e.outcome(), Some(&Ok(2)));
+
+    let snapshot = e.heap_snapshot();
+    assert_eq!(
+        snapshot
+            .roots
+            .iter()
+            .map(|root| (root.depth, root.var.as_str()))
+            .collect::<Vec<_>>(),
+        vec![(0, "head"), (1, "l"), (1, "q")]
+    );
+    assert_eq!(
+        snapshot
+            .objects
+            .iter()
+            .map(|object| (object.id, object.freed, object.cells.len()))
+            .collect::<Vec<_>>(),
+        vec![(3, true, 0)]
+    );
+    assert!(snapshot
+        .to_dot()
+        .contains("\"heap3\" [label=\"heap3: _a1 at main:body:0 (freed)\"]"));
+}
+
+#[test]
+fn debugger_writes_heap_snapshots() {
+    let dir = tempfile::tempdir().unwrap();
+    let json = dir.path().join("heap.json");
+    let dot = dir.path().join("heap.dot");
+    let out = debug_session(
+        LIST,
+        &format!(
+            "break len\ncontinue\ncontinue\nheap {}\nheap {} dot\nheap {} svg\nquit\n",
+            json.display(),
+            dot.display(),
+            dot.display()
+        ),
+    );
+    assert_eq!(
+        out,
+        format!(
+            "main:entry:0  $jump loop
+breakpoint at len:entry
+hit breakpoint at len:entry
+len:entry:0  c = $cmp eq p 0
+hit breakpoint at len:entry
+len:entry:0  c = $cmp eq p 0
+wrote the heap to {0}
+wrote the heap to {1}
+unknown command: heap {1} svg; type help for a list of commands
+
+",
+            json.display(),
+            dot.display()
+        )
+    );
+    let snapshot = HeapSnapshot::from_json(&std::fs::read_to_string(&json).unwrap()).unwrap();
+    assert_eq!(snapshot.objects.len(), 3);
+    assert_eq!(std::fs::read_to_string(&dot).unwrap(), snapshot.to_dot());
+}
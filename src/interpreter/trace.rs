@@ -0,0 +1,297 @@
+// tracing executions. a tracer logs every instruction and terminal the program
+// executes along with the values it reads and writes, and every function entry
+// (with the arguments) and exit (with the return value).
+//
+// the text format is indented by call depth and meant to be read by people:
+//
+//     enter main()
+//       main:entry:0  x = $call_dir f(m) then bb1  (m = 2)
+//       enter f(n = 2)
+//         f:entry:0  _t1 = $cmp lte n 0  (n = 2) -> _t1 = 0
+//         ...
+//       exit f -> 0
+//
+// the JSON-lines format has one object per line, with an "event" field that's
+// "enter", "exit" or "step" and the call depth in "depth".
+
+use super::*;
+use serde::Serialize;
+use std::collections::BTreeSet as Set;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, Default, Display, Eq, PartialEq)]
+pub enum TraceFormat {
+    #[default]
+    #[display(fmt = "text")]
+    Text,
+    #[display(fmt = "json")]
+    JsonLines,
+}
+
+impl FromStr for TraceFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(TraceFormat::Text),
+            "json" => Ok(TraceFormat::JsonLines),
+            _ => Err(format!("unknown trace format {s}; expected text or json")),
+        }
+    }
+}
+
+// Writes a trace of the execution to a writer.
+pub struct Tracer {
+    out: Box<dyn io::Write>,
+    format: TraceFormat,
+    // the functions to trace; all of them if `None`
+    functions: Option<Set<String>>,
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tracer")
+            .field("format", &self.format)
+            .field("functions", &self.functions)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Tracer {
+    pub fn new(out: impl io::Write + 'static, format: TraceFormat) -> Self {
+        Tracer {
+            out: Box::new(out),
+            format,
+            functions: None,
+        }
+    }
+
+    // Only trace what happens in the given functions: their instructions and
+    // terminals, and their entries and exits.
+    pub fn only<S: Into<String>>(mut self, functions: impl IntoIterator<Item = S>) -> Self {
+        self.functions = Some(functions.into_iter().map(Into::into).collect());
+        self
+    }
+
+    fn traces(&self, func: &FuncId) -> bool {
+        self.functions
+            .as_ref()
+            .is_none_or(|functions| functions.contains(func.name()))
+    }
+
+    fn emit(&mut self, record: &Record) -> Result<(), RuntimeError> {
+        let result = match self.format {
+            TraceFormat::Text => {
+                let indent = 2 * (record.depth - 1)
+                    + 2 * matches!(record.event, Event::Step { .. }) as usize;
+                writeln!(self.out, "{:indent$}{}", "", record.event)
+            }
+            TraceFormat::JsonLines => serde_json::to_writer(&mut self.out, record)
+                .map_err(io::Error::from)
+                .and_then(|_| writeln!(self.out)),
+        };
+        result.or_else(|e| err(format!("could not write the trace: {e}")))
+    }
+}
+
+#[derive(Serialize)]
+struct Record {
+    // the number of active calls, counting `main`
+    depth: usize,
+    #[serde(flatten)]
+    event: Event,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event {
+    Enter {
+        func: String,
+        args: Vec<Binding>,
+    },
+    // `value` is missing if the function doesn't return anything
+    Exit {
+        func: String,
+        value: Option<String>,
+    },
+    // an instruction or terminal. memory cells are named after the pointer
+    // they're accessed through, as `*p`.
+    Step {
+        at: String,
+        code: String,
+        reads: Vec<Binding>,
+        writes: Vec<Binding>,
+    },
+}
+
+#[derive(Serialize)]
+struct Binding {
+    var: String,
+    value: String,
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} = {}", self.var, self.value)
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let join = |bindings: &[Binding]| {
+            bindings
+                .iter()
+                .map(Binding::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match self {
+            Event::Enter { func, args } => write!(f, "enter {func}({})", join(args)),
+            Event::Exit { func, value: None } => write!(f, "exit {func}"),
+            Event::Exit {
+                func,
+                value: Some(v),
+            } => write!(f, "exit {func} -> {v}"),
+            Event::Step {
+                at,
+                code,
+                reads,
+                writes,
+            } => {
+                write!(f, "{at}  {code}")?;
+                if !reads.is_empty() {
+                    write!(f, "  ({})", join(reads))?;
+                }
+                if !writes.is_empty() {
+                    write!(f, " -> {}", join(writes))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl State {
+    fn tracing(&self, func: &FuncId) -> bool {
+        self.tracer.as_ref().is_some_and(|t| t.traces(func))
+    }
+
+    fn trace(&mut self, depth: usize, event: Event) -> Result<(), RuntimeError> {
+        let tracer = self.tracer.as_mut().expect("only called while tracing");
+        tracer.emit(&Record { depth, event })
+    }
+
+    // log the entry into `main`.
+    pub(super) fn trace_start(&mut self) -> Result<(), RuntimeError> {
+        if self.tracing(&self.func) {
+            let func = self.func.to_string();
+            self.trace(1, Event::Enter { func, args: vec![] })?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn flush_trace(&mut self) -> Result<(), RuntimeError> {
+        match &mut self.tracer {
+            Some(tracer) => tracer
+                .out
+                .flush()
+                .or_else(|e| err(format!("could not write the trace: {e}"))),
+            None => Ok(()),
+        }
+    }
+
+    // execute the next instruction or terminal like `execute_next`, logging
+    // what happens.
+    pub(super) fn step_traced(&mut self) -> Result<Option<i64>, RuntimeError> {
+        let func = self.func.clone();
+        let depth = self.stack.len() + 1;
+        let inst = self.control.insts.get(self.pc).cloned();
+        let term = self.control.term.clone();
+        let traced = self.tracing(&func);
+
+        let mut step = None;
+        let mut returned = None;
+        if traced {
+            let mut reads = match &inst {
+                Some(inst) => self.bindings(inst.uses()),
+                None => self.bindings(term.uses()),
+            };
+            if let Some(Instruction::Load { src, .. }) = &inst {
+                reads.extend(self.binding_through(src));
+            }
+            if let (None, Terminal::Ret(Some(op))) = (&inst, &term) {
+                returned = self.eval(op).ok().map(|v| v.to_string());
+            }
+            step = Some((
+                format!("{func}:{}:{}", self.control.id, self.pc),
+                inst.as_ref()
+                    .map_or_else(|| term.to_string(), ToString::to_string),
+                reads,
+            ));
+        }
+
+        let result = self.execute_next();
+
+        if let Some((at, code, reads)) = step {
+            let mut writes = vec![];
+            if let (Ok(_), Some(inst)) = (&result, &inst) {
+                writes = self.bindings(inst.lhs().into_iter().collect());
+                if let Instruction::Store { dst, .. } = inst {
+                    writes.extend(self.binding_through(dst));
+                }
+            }
+            let event = Event::Step {
+                at,
+                code,
+                reads,
+                writes,
+            };
+            self.trace(depth, event)?;
+        }
+        let result = result?;
+
+        if inst.is_none() {
+            if self.stack.len() + 1 > depth && self.tracing(&self.func) {
+                let params = self.program.functions[&self.func].params.clone();
+                let args = self.bindings(params.iter().collect());
+                let func = self.func.to_string();
+                self.trace(depth + 1, Event::Enter { func, args })?;
+            }
+            if matches!(term, Terminal::Ret(_)) && traced {
+                let func = func.to_string();
+                self.trace(
+                    depth,
+                    Event::Exit {
+                        func,
+                        value: returned,
+                    },
+                )?;
+            }
+        }
+        Ok(result)
+    }
+
+    fn bindings(&self, vars: Vec<&VarId>) -> Vec<Binding> {
+        vars.into_iter()
+            .filter_map(|x| {
+                let value = self.lookup(x).ok()?.to_string();
+                Some(Binding {
+                    var: x.to_string(),
+                    value,
+                })
+            })
+            .collect()
+    }
+
+    // the memory cell the pointer variable `x` points to.
+    fn binding_through(&mut self, x: &VarId) -> Option<Binding> {
+        let Value::Ptr(address) = self.lookup(x).ok()? else {
+            return None;
+        };
+        let value = self.value_ref(&address).ok()?.to_string();
+        Some(Binding {
+            var: format!("*{x}"),
+            value,
+        })
+    }
+}
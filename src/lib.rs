@@ -2,3 +2,4 @@ pub mod commons;
 pub mod front_end;
 pub mod interpreter;
 pub mod middle_end;
+pub mod reduce;
@@ -0,0 +1,17 @@
+// static analyses over LIR programs.
+
+// use ordered sets and maps to allow for deterministic outputs.
+#[allow(unused_imports)]
+use std::collections::{BTreeMap as Map, BTreeSet as Set};
+
+use derive_more::Display;
+
+use crate::middle_end::lir::*;
+
+mod call_graph;
+mod cfg;
+mod intervals;
+
+pub use self::call_graph::*;
+pub use self::cfg::*;
+pub use self::intervals::*;
@@ -0,0 +1,53 @@
+// the whole-program call graph.
+
+use super::*;
+
+#[derive(Clone, Copy, Debug, Display, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum CallKind {
+    #[display(fmt = "direct")]
+    Direct,
+    // the callee is one of the internal functions the function pointer may
+    // point to.
+    #[display(fmt = "indirect")]
+    Indirect,
+    #[display(fmt = "extern")]
+    Extern,
+}
+
+// maps each internal function to the functions it may call. the targets of an
+// indirect call are approximated by every internal function (except main) with
+// the type the called function pointer points to.
+pub fn call_graph(program: &Program) -> Map<FuncId, Set<(FuncId, CallKind)>> {
+    let func_typ =
+        |f: &Function| func_ty(f.ret_ty.clone(), f.params.iter().map(|p| p.typ()).collect());
+
+    program
+        .functions
+        .values()
+        .map(|func| {
+            let mut callees = Set::new();
+            for bb in func.body.values() {
+                for inst in &bb.insts {
+                    if let Instruction::CallExt { ext_callee, .. } = inst {
+                        callees.insert((ext_callee.clone(), CallKind::Extern));
+                    }
+                }
+                match &bb.term {
+                    Terminal::CallDirect { callee, .. } => {
+                        callees.insert((callee.clone(), CallKind::Direct));
+                    }
+                    Terminal::CallIndirect { callee, .. } => {
+                        let typ = callee.typ().get_deref_type().cloned();
+                        for target in program.functions.values() {
+                            if target.id.name() != "main" && Some(func_typ(target)) == typ {
+                                callees.insert((target.id.clone(), CallKind::Indirect));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            (func.id.clone(), callees)
+        })
+        .collect()
+}
@@ -0,0 +1,106 @@
+// control-flow graph utilities shared by the analyses.
+
+use super::*;
+
+// maps every basic block of the function to the blocks that may jump to it.
+// every block (including entry) has an entry in the result.
+pub fn predecessors(func: &Function) -> Map<BbId, Vec<BbId>> {
+    let mut preds: Map<BbId, Vec<BbId>> = func.body.keys().map(|bb| (bb.clone(), vec![])).collect();
+
+    for (label, bb) in &func.body {
+        for succ in bb.term.successors() {
+            preds.entry(succ).or_default().push(label.clone());
+        }
+    }
+
+    preds
+}
+
+// returns the basic blocks reachable from entry in reverse postorder. the
+// traversal is iterative so that very large functions don't overflow the
+// stack.
+pub fn reverse_postorder(func: &Function) -> Vec<BbId> {
+    let entry = bb_id("entry");
+    if !func.body.contains_key(&entry) {
+        return vec![];
+    }
+
+    let mut visited = Set::from([entry.clone()]);
+    let mut postorder = vec![];
+    // each stack frame is a block together with the successors left to visit.
+    let mut stack = vec![(
+        entry.clone(),
+        func.body[&entry].term.successors().into_iter(),
+    )];
+
+    while let Some((label, succs)) = stack.last_mut() {
+        match succs.next() {
+            Some(succ) => {
+                if func.body.contains_key(&succ) && visited.insert(succ.clone()) {
+                    let next = func.body[&succ].term.successors().into_iter();
+                    stack.push((succ, next));
+                }
+            }
+            None => {
+                postorder.push(label.clone());
+                stack.pop();
+            }
+        }
+    }
+
+    postorder.reverse();
+    postorder
+}
+
+// maps every block reachable from entry, except entry itself, to its
+// immediate dominator. uses the iterative algorithm from Cooper, Harvey and
+// Kennedy's "A Simple, Fast Dominance Algorithm".
+pub fn immediate_dominators(func: &Function) -> Map<BbId, BbId> {
+    let rpo = reverse_postorder(func);
+    let order = rpo
+        .iter()
+        .enumerate()
+        .map(|(i, bb)| (bb.clone(), i))
+        .collect::<Map<_, _>>();
+    let preds = predecessors(func);
+
+    // idom[i] is the rpo index of the immediate dominator of rpo[i].
+    let mut idom: Vec<Option<usize>> = vec![None; rpo.len()];
+    if rpo.is_empty() {
+        return Map::new();
+    }
+    idom[0] = Some(0);
+
+    let intersect = |idom: &[Option<usize>], mut a: usize, mut b: usize| {
+        while a != b {
+            while a > b {
+                a = idom[a].unwrap();
+            }
+            while b > a {
+                b = idom[b].unwrap();
+            }
+        }
+        a
+    };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for i in 1..rpo.len() {
+            let new_idom = preds[&rpo[i]]
+                .iter()
+                .filter_map(|p| order.get(p))
+                .filter(|&&p| idom[p].is_some())
+                .copied()
+                .reduce(|a, b| intersect(&idom, a, b));
+            if new_idom.is_some() && idom[i] != new_idom {
+                idom[i] = new_idom;
+                changed = true;
+            }
+        }
+    }
+
+    (1..rpo.len())
+        .map(|i| (rpo[i].clone(), rpo[idom[i].unwrap()].clone()))
+        .collect()
+}
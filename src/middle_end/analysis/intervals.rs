@@ -0,0 +1,744 @@
+// integer interval analysis.
+//
+// an abstract interpretation of each function over the interval domain, with
+// widening at loop headers followed by a few rounds of narrowing. the analysis
+// is intraprocedural: parameters, globals, loaded values and call results are
+// assumed to be arbitrary 32-bit integers, and locals whose address is taken
+// are not tracked. C♭ integers are 32 bits wide but the interpreter computes
+// with 64-bit integers, so the analysis reports
+//
+// - $arith div instructions whose divisor may be zero,
+// - $alloc instructions whose number of elements may be negative, and
+// - $arith instructions whose result may not fit in 32 bits.
+
+use super::*;
+
+// SECTION: the interval domain
+
+// the extremes of i64 represent the infinities.
+const NEG_INF: i64 = i64::MIN;
+const POS_INF: i64 = i64::MAX;
+
+// a non-empty interval [lo, hi]; empty intervals are represented by the
+// absence of an interval (or of an abstract state).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Interval {
+    pub lo: i64,
+    pub hi: i64,
+}
+
+impl Interval {
+    pub fn new(lo: i64, hi: i64) -> Self {
+        assert!(lo <= hi, "empty interval [{lo}, {hi}]");
+        Interval { lo, hi }
+    }
+
+    pub fn constant(n: i64) -> Self {
+        Interval::new(n, n)
+    }
+
+    pub fn top() -> Self {
+        Interval::new(NEG_INF, POS_INF)
+    }
+
+    // the values of a C♭ integer.
+    pub fn int32() -> Self {
+        Interval::new(i32::MIN as i64, i32::MAX as i64)
+    }
+
+    pub fn contains(&self, n: i64) -> bool {
+        self.lo <= n && n <= self.hi
+    }
+
+    pub fn fits_i32(&self) -> bool {
+        self.lo >= i32::MIN as i64 && self.hi <= i32::MAX as i64
+    }
+
+    pub fn join(&self, other: &Interval) -> Interval {
+        Interval::new(self.lo.min(other.lo), self.hi.max(other.hi))
+    }
+
+    pub fn meet(&self, other: &Interval) -> Option<Interval> {
+        let (lo, hi) = (self.lo.max(other.lo), self.hi.min(other.hi));
+        (lo <= hi).then(|| Interval::new(lo, hi))
+    }
+
+    // bounds that are still moving jump straight to infinity.
+    pub fn widen(&self, newer: &Interval) -> Interval {
+        Interval::new(
+            if newer.lo < self.lo { NEG_INF } else { self.lo },
+            if newer.hi > self.hi { POS_INF } else { self.hi },
+        )
+    }
+
+    // only infinite bounds are refined, which guarantees termination.
+    pub fn narrow(&self, newer: &Interval) -> Interval {
+        Interval::new(
+            if self.lo == NEG_INF {
+                newer.lo
+            } else {
+                self.lo
+            },
+            if self.hi == POS_INF {
+                newer.hi
+            } else {
+                self.hi
+            },
+        )
+    }
+
+    fn add(&self, other: &Interval) -> Interval {
+        Interval::new(
+            to_bound(Ext::add(ext(self.lo), ext(other.lo), Ext::NegInf)),
+            to_bound(Ext::add(ext(self.hi), ext(other.hi), Ext::PosInf)),
+        )
+    }
+
+    fn neg(&self) -> Interval {
+        Interval::new(to_bound(ext(self.hi).neg()), to_bound(ext(self.lo).neg()))
+    }
+
+    fn sub(&self, other: &Interval) -> Interval {
+        self.add(&other.neg())
+    }
+
+    fn mul(&self, other: &Interval) -> Interval {
+        corners(self, other, Ext::mul)
+    }
+
+    // returns None if the divisor is exactly zero, i.e., the division always
+    // fails.
+    fn div(&self, other: &Interval) -> Option<Interval> {
+        let negative = other.meet(&Interval::new(NEG_INF, -1));
+        let positive = other.meet(&Interval::new(1, POS_INF));
+        [negative, positive]
+            .into_iter()
+            .flatten()
+            .map(|divisor| corners(self, &divisor, Ext::div))
+            .reduce(|x, y| x.join(&y))
+    }
+}
+
+impl std::fmt::Display for Interval {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let show = |b: i64| match b {
+            NEG_INF => "-inf".to_string(),
+            POS_INF => "+inf".to_string(),
+            n => n.to_string(),
+        };
+        write!(f, "[{}, {}]", show(self.lo), show(self.hi))
+    }
+}
+
+// bounds extended with infinities, used to compute bounds exactly before
+// converting them back to i64.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum Ext {
+    NegInf,
+    Fin(i128),
+    PosInf,
+}
+
+impl Ext {
+    // `conflict` is the result of adding infinities of opposite signs, which
+    // only happens for degenerate intervals.
+    fn add(x: Ext, y: Ext, conflict: Ext) -> Ext {
+        match (x, y) {
+            (Ext::Fin(a), Ext::Fin(b)) => Ext::Fin(a + b),
+            (Ext::NegInf, Ext::PosInf) | (Ext::PosInf, Ext::NegInf) => conflict,
+            (Ext::Fin(_), inf) | (inf, _) => inf,
+        }
+    }
+
+    fn neg(self) -> Ext {
+        match self {
+            Ext::NegInf => Ext::PosInf,
+            Ext::Fin(a) => Ext::Fin(-a),
+            Ext::PosInf => Ext::NegInf,
+        }
+    }
+
+    fn signum(self) -> i128 {
+        match self {
+            Ext::NegInf => -1,
+            Ext::Fin(a) => a.signum(),
+            Ext::PosInf => 1,
+        }
+    }
+
+    fn inf_with_sign(sign: i128) -> Ext {
+        match sign {
+            0 => Ext::Fin(0),
+            s if s < 0 => Ext::NegInf,
+            _ => Ext::PosInf,
+        }
+    }
+
+    fn mul(x: Ext, y: Ext) -> Ext {
+        match (x, y) {
+            (Ext::Fin(a), Ext::Fin(b)) => Ext::Fin(a * b),
+            _ => Ext::inf_with_sign(x.signum() * y.signum()),
+        }
+    }
+
+    // truncating division; the divisor is never zero.
+    fn div(x: Ext, y: Ext) -> Ext {
+        match (x, y) {
+            (Ext::Fin(a), Ext::Fin(b)) => Ext::Fin(a / b),
+            (_, Ext::NegInf) | (_, Ext::PosInf) => Ext::Fin(0),
+            _ => Ext::inf_with_sign(x.signum() * y.signum()),
+        }
+    }
+}
+
+fn ext(b: i64) -> Ext {
+    match b {
+        NEG_INF => Ext::NegInf,
+        POS_INF => Ext::PosInf,
+        n => Ext::Fin(n as i128),
+    }
+}
+
+// finite values that don't fit between the infinities become infinite.
+fn to_bound(e: Ext) -> i64 {
+    match e {
+        Ext::NegInf => NEG_INF,
+        Ext::PosInf => POS_INF,
+        Ext::Fin(n) if n <= NEG_INF as i128 => NEG_INF,
+        Ext::Fin(n) if n >= POS_INF as i128 => POS_INF,
+        Ext::Fin(n) => n as i64,
+    }
+}
+
+// applies a binary operation that is monotone in each argument over each
+// combination of bounds.
+fn corners(x: &Interval, y: &Interval, op: fn(Ext, Ext) -> Ext) -> Interval {
+    let results = [
+        op(ext(x.lo), ext(y.lo)),
+        op(ext(x.lo), ext(y.hi)),
+        op(ext(x.hi), ext(y.lo)),
+        op(ext(x.hi), ext(y.hi)),
+    ];
+    Interval::new(
+        to_bound(*results.iter().min().unwrap()),
+        to_bound(*results.iter().max().unwrap()),
+    )
+}
+
+// moves a bound by one, leaving infinities alone.
+fn step(b: i64, by: i64) -> i64 {
+    match b {
+        NEG_INF | POS_INF => b,
+        n => to_bound(Ext::Fin(n as i128 + by as i128)),
+    }
+}
+
+// SECTION: analysis results
+
+#[derive(Clone, Copy, Debug, Display, Eq, Ord, PartialEq, PartialOrd)]
+pub enum IntervalWarningKind {
+    #[display(fmt = "the divisor may be zero")]
+    DivisionByZero,
+    #[display(fmt = "the number of allocated elements may be negative")]
+    NegativeAlloc,
+    #[display(fmt = "the result may overflow 32 bits")]
+    Overflow,
+}
+
+#[derive(Clone, Debug, Display, Eq, Ord, PartialEq, PartialOrd)]
+#[display(fmt = "instruction at {loc}: {kind}")]
+pub struct IntervalWarning {
+    pub loc: InstLoc,
+    pub kind: IntervalWarningKind,
+}
+
+// abstract states map each tracked variable to its interval; unreachable
+// program points have no state.
+pub type IntervalState = Map<VarId, Interval>;
+
+#[derive(Clone, Debug, Default)]
+pub struct IntervalAnalysis {
+    // the state before each reachable instruction (and terminal).
+    states: Map<InstLoc, IntervalState>,
+    // variables tracked by the analysis, per function.
+    tracked: Map<FuncId, Set<VarId>>,
+    pub warnings: Set<IntervalWarning>,
+}
+
+impl IntervalAnalysis {
+    // the abstract state right before the given instruction, or None if the
+    // instruction is unreachable.
+    pub fn state_before(&self, loc: &InstLoc) -> Option<&IntervalState> {
+        self.states.get(loc)
+    }
+
+    // the interval of an operand right before the given instruction, or None if
+    // the instruction is unreachable. untracked variables may hold any 32-bit
+    // integer.
+    pub fn interval_before(&self, loc: &InstLoc, op: &Operand) -> Option<Interval> {
+        let state = self.states.get(loc)?;
+        Some(eval(state, op))
+    }
+
+    // whether the analysis tracks the variable's value precisely.
+    pub fn is_tracked(&self, func: &FuncId, var: &VarId) -> bool {
+        self.tracked
+            .get(func)
+            .is_some_and(|vars| vars.contains(var))
+    }
+}
+
+// SECTION: analysis implementation
+
+// analyze every function in the program.
+pub fn analyze_intervals(program: &Program) -> IntervalAnalysis {
+    let mut result = IntervalAnalysis::default();
+    for func in program.functions.values() {
+        analyze_function(func, &mut result);
+    }
+    result
+}
+
+// the number of decreasing iterations performed after reaching a fixpoint.
+const NARROWING_ROUNDS: usize = 3;
+
+fn analyze_function(func: &Function, result: &mut IntervalAnalysis) {
+    // locals whose address is taken may change behind our back.
+    let address_taken = func
+        .body
+        .values()
+        .flat_map(|bb| &bb.insts)
+        .filter_map(|inst| match inst {
+            Instruction::AddrOf { rhs, .. } => Some(rhs.clone()),
+            _ => None,
+        })
+        .collect::<Set<_>>();
+    let tracked = func
+        .params
+        .iter()
+        .chain(&func.locals)
+        .filter(|v| v.typ().is_int() && !address_taken.contains(v))
+        .cloned()
+        .collect::<Set<_>>();
+
+    // parameters are arbitrary and locals are zero-initialized.
+    let entry_state = tracked
+        .iter()
+        .map(|v| {
+            let init = if func.params.contains(v) {
+                Interval::int32()
+            } else {
+                Interval::constant(0)
+            };
+            (v.clone(), init)
+        })
+        .collect::<IntervalState>();
+
+    let rpo = reverse_postorder(func);
+    let order = rpo
+        .iter()
+        .enumerate()
+        .map(|(i, bb)| (bb.clone(), i))
+        .collect::<Map<_, _>>();
+    // loop headers are targets of retreating edges.
+    let headers = rpo
+        .iter()
+        .flat_map(|bb| {
+            func.body[bb]
+                .term
+                .successors()
+                .into_iter()
+                .filter(|succ| order[succ] <= order[bb])
+        })
+        .collect::<Set<_>>();
+
+    let entry = bb_id("entry");
+    let mut states: Map<BbId, IntervalState> = Map::new();
+    if rpo.is_empty() {
+        return;
+    }
+    states.insert(entry.clone(), entry_state.clone());
+
+    // ascending phase: chaotic iteration in reverse postorder with widening at
+    // loop headers.
+    let mut worklist = Set::from([0]);
+    while let Some(i) = worklist.pop_first() {
+        let label = &rpo[i];
+        let (_, edges) = transfer_block(&func.body[label], &states[label], &tracked);
+        for (succ, out) in edges {
+            let new = match states.get(&succ) {
+                None => out,
+                Some(old) => {
+                    let joined = join_states(old, &out);
+                    if headers.contains(&succ) {
+                        widen_states(old, &joined)
+                    } else {
+                        joined
+                    }
+                }
+            };
+            if states.get(&succ) != Some(&new) {
+                states.insert(succ.clone(), new);
+                worklist.insert(order[&succ]);
+            }
+        }
+    }
+
+    // descending phase: recompute every block's state from its predecessors,
+    // narrowing at loop headers.
+    for _ in 0..NARROWING_ROUNDS {
+        let mut incoming: Map<BbId, IntervalState> =
+            Map::from([(entry.clone(), entry_state.clone())]);
+        for label in &rpo {
+            if let Some(state) = states.get(label) {
+                for (succ, out) in transfer_block(&func.body[label], state, &tracked).1 {
+                    let joined = match incoming.get(&succ) {
+                        Some(prev) => join_states(prev, &out),
+                        None => out,
+                    };
+                    incoming.insert(succ, joined);
+                }
+            }
+        }
+
+        let mut next = Map::new();
+        for (label, new) in incoming {
+            let state = match states.get(&label) {
+                Some(old) if headers.contains(&label) => narrow_states(old, &new),
+                _ => new,
+            };
+            next.insert(label, state);
+        }
+
+        if next == states {
+            break;
+        }
+        states = next;
+    }
+
+    // record the per-instruction states and report possible errors.
+    for (label, state) in &states {
+        let bb = &func.body[label];
+        let (per_inst, _) = transfer_block(bb, state, &tracked);
+        for (idx, state) in per_inst.into_iter().enumerate() {
+            let loc = InstLoc::new(&func.id, label, idx);
+            if let Some(inst) = bb.insts.get(idx) {
+                for kind in check_inst(&state, inst) {
+                    result.warnings.insert(IntervalWarning {
+                        loc: loc.clone(),
+                        kind,
+                    });
+                }
+            }
+            result.states.insert(loc, state);
+        }
+    }
+    result.tracked.insert(func.id.clone(), tracked);
+}
+
+// possible errors the given instruction may cause in the given state.
+fn check_inst(state: &IntervalState, inst: &Instruction) -> Vec<IntervalWarningKind> {
+    use IntervalWarningKind::*;
+
+    match inst {
+        Instruction::Arith { aop, op1, op2, .. } => {
+            let mut kinds = vec![];
+            let (x, y) = (eval(state, op1), eval(state, op2));
+            if *aop == ArithmeticOp::Divide && y.contains(0) {
+                kinds.push(DivisionByZero);
+            }
+            if !arith(*aop, &x, &y).is_none_or(|r| r.fits_i32()) {
+                kinds.push(Overflow);
+            }
+            kinds
+        }
+        Instruction::Alloc { num, .. } if eval(state, num).lo < 0 => vec![NegativeAlloc],
+        _ => vec![],
+    }
+}
+
+fn eval(state: &IntervalState, op: &Operand) -> Interval {
+    match op {
+        Operand::CInt(n) => Interval::constant(*n as i64),
+        Operand::Var(v) => state.get(v).copied().unwrap_or_else(Interval::int32),
+    }
+}
+
+// the result of an arithmetic operation, or None if it always fails.
+fn arith(aop: ArithmeticOp, x: &Interval, y: &Interval) -> Option<Interval> {
+    match aop {
+        ArithmeticOp::Add => Some(x.add(y)),
+        ArithmeticOp::Subtract => Some(x.sub(y)),
+        ArithmeticOp::Multiply => Some(x.mul(y)),
+        ArithmeticOp::Divide => x.div(y),
+    }
+}
+
+// the possible results of comparing integers in the given intervals.
+fn compare(rop: ComparisonOp, x: &Interval, y: &Interval) -> Interval {
+    let (always, never) = match rop {
+        ComparisonOp::Eq => (x.lo == x.hi && *x == *y, x.meet(y).is_none()),
+        ComparisonOp::Neq => (x.meet(y).is_none(), x.lo == x.hi && *x == *y),
+        ComparisonOp::Less => (x.hi < y.lo, x.lo >= y.hi),
+        ComparisonOp::LessEq => (x.hi <= y.lo, x.lo > y.hi),
+        ComparisonOp::Greater => (x.lo > y.hi, x.hi <= y.lo),
+        ComparisonOp::GreaterEq => (x.lo >= y.hi, x.hi < y.lo),
+    };
+    match (always, never) {
+        (true, _) => Interval::constant(1),
+        (_, true) => Interval::constant(0),
+        _ => Interval::new(0, 1),
+    }
+}
+
+fn assign(state: &mut IntervalState, tracked: &Set<VarId>, lhs: &VarId, value: Interval) {
+    if tracked.contains(lhs) {
+        state.insert(lhs.clone(), value);
+    }
+}
+
+// executes one instruction; returns false if the instruction always fails.
+fn transfer_inst(state: &mut IntervalState, inst: &Instruction, tracked: &Set<VarId>) -> bool {
+    use Instruction::*;
+
+    match inst {
+        Arith { lhs, aop, op1, op2 } => match arith(*aop, &eval(state, op1), &eval(state, op2)) {
+            Some(value) => assign(state, tracked, lhs, value),
+            None => return false,
+        },
+        Cmp { lhs, rop, op1, op2 } => {
+            let value = if op1.typ().is_int() && op2.typ().is_int() {
+                compare(*rop, &eval(state, op1), &eval(state, op2))
+            } else {
+                Interval::new(0, 1)
+            };
+            assign(state, tracked, lhs, value);
+        }
+        Copy { lhs, op } => assign(state, tracked, lhs, eval(state, op)),
+        Phi { lhs, args } => {
+            if let Some(value) = args
+                .iter()
+                .map(|op| eval(state, op))
+                .reduce(|x, y| x.join(&y))
+            {
+                assign(state, tracked, lhs, value);
+            }
+        }
+        CallExt { lhs: Some(lhs), .. } | Load { lhs, .. } => {
+            assign(state, tracked, lhs, Interval::int32())
+        }
+        Alloc { num, .. } => {
+            if eval(state, num).hi < 0 {
+                return false;
+            }
+        }
+        AddrOf { .. }
+        | CallExt { lhs: None, .. }
+        | Free { .. }
+        | Gep { .. }
+        | Gfp { .. }
+        | Store { .. } => {}
+    }
+
+    true
+}
+
+// executes a basic block, returning the states before each instruction and
+// the terminal (if reachable), and the states flowing to each successor.
+fn transfer_block(
+    bb: &BasicBlock,
+    state: &IntervalState,
+    tracked: &Set<VarId>,
+) -> (Vec<IntervalState>, Vec<(BbId, IntervalState)>) {
+    let mut per_inst = vec![];
+    let mut state = state.clone();
+
+    for inst in &bb.insts {
+        per_inst.push(state.clone());
+        if !transfer_inst(&mut state, inst, tracked) {
+            return (per_inst, vec![]);
+        }
+    }
+    per_inst.push(state.clone());
+
+    let edges = match &bb.term {
+        Terminal::Branch { cond, tt, ff } => [(tt, true), (ff, false)]
+            .into_iter()
+            .filter_map(|(target, taken)| {
+                refine_branch(&state, bb, cond, taken, tracked).map(|s| (target.clone(), s))
+            })
+            .collect(),
+        Terminal::CallDirect { lhs, next_bb, .. } | Terminal::CallIndirect { lhs, next_bb, .. } => {
+            if let Some(lhs) = lhs {
+                assign(&mut state, tracked, lhs, Interval::int32());
+            }
+            vec![(next_bb.clone(), state)]
+        }
+        Terminal::Jump(target) => vec![(target.clone(), state)],
+        Terminal::Ret(_) => vec![],
+    };
+
+    (per_inst, edges)
+}
+
+// the state along the true (taken) or false edge of a branch, or None if that
+// edge can't be taken.
+fn refine_branch(
+    state: &IntervalState,
+    bb: &BasicBlock,
+    cond: &Operand,
+    taken: bool,
+    tracked: &Set<VarId>,
+) -> Option<IntervalState> {
+    let c = match cond {
+        Operand::CInt(n) => return ((*n != 0) == taken).then(|| state.clone()),
+        Operand::Var(c) => c,
+    };
+
+    let mut state = state.clone();
+
+    // if the condition is computed by a comparison whose operands are unchanged
+    // since (including by the comparison itself), refine the operands too.
+    let defining = bb.insts.iter().rposition(|inst| defines(inst, c));
+    if let Some(pos) = defining {
+        if let Instruction::Cmp { lhs, rop, op1, op2 } = &bb.insts[pos] {
+            let unchanged = |op: &Operand| match op {
+                Operand::Var(v) => {
+                    v != lhs && !bb.insts[pos + 1..].iter().any(|inst| defines(inst, v))
+                }
+                Operand::CInt(_) => true,
+            };
+            if op1.typ().is_int() && op2.typ().is_int() && unchanged(op1) && unchanged(op2) {
+                let rop = if taken { *rop } else { negate(*rop) };
+                state = refine_cmp(state, rop, op1, op2, tracked)?;
+            }
+        }
+    }
+
+    // the condition itself is non-zero on the true edge and zero on the false
+    // edge.
+    let value = eval(&state, cond);
+    let refined = if taken {
+        match (value.lo, value.hi) {
+            (0, 0) => None,
+            (0, hi) => Some(Interval::new(1, hi)),
+            (lo, 0) => Some(Interval::new(lo, -1)),
+            _ => Some(value),
+        }
+    } else {
+        value.meet(&Interval::constant(0))
+    }?;
+    assign(&mut state, tracked, c, refined);
+
+    Some(state)
+}
+
+// refines the operands of a comparison assuming that it holds.
+fn refine_cmp(
+    mut state: IntervalState,
+    rop: ComparisonOp,
+    op1: &Operand,
+    op2: &Operand,
+    tracked: &Set<VarId>,
+) -> Option<IntervalState> {
+    use ComparisonOp::*;
+
+    let (x, y) = (eval(&state, op1), eval(&state, op2));
+    let (x, y) = match rop {
+        Eq => {
+            let both = x.meet(&y)?;
+            (both, both)
+        }
+        Neq => {
+            // only a constant at the end of the other interval can be removed.
+            let exclude = |x: Interval, y: Interval| -> Option<Interval> {
+                if y.lo != y.hi {
+                    Some(x)
+                } else if x.lo == y.lo && x.hi == y.lo {
+                    None
+                } else if x.lo == y.lo {
+                    Some(Interval::new(step(x.lo, 1), x.hi))
+                } else if x.hi == y.lo {
+                    Some(Interval::new(x.lo, step(x.hi, -1)))
+                } else {
+                    Some(x)
+                }
+            };
+            (exclude(x, y)?, exclude(y, x)?)
+        }
+        Less => (
+            x.meet(&Interval::new(NEG_INF, step(y.hi, -1)))?,
+            y.meet(&Interval::new(step(x.lo, 1), POS_INF))?,
+        ),
+        LessEq => (
+            x.meet(&Interval::new(NEG_INF, y.hi))?,
+            y.meet(&Interval::new(x.lo, POS_INF))?,
+        ),
+        Greater => return refine_cmp(state, Less, op2, op1, tracked),
+        GreaterEq => return refine_cmp(state, LessEq, op2, op1, tracked),
+    };
+
+    if let Operand::Var(v) = op1 {
+        assign(&mut state, tracked, v, x);
+    }
+    if let Operand::Var(v) = op2 {
+        assign(&mut state, tracked, v, y);
+    }
+
+    Some(state)
+}
+
+fn negate(rop: ComparisonOp) -> ComparisonOp {
+    use ComparisonOp::*;
+
+    match rop {
+        Eq => Neq,
+        Neq => Eq,
+        Less => GreaterEq,
+        LessEq => Greater,
+        Greater => LessEq,
+        GreaterEq => Less,
+    }
+}
+
+// whether the instruction assigns to the variable.
+fn defines(inst: &Instruction, var: &VarId) -> bool {
+    use Instruction::*;
+
+    match inst {
+        AddrOf { lhs, .. }
+        | Alloc { lhs, .. }
+        | Arith { lhs, .. }
+        | Cmp { lhs, .. }
+        | Copy { lhs, .. }
+        | Gep { lhs, .. }
+        | Gfp { lhs, .. }
+        | Load { lhs, .. }
+        | Phi { lhs, .. } => lhs == var,
+        CallExt { lhs, .. } => lhs.as_ref() == Some(var),
+        Free { .. } | Store { .. } => false,
+    }
+}
+
+fn join_states(x: &IntervalState, y: &IntervalState) -> IntervalState {
+    combine_states(x, y, Interval::join)
+}
+
+fn widen_states(old: &IntervalState, new: &IntervalState) -> IntervalState {
+    combine_states(old, new, Interval::widen)
+}
+
+fn narrow_states(old: &IntervalState, new: &IntervalState) -> IntervalState {
+    combine_states(old, new, Interval::narrow)
+}
+
+// all reachable states track the same variables.
+fn combine_states(
+    x: &IntervalState,
+    y: &IntervalState,
+    f: fn(&Interval, &Interval) -> Interval,
+) -> IntervalState {
+    x.iter()
+        .map(|(v, i)| (v.clone(), y.get(v).map_or(*i, |j| f(i, j))))
+        .collect()
+}
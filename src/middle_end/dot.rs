@@ -0,0 +1,179 @@
+// Graphviz (DOT) output for debugging: per-function control-flow graphs, the
+// whole-program call graph and dominator trees. render the output with e.g.
+// `dot -Tsvg out.dot > out.svg`.
+
+use crate::middle_end::analysis::{call_graph, immediate_dominators, CallKind};
+use crate::middle_end::lir::*;
+
+// SECTION: public interface
+
+// the control-flow graph of a single function. each node is a basic block
+// listing its instructions.
+pub fn cfg_to_dot(func: &Function) -> String {
+    digraph(&func.id.to_string(), &cfg_body(func))
+}
+
+// the control-flow graphs of all functions, one cluster per function.
+pub fn program_cfgs_to_dot(program: &Program) -> String {
+    let clusters = program
+        .functions
+        .values()
+        .map(|func| cluster(&func.id, &cfg_body(func)))
+        .collect::<Vec<_>>();
+    digraph("program", &clusters)
+}
+
+// the dominator tree of a single function.
+pub fn dominator_tree_to_dot(func: &Function) -> String {
+    digraph(&func.id.to_string(), &dominator_tree_body(func))
+}
+
+// the dominator trees of all functions, one cluster per function.
+pub fn program_dominator_trees_to_dot(program: &Program) -> String {
+    let clusters = program
+        .functions
+        .values()
+        .map(|func| cluster(&func.id, &dominator_tree_body(func)))
+        .collect::<Vec<_>>();
+    digraph("dominators", &clusters)
+}
+
+// the call graph of the program. indirect calls (dashed edges) go to every
+// function with a matching type; extern functions are drawn as ellipses and
+// calls to them as dotted edges.
+pub fn call_graph_to_dot(program: &Program) -> String {
+    let mut lines = vec!["node [shape=box]".to_string()];
+
+    for func in program.functions.keys() {
+        lines.push(quote(func.name()));
+    }
+    for ext in program.externs.keys() {
+        lines.push(format!("{} [shape=ellipse]", quote(ext.name())));
+    }
+
+    for (caller, callees) in call_graph(program) {
+        for (callee, kind) in callees {
+            let style = match kind {
+                CallKind::Direct => "",
+                CallKind::Indirect => " [style=dashed]",
+                CallKind::Extern => " [style=dotted]",
+            };
+            lines.push(format!(
+                "{} -> {}{style}",
+                quote(caller.name()),
+                quote(callee.name())
+            ));
+        }
+    }
+
+    digraph("callgraph", &lines)
+}
+
+// SECTION: helpers
+
+fn cfg_body(func: &Function) -> Vec<String> {
+    let mut lines = vec!["node [shape=box, fontname=monospace]".to_string()];
+
+    for (label, bb) in &func.body {
+        // left-justify every line of the label.
+        let text = std::iter::once(format!("{label}:"))
+            .chain(bb.insts.iter().map(|inst| format!("  {inst}")))
+            .chain(std::iter::once(format!("  {}", bb.term)))
+            .map(|line| escape(&line) + "\\l")
+            .collect::<String>();
+        let exit = if matches!(bb.term, Terminal::Ret(_)) {
+            ", peripheries=2"
+        } else {
+            ""
+        };
+        lines.push(format!(
+            "{} [label=\"{text}\"{exit}]",
+            node(&func.id, label)
+        ));
+    }
+
+    for (label, bb) in &func.body {
+        let from = node(&func.id, label);
+        match &bb.term {
+            Terminal::Branch { tt, ff, .. } => {
+                lines.push(format!("{from} -> {} [label=\"true\"]", node(&func.id, tt)));
+                lines.push(format!(
+                    "{from} -> {} [label=\"false\"]",
+                    node(&func.id, ff)
+                ));
+            }
+            Terminal::CallDirect { next_bb, .. } | Terminal::CallIndirect { next_bb, .. } => {
+                lines.push(format!(
+                    "{from} -> {} [label=\"return\", style=dashed]",
+                    node(&func.id, next_bb)
+                ));
+            }
+            Terminal::Jump(target) => lines.push(format!("{from} -> {}", node(&func.id, target))),
+            Terminal::Ret(_) => {}
+        }
+    }
+
+    lines
+}
+
+fn dominator_tree_body(func: &Function) -> Vec<String> {
+    let idoms = immediate_dominators(func);
+    let mut lines = vec!["node [shape=box]".to_string()];
+
+    // unreachable blocks aren't part of the tree.
+    if func.body.contains_key(&bb_id("entry")) {
+        lines.push(format!(
+            "{} [label={}]",
+            node(&func.id, &bb_id("entry")),
+            quote("entry")
+        ));
+    }
+    for (bb, idom) in &idoms {
+        lines.push(format!(
+            "{} [label={}]",
+            node(&func.id, bb),
+            quote(bb.name())
+        ));
+        lines.push(format!(
+            "{} -> {}",
+            node(&func.id, idom),
+            node(&func.id, bb)
+        ));
+    }
+
+    lines
+}
+
+pub(crate) fn digraph(name: &str, lines: &[String]) -> String {
+    let body = lines
+        .iter()
+        .flat_map(|line| line.lines())
+        .map(|line| format!("  {line}\n"))
+        .collect::<String>();
+    format!("digraph {} {{\n{body}}}\n", quote(name))
+}
+
+fn cluster(func: &FuncId, lines: &[String]) -> String {
+    let body = lines
+        .iter()
+        .map(|line| format!("  {line}\n"))
+        .collect::<String>();
+    format!(
+        "subgraph {} {{\n  label={}\n{body}}}",
+        quote(&format!("cluster_{func}")),
+        quote(func.name())
+    )
+}
+
+// blocks are named after their function so that clusters don't clash.
+fn node(func: &FuncId, bb: &BbId) -> String {
+    quote(&format!("{func}.{bb}"))
+}
+
+pub(crate) fn quote(s: &str) -> String {
+    format!("\"{}\"", escape(s))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -10,10 +10,15 @@ use derive_more::Display;
 use hashconsing::HConsed;
 use serde::{Deserialize, Serialize};
 
+mod arbitrary_impl;
 mod associated_impl;
+pub mod builder;
 mod display_impl;
+pub mod equiv;
+pub mod facts;
 mod fromstr_impl;
 mod id_type_factories;
+mod link;
 mod misc_impl;
 mod validate;
 
@@ -21,6 +26,7 @@ pub use self::associated_impl::*;
 pub use self::display_impl::*;
 pub use self::fromstr_impl::*;
 pub use self::id_type_factories::*;
+pub use self::link::*;
 pub use self::misc_impl::*;
 pub use self::validate::*;
 
@@ -137,6 +143,12 @@ pub enum Instruction {
         lhs: VarId,
         op: Operand,
     },
+    Free {
+        // Deallocates the object(s) src points to, which must have been
+        // allocated by an `Alloc` and not freed yet. src must point to the
+        // start of the allocation.
+        src: VarId,
+    },
     Gep {
         // Get Element Pointer.  Sets lhs to an offset of idx elements from src.
         // Both lhs and src are pointers of the same type.
@@ -229,6 +241,19 @@ pub enum ComparisonOp {
     GreaterEq,
 }
 
+// SECTION: program locations
+
+// the location of an instruction: index `idx` into the instructions of basic
+// block `bb` of function `func`. following the validator's convention, the
+// index `insts.len()` refers to the block's terminal.
+#[derive(Clone, Debug, Display, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[display(fmt = "{func}.{bb}.{idx}")]
+pub struct InstLoc {
+    pub func: FuncId,
+    pub bb: BbId,
+    pub idx: usize,
+}
+
 // A convenient and more fluent way to refer to arithmetic and comparison
 // operations.
 macro_rules! LirOp {
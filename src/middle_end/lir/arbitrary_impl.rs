@@ -0,0 +1,543 @@
+// generation of random valid LIR programs (e.g., for fuzzing the passes and the
+// LIR parser). the generated programs satisfy every check of validate():
+//
+// - all the names are distinct (except for struct fields, which are distinct
+//   within their struct), so there is no shadowing, and alloc ids are unique.
+// - the blocks of a function are generated in order, and every block but the
+//   last one falls through to the next one (via a $jump, a call's next block
+//   or a branch target), so every block is reachable from entry and reaches
+//   the last block, which holds the only $ret.
+// - every instruction is built to be well-typed, using the variables in scope
+//   of the right types or constants (0 for pointers).
+//
+// the programs don't necessarily terminate or run without errors.
+
+use arbitrary::{Arbitrary, Result, Unstructured};
+
+use super::*;
+
+// SECTION: interface
+
+impl<'a> Arbitrary<'a> for Program {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Generator::new(u).program()
+    }
+}
+
+// SECTION: generator
+
+// the size limits of the generated programs.
+const MAX_STRUCTS: usize = 2;
+const MAX_FIELDS: usize = 3;
+const MAX_EXTERNS: usize = 2;
+const MAX_FUNCTIONS: usize = 3;
+const MAX_GLOBALS: usize = 3;
+const MAX_PARAMS: usize = 3;
+const MAX_LOCALS: usize = 5;
+const MAX_BLOCKS: usize = 5;
+const MAX_INSTS: usize = 5;
+const MAX_PHI_ARGS: usize = 3;
+const MAX_CONST: i32 = 10;
+
+struct Generator<'a, 'u> {
+    u: &'u mut Unstructured<'a>,
+    structs: Map<StructId, Set<FieldId>>,
+    globals: Set<VarId>,
+    externs: Map<FuncId, Type>,
+    // the signatures of the functions, which are generated before their bodies
+    // so that any function can call any other one.
+    signatures: Map<FuncId, (Vec<VarId>, Option<Type>)>,
+    // the variables in scope in the current function.
+    vars: Vec<VarId>,
+    // the variables the current function has assigned an $alloc to so far.
+    allocated: Vec<VarId>,
+    alloc_ctr: u32,
+}
+
+impl<'a, 'u> Generator<'a, 'u> {
+    fn new(u: &'u mut Unstructured<'a>) -> Self {
+        Generator {
+            u,
+            structs: Map::new(),
+            globals: Set::new(),
+            externs: Map::new(),
+            signatures: Map::new(),
+            vars: vec![],
+            allocated: vec![],
+            alloc_ctr: 0,
+        }
+    }
+
+    fn program(mut self) -> Result<Program> {
+        // structs are generated in two steps because fields may point to any
+        // struct.
+        let num_structs = self.u.int_in_range(0..=MAX_STRUCTS)?;
+        let ids = (0..num_structs)
+            .map(|i| struct_id(&format!("st{i}")))
+            .collect::<Vec<_>>();
+        for id in &ids {
+            let mut fields = Set::new();
+            for j in 0..self.u.int_in_range(1..=MAX_FIELDS)? {
+                let typ = match self.u.int_in_range(0..=2)? {
+                    0 => ptr_ty(int_ty()),
+                    1 => ptr_ty(struct_ty(self.u.choose(&ids)?.clone())),
+                    _ => int_ty(),
+                };
+                fields.insert(field_id(&format!("x{j}"), typ));
+            }
+            self.structs.insert(id.clone(), fields);
+        }
+
+        for i in 0..self.u.int_in_range(0..=MAX_EXTERNS)? {
+            let typ = self.func_type(MAX_PARAMS, false)?;
+            self.externs.insert(func_id(&format!("e{i}")), typ);
+        }
+
+        // function parameters can only point to the functions generated before
+        // them, which avoids cycles in the types.
+        for i in 0..self.u.int_in_range(0..=MAX_FUNCTIONS)? {
+            let name = format!("f{i}");
+            let id = func_id(&name);
+            let mut params = vec![];
+            for j in 0..self.u.int_in_range(0..=MAX_PARAMS)? {
+                let typ = self.var_type(true)?;
+                params.push(var_id(&format!("p{j}"), typ, Some(id.clone())));
+            }
+            let ret_ty = match self.u.arbitrary()? {
+                true => Some(self.var_type(true)?),
+                false => None,
+            };
+            // some functions have a function pointer, as if their address was
+            // taken.
+            if self.u.arbitrary()? {
+                let typ = func_ty(ret_ty.clone(), params.iter().map(VarId::typ).collect());
+                self.globals.insert(var_id(&name, ptr_ty(typ), None));
+            }
+            self.signatures.insert(id, (params, ret_ty));
+        }
+        self.signatures
+            .insert(func_id("main"), (vec![], Some(int_ty())));
+
+        for i in 0..self.u.int_in_range(0..=MAX_GLOBALS)? {
+            let typ = self.var_type(true)?;
+            self.globals.insert(var_id(&format!("g{i}"), typ, None));
+        }
+
+        let mut functions = Map::new();
+        for id in self.signatures.keys().cloned().collect::<Vec<_>>() {
+            let function = self.function(id.clone())?;
+            functions.insert(id, function);
+        }
+
+        Ok(Program {
+            structs: self.structs,
+            globals: self.globals,
+            functions,
+            externs: self.externs,
+        })
+    }
+
+    fn function(&mut self, id: FuncId) -> Result<Function> {
+        let (params, ret_ty) = self.signatures[&id].clone();
+
+        // there is at least one local, so that instructions have a left-hand
+        // side.
+        let mut locals = Set::new();
+        for j in 0..self.u.int_in_range(1..=MAX_LOCALS)? {
+            let typ = self.var_type(true)?;
+            locals.insert(var_id(&format!("x{j}"), typ, Some(id.clone())));
+        }
+
+        self.vars = self.globals.iter().cloned().collect();
+        self.vars.extend(params.iter().cloned());
+        self.vars.extend(locals.iter().cloned());
+        self.allocated.clear();
+
+        let num_blocks = self.u.int_in_range(1..=MAX_BLOCKS)?;
+        let labels = (0..num_blocks)
+            .map(|i| match i {
+                0 => bb_id("entry"),
+                _ => bb_id(&format!("bb{i}")),
+            })
+            .collect::<Vec<_>>();
+
+        let mut body = Map::new();
+        for (i, label) in labels.iter().enumerate() {
+            let mut insts = vec![];
+            for _ in 0..self.u.int_in_range(0..=MAX_INSTS)? {
+                insts.push(self.inst()?);
+            }
+            let term = match labels.get(i + 1) {
+                Some(next) => self.terminal(next, &labels)?,
+                None => Terminal::Ret(match &ret_ty {
+                    Some(typ) => Some(self.operand(typ)?),
+                    None => None,
+                }),
+            };
+            body.insert(
+                label.clone(),
+                BasicBlock {
+                    id: label.clone(),
+                    insts,
+                    term,
+                },
+            );
+        }
+
+        Ok(Function {
+            id,
+            ret_ty,
+            params,
+            locals,
+            body,
+        })
+    }
+
+    // SECTION: instructions
+
+    // a well-typed instruction, falling back on a $copy if the chosen kind of
+    // instruction can't be built from the variables in scope.
+    fn inst(&mut self) -> Result<Instruction> {
+        use Instruction::*;
+
+        let inst = match self.u.int_in_range(0..=11)? {
+            0 => match self.lhs(|_, typ| typ.is_int())? {
+                Some(lhs) => Some(Arith {
+                    lhs,
+                    aop: *self.u.choose(&[
+                        ArithmeticOp::Add,
+                        ArithmeticOp::Subtract,
+                        ArithmeticOp::Multiply,
+                        ArithmeticOp::Divide,
+                    ])?,
+                    op1: self.operand(&int_ty())?,
+                    op2: self.operand(&int_ty())?,
+                }),
+                None => None,
+            },
+            1 => match self.lhs(|_, typ| typ.is_int())? {
+                Some(lhs) => {
+                    let rop = *self.u.choose(&[
+                        ComparisonOp::Eq,
+                        ComparisonOp::Neq,
+                        ComparisonOp::Less,
+                        ComparisonOp::LessEq,
+                        ComparisonOp::Greater,
+                        ComparisonOp::GreaterEq,
+                    ])?;
+                    let typ = self.u.choose(&self.operand_types())?.clone();
+                    Some(Cmp {
+                        lhs,
+                        rop,
+                        op1: self.operand(&typ)?,
+                        op2: self.operand(&typ)?,
+                    })
+                }
+                None => None,
+            },
+            2 => match self.lhs(|gen, typ| {
+                typ.get_deref_type()
+                    .is_some_and(|typ| gen.vars_of(typ).next().is_some())
+            })? {
+                Some(lhs) => {
+                    let rhs = self.var_of(lhs.typ().get_deref_type().unwrap())?.unwrap();
+                    Some(AddrOf { lhs, rhs })
+                }
+                None => None,
+            },
+            3 => match self.lhs(|_, typ| is_data_ptr(typ))? {
+                Some(lhs) => {
+                    self.alloc_ctr += 1;
+                    let typ = lhs.typ().get_deref_type().unwrap().clone();
+                    let id = var_id(&format!("_a{}", self.alloc_ctr), typ, None);
+                    let num = self.operand(&int_ty())?;
+                    self.allocated.push(lhs.clone());
+                    Some(Alloc { lhs, num, id })
+                }
+                None => None,
+            },
+            4 => match self.lhs(|_, typ| is_data_ptr(typ))? {
+                Some(lhs) => Some(Gep {
+                    src: self.var_of(&lhs.typ())?.unwrap(),
+                    idx: self.operand(&int_ty())?,
+                    lhs,
+                }),
+                None => None,
+            },
+            5 => self.gfp()?,
+            6 => match self.lhs(|gen, typ| gen.vars_of(&ptr_ty(typ.clone())).next().is_some())? {
+                Some(lhs) => Some(Load {
+                    src: self.var_of(&ptr_ty(lhs.typ()))?.unwrap(),
+                    lhs,
+                }),
+                None => None,
+            },
+            7 => match self
+                .lhs(|_, typ| is_data_ptr(typ) && !typ.get_deref_type().unwrap().is_struct())?
+            {
+                Some(dst) => Some(Store {
+                    op: self.operand(dst.typ().get_deref_type().unwrap())?,
+                    dst,
+                }),
+                None => None,
+            },
+            8 => self.call_ext()?,
+            9 => {
+                let lhs = self.lhs(|_, _| true)?.unwrap();
+                let mut args = vec![];
+                for _ in 0..self.u.int_in_range(1..=MAX_PHI_ARGS)? {
+                    args.push(self.operand(&lhs.typ())?);
+                }
+                Some(Phi { lhs, args })
+            }
+            10 => match self.allocated.is_empty() {
+                true => None,
+                false => Some(Free {
+                    src: self.u.choose(&self.allocated)?.clone(),
+                }),
+            },
+            _ => None,
+        };
+
+        Ok(match inst {
+            Some(inst) => inst,
+            None => {
+                let lhs = self.lhs(|_, _| true)?.unwrap();
+                let op = self.operand(&lhs.typ())?;
+                Copy { lhs, op }
+            }
+        })
+    }
+
+    // a $gfp, if some struct pointer in scope has a field whose type a
+    // variable points to.
+    fn gfp(&mut self) -> Result<Option<Instruction>> {
+        let mut candidates = vec![];
+        for var in &self.vars {
+            let var_ty = var.typ();
+            let Some(LirType::Struct(id)) = var_ty.get_deref_type().map(|typ| &*typ.0) else {
+                continue;
+            };
+            for field in &self.structs[id] {
+                if self.vars_of(&ptr_ty(field.typ.clone())).next().is_some() {
+                    candidates.push((var.clone(), field.clone()));
+                }
+            }
+        }
+        if candidates.is_empty() {
+            return Ok(None);
+        }
+
+        let (src, field) = self.u.choose(&candidates)?.clone();
+        let lhs = self.lhs(|_, typ| *typ == ptr_ty(field.typ.clone()))?;
+        Ok(lhs.map(|lhs| Instruction::Gfp { lhs, src, field }))
+    }
+
+    // a call of an extern, if there is one. its result is assigned to a
+    // variable, if it returns one, only sometimes.
+    fn call_ext(&mut self) -> Result<Option<Instruction>> {
+        let externs = self
+            .externs
+            .iter()
+            .map(|(id, typ)| (id.clone(), typ.clone()))
+            .collect::<Vec<_>>();
+        if externs.is_empty() {
+            return Ok(None);
+        }
+
+        let (ext_callee, typ) = self.u.choose(&externs)?.clone();
+        let LirType::Function { ret_ty, param_ty } = &*typ.0 else {
+            unreachable!()
+        };
+        let lhs = match ret_ty {
+            Some(ret_ty) if self.u.arbitrary()? => self.lhs(|_, typ| typ == ret_ty)?,
+            _ => None,
+        };
+        Ok(Some(Instruction::CallExt {
+            lhs,
+            ext_callee,
+            args: self.args(param_ty)?,
+        }))
+    }
+
+    // SECTION: terminals
+
+    // a terminal for a block which isn't the last one, that may go to the next
+    // block, or also to any of the given blocks.
+    fn terminal(&mut self, next: &BbId, labels: &[BbId]) -> Result<Terminal> {
+        use Terminal::*;
+
+        let next_bb = next.clone();
+        Ok(match self.u.int_in_range(0..=3)? {
+            0 => {
+                let other = self.u.choose(labels)?.clone();
+                let (tt, ff) = match self.u.arbitrary()? {
+                    true => (next_bb, other),
+                    false => (other, next_bb),
+                };
+                Branch {
+                    cond: self.operand(&int_ty())?,
+                    tt,
+                    ff,
+                }
+            }
+            1 => {
+                let callees = self
+                    .signatures
+                    .keys()
+                    .filter(|id| id.name() != "main")
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if callees.is_empty() {
+                    return Ok(Jump(next_bb));
+                }
+                let callee = self.u.choose(&callees)?.clone();
+                let (params, ret_ty) = self.signatures[&callee].clone();
+                let param_ty = params.iter().map(VarId::typ).collect::<Vec<_>>();
+                CallDirect {
+                    lhs: self.call_lhs(&ret_ty)?,
+                    callee,
+                    args: self.args(&param_ty)?,
+                    next_bb,
+                }
+            }
+            2 => {
+                let callees = self
+                    .vars
+                    .iter()
+                    .filter(|var| var.typ().get_deref_type().is_some_and(Type::is_function))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if callees.is_empty() {
+                    return Ok(Jump(next_bb));
+                }
+                let callee = self.u.choose(&callees)?.clone();
+                let typ = callee.typ();
+                let LirType::Function { ret_ty, param_ty } = &*typ.get_deref_type().unwrap().0
+                else {
+                    unreachable!()
+                };
+                CallIndirect {
+                    lhs: self.call_lhs(ret_ty)?,
+                    args: self.args(param_ty)?,
+                    callee,
+                    next_bb,
+                }
+            }
+            _ => Jump(next_bb),
+        })
+    }
+
+    // the variable a call's result is assigned to, if any.
+    fn call_lhs(&mut self, ret_ty: &Option<Type>) -> Result<Option<VarId>> {
+        match ret_ty {
+            Some(typ) if self.u.arbitrary()? => self.var_of(typ),
+            _ => Ok(None),
+        }
+    }
+
+    fn args(&mut self, param_ty: &[Type]) -> Result<Vec<Operand>> {
+        param_ty.iter().map(|typ| self.operand(typ)).collect()
+    }
+
+    // SECTION: helpers
+
+    // a type for a variable: an int, or a pointer to an int, an int pointer, a
+    // struct or (if fn_ptrs is true) a function in a signature generated so
+    // far.
+    fn var_type(&mut self, fn_ptrs: bool) -> Result<Type> {
+        let mut types = vec![
+            int_ty(),
+            int_ty(),
+            ptr_ty(int_ty()),
+            ptr_ty(ptr_ty(int_ty())),
+        ];
+        types.extend(self.structs.keys().map(|id| ptr_ty(struct_ty(id.clone()))));
+        if fn_ptrs {
+            types.extend(
+                self.signatures
+                    .iter()
+                    .filter(|(id, _)| id.name() != "main")
+                    .map(|(_, (params, ret_ty))| {
+                        ptr_ty(func_ty(
+                            ret_ty.clone(),
+                            params.iter().map(VarId::typ).collect(),
+                        ))
+                    }),
+            );
+        }
+        Ok(self.u.choose(&types)?.clone())
+    }
+
+    fn func_type(&mut self, max_params: usize, fn_ptrs: bool) -> Result<Type> {
+        let mut params = vec![];
+        for _ in 0..self.u.int_in_range(0..=max_params)? {
+            params.push(self.var_type(fn_ptrs)?);
+        }
+        let ret_ty = match self.u.arbitrary()? {
+            true => Some(self.var_type(fn_ptrs)?),
+            false => None,
+        };
+        Ok(func_ty(ret_ty, params))
+    }
+
+    // a variable with a type satisfying pred that can be assigned, i.e., any
+    // variable in scope except for the function pointers named after functions.
+    fn lhs(&mut self, pred: impl Fn(&Self, &Type) -> bool) -> Result<Option<VarId>> {
+        let vars = self
+            .vars
+            .iter()
+            .filter(|var| !self.signatures.contains_key(&func_id(var.name())))
+            .filter(|var| pred(self, &var.typ()))
+            .cloned()
+            .collect::<Vec<_>>();
+        match vars.is_empty() {
+            true => Ok(None),
+            false => Ok(Some(self.u.choose(&vars)?.clone())),
+        }
+    }
+
+    // a variable in scope with the given type, if there is one.
+    fn var_of(&mut self, typ: &Type) -> Result<Option<VarId>> {
+        let vars = self.vars_of(typ).cloned().collect::<Vec<_>>();
+        match vars.is_empty() {
+            true => Ok(None),
+            false => Ok(Some(self.u.choose(&vars)?.clone())),
+        }
+    }
+
+    // the variables in scope with the given type.
+    fn vars_of<'s>(&'s self, typ: &'s Type) -> impl Iterator<Item = &'s VarId> {
+        self.vars.iter().filter(move |var| var.typ() == *typ)
+    }
+
+    // an operand with the given type, where 0 may stand for a nil pointer.
+    fn operand(&mut self, typ: &Type) -> Result<Operand> {
+        if let Some(var) = self.var_of(typ)? {
+            if self.u.ratio(2, 3)? {
+                return Ok(Operand::Var(var));
+            }
+        }
+        Ok(Operand::CInt(match typ.is_int() {
+            true => self.u.int_in_range(-MAX_CONST..=MAX_CONST)?,
+            false => 0,
+        }))
+    }
+
+    // the types of the operands that can be compared: ints and the pointers
+    // in scope.
+    fn operand_types(&self) -> Vec<Type> {
+        let mut types = vec![int_ty()];
+        for var in &self.vars {
+            if var.typ().is_ptr() && !types.contains(&var.typ()) {
+                types.push(var.typ());
+            }
+        }
+        types
+    }
+}
+
+// whether typ is a pointer to data (and not to a function).
+fn is_data_ptr(typ: &Type) -> bool {
+    typ.get_deref_type().is_some_and(|typ| !typ.is_function())
+}
@@ -98,4 +98,251 @@ impl Operand {
             int_ty()
         }
     }
+
+    pub fn var(&self) -> Option<&VarId> {
+        match self {
+            Operand::Var(v) => Some(v),
+            Operand::CInt(_) => None,
+        }
+    }
+}
+
+impl Instruction {
+    // returns the variable the instruction assigns to, if any ($store assigns
+    // through a pointer rather than to a variable, and $free assigns nothing).
+    pub fn lhs(&self) -> Option<&VarId> {
+        use Instruction::*;
+
+        match self {
+            AddrOf { lhs, .. }
+            | Alloc { lhs, .. }
+            | Arith { lhs, .. }
+            | Cmp { lhs, .. }
+            | Copy { lhs, .. }
+            | Gep { lhs, .. }
+            | Gfp { lhs, .. }
+            | Load { lhs, .. }
+            | Phi { lhs, .. } => Some(lhs),
+            CallExt { lhs, .. } => lhs.as_ref(),
+            Free { .. } | Store { .. } => None,
+        }
+    }
+
+    // returns the variables whose values the instruction reads, in the order
+    // they appear ($addrof takes the address of its operand without reading it).
+    pub fn uses(&self) -> Vec<&VarId> {
+        use Instruction::*;
+
+        let ops: Vec<&Operand> = match self {
+            AddrOf { .. } => vec![],
+            Alloc { num, .. } => vec![num],
+            Arith { op1, op2, .. } | Cmp { op1, op2, .. } => vec![op1, op2],
+            CallExt { args, .. } | Phi { args, .. } => args.iter().collect(),
+            Copy { op, .. } => vec![op],
+            Gep { src, idx, .. } => return [src].into_iter().chain(idx.var()).collect(),
+            Free { src } | Gfp { src, .. } | Load { src, .. } => return vec![src],
+            Store { dst, op } => return [dst].into_iter().chain(op.var()).collect(),
+        };
+        ops.into_iter().filter_map(Operand::var).collect()
+    }
+
+    // the instruction's name in LIR syntax, e.g., `$arith`.
+    pub fn opcode(&self) -> &'static str {
+        use Instruction::*;
+
+        match self {
+            AddrOf { .. } => "$addrof",
+            Alloc { .. } => "$alloc",
+            Arith { .. } => "$arith",
+            CallExt { .. } => "$call_ext",
+            Cmp { .. } => "$cmp",
+            Copy { .. } => "$copy",
+            Free { .. } => "$free",
+            Gep { .. } => "$gep",
+            Gfp { .. } => "$gfp",
+            Load { .. } => "$load",
+            Phi { .. } => "$phi",
+            Store { .. } => "$store",
+        }
+    }
+}
+
+impl Terminal {
+    // returns the variable the result of a call is assigned to, if any.
+    pub fn lhs(&self) -> Option<&VarId> {
+        match self {
+            Terminal::CallDirect { lhs, .. } | Terminal::CallIndirect { lhs, .. } => lhs.as_ref(),
+            _ => None,
+        }
+    }
+
+    // returns the variables whose values the terminal reads, in the order they
+    // appear.
+    pub fn uses(&self) -> Vec<&VarId> {
+        match self {
+            Terminal::Branch { cond, .. } => cond.var().into_iter().collect(),
+            Terminal::CallDirect { args, .. } => args.iter().filter_map(Operand::var).collect(),
+            Terminal::CallIndirect { callee, args, .. } => [callee]
+                .into_iter()
+                .chain(args.iter().filter_map(Operand::var))
+                .collect(),
+            Terminal::Jump(_) | Terminal::Ret(None) => vec![],
+            Terminal::Ret(Some(op)) => op.var().into_iter().collect(),
+        }
+    }
+
+    // the terminal's name in LIR syntax, e.g., `$branch`.
+    pub fn opcode(&self) -> &'static str {
+        match self {
+            Terminal::Branch { .. } => "$branch",
+            Terminal::CallDirect { .. } => "$call_dir",
+            Terminal::CallIndirect { .. } => "$call_idr",
+            Terminal::Jump(_) => "$jump",
+            Terminal::Ret(_) => "$ret",
+        }
+    }
+
+    // returns the basic blocks control may flow to after this terminal, in the
+    // order they appear in the instruction.
+    pub fn successors(&self) -> Vec<BbId> {
+        match self {
+            Terminal::Branch { tt, ff, .. } => vec![tt.clone(), ff.clone()],
+            Terminal::CallDirect { next_bb, .. } | Terminal::CallIndirect { next_bb, .. } => {
+                vec![next_bb.clone()]
+            }
+            Terminal::Jump(target) => vec![target.clone()],
+            Terminal::Ret(_) => vec![],
+        }
+    }
+}
+
+impl InstLoc {
+    pub fn new(func: &FuncId, bb: &BbId, idx: usize) -> Self {
+        InstLoc {
+            func: func.clone(),
+            bb: bb.clone(),
+            idx,
+        }
+    }
+}
+
+// renaming helpers: each returns a copy with every variable v (including alloc
+// ids) replaced by var(v) and every basic block label l replaced by bb(l).
+// variables are visited in the order they appear in the program text.
+
+impl Operand {
+    pub fn map_vars(&self, var: &mut impl FnMut(&VarId) -> VarId) -> Operand {
+        match self {
+            Operand::CInt(n) => Operand::CInt(*n),
+            Operand::Var(v) => Operand::Var(var(v)),
+        }
+    }
+}
+
+impl Instruction {
+    pub fn map_vars(&self, var: &mut impl FnMut(&VarId) -> VarId) -> Instruction {
+        use Instruction::*;
+
+        match self {
+            AddrOf { lhs, rhs } => AddrOf {
+                lhs: var(lhs),
+                rhs: var(rhs),
+            },
+            Alloc { lhs, num, id } => Alloc {
+                lhs: var(lhs),
+                num: num.map_vars(var),
+                id: var(id),
+            },
+            Arith { lhs, aop, op1, op2 } => Arith {
+                lhs: var(lhs),
+                aop: *aop,
+                op1: op1.map_vars(var),
+                op2: op2.map_vars(var),
+            },
+            CallExt {
+                lhs,
+                ext_callee,
+                args,
+            } => CallExt {
+                lhs: lhs.as_ref().map(&mut *var),
+                ext_callee: ext_callee.clone(),
+                args: args.iter().map(|op| op.map_vars(var)).collect(),
+            },
+            Cmp { lhs, rop, op1, op2 } => Cmp {
+                lhs: var(lhs),
+                rop: *rop,
+                op1: op1.map_vars(var),
+                op2: op2.map_vars(var),
+            },
+            Copy { lhs, op } => Copy {
+                lhs: var(lhs),
+                op: op.map_vars(var),
+            },
+            Free { src } => Free { src: var(src) },
+            Gep { lhs, src, idx } => Gep {
+                lhs: var(lhs),
+                src: var(src),
+                idx: idx.map_vars(var),
+            },
+            Gfp { lhs, src, field } => Gfp {
+                lhs: var(lhs),
+                src: var(src),
+                field: field.clone(),
+            },
+            Load { lhs, src } => Load {
+                lhs: var(lhs),
+                src: var(src),
+            },
+            Phi { lhs, args } => Phi {
+                lhs: var(lhs),
+                args: args.iter().map(|op| op.map_vars(var)).collect(),
+            },
+            Store { dst, op } => Store {
+                dst: var(dst),
+                op: op.map_vars(var),
+            },
+        }
+    }
+}
+
+impl Terminal {
+    pub fn map_vars_and_blocks(
+        &self,
+        var: &mut impl FnMut(&VarId) -> VarId,
+        bb: &mut impl FnMut(&BbId) -> BbId,
+    ) -> Terminal {
+        use Terminal::*;
+
+        match self {
+            Branch { cond, tt, ff } => Branch {
+                cond: cond.map_vars(var),
+                tt: bb(tt),
+                ff: bb(ff),
+            },
+            CallDirect {
+                lhs,
+                callee,
+                args,
+                next_bb,
+            } => CallDirect {
+                lhs: lhs.as_ref().map(&mut *var),
+                callee: callee.clone(),
+                args: args.iter().map(|op| op.map_vars(var)).collect(),
+                next_bb: bb(next_bb),
+            },
+            CallIndirect {
+                lhs,
+                callee,
+                args,
+                next_bb,
+            } => CallIndirect {
+                lhs: lhs.as_ref().map(&mut *var),
+                callee: var(callee),
+                args: args.iter().map(|op| op.map_vars(var)).collect(),
+                next_bb: bb(next_bb),
+            },
+            Jump(target) => Jump(bb(target)),
+            Ret(op) => Ret(op.as_ref().map(|op| op.map_vars(var))),
+        }
+    }
 }
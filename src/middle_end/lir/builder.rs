@@ -0,0 +1,665 @@
+// a fluent API for constructing LIR programs in rust code (e.g., in tests)
+// without writing LIR text or assembling the datatypes by hand:
+//
+//     let mut p = ProgramBuilder::new();
+//     let mut f = p.function("main", vec![], Some(int_ty()));
+//     let x = f.local("x", int_ty());
+//     let exit = f.fresh_label();
+//     let mut b = f.entry();
+//     let t = b.arith(LirOp![+], &x, 1);
+//     b.jump(&exit);
+//     f.block(&exit).ret(t);
+//     f.finish();
+//     let program = p.finish()?;
+//
+// - variables scoped to a function that are used in its body are added to the
+//   function's locals automatically.
+// - helpers that compute a value put it in a fresh temporary whose type is
+//   inferred from the operands, and return that temporary.
+// - a basic block becomes part of its function once it gets a terminal.
+//   terminal helpers consume the block builder, so no instructions can be
+//   added after a terminal; building the same block twice panics.
+// - calls are terminals; the call helpers return a builder for a fresh block
+//   that control returns to.
+// - everything else (e.g., blocks that never got a terminal, ill-typed
+//   instructions) is reported by ProgramBuilder::finish(), which validates the
+//   program.
+
+use super::*;
+use crate::commons::ValidationError;
+
+// SECTION: programs
+
+#[derive(Clone, Debug, Default)]
+pub struct ProgramBuilder {
+    structs: Map<StructId, Set<FieldId>>,
+    globals: Set<VarId>,
+    functions: Map<FuncId, Function>,
+    externs: Map<FuncId, Type>,
+    signatures: Map<FuncId, Type>, // types of declared internal functions
+    alloc_ids: Set<String>,        // names of the alloc ids used so far
+    alloc_ctr: u32,                // for generating fresh alloc ids
+    errors: ValidationError,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // defines a struct with the given fields.
+    pub fn struct_def(&mut self, name: &str, fields: Vec<(&str, Type)>) -> StructId {
+        let id = struct_id(name);
+        let fields = fields
+            .into_iter()
+            .map(|(field, typ)| field_id(field, typ))
+            .collect();
+        if self.structs.insert(id.clone(), fields).is_some() {
+            self.errors
+                .add_error(format!("struct {id} is defined more than once"));
+        }
+        id
+    }
+
+    // declares a global variable.
+    pub fn global(&mut self, name: &str, typ: Type) -> VarId {
+        let var = var_id(name, typ, None);
+        self.globals.insert(var.clone());
+        var
+    }
+
+    // declares an external function.
+    pub fn extern_decl(&mut self, name: &str, params: Vec<Type>, ret_ty: Option<Type>) -> FuncId {
+        let id = func_id(name);
+        self.externs.insert(id.clone(), func_ty(ret_ty, params));
+        id
+    }
+
+    // declares the signature of an internal function before it is built, so
+    // that it can be called (e.g., for mutual recursion).
+    pub fn declare(&mut self, name: &str, params: Vec<Type>, ret_ty: Option<Type>) -> FuncId {
+        let id = func_id(name);
+        let typ = func_ty(ret_ty, params);
+        match self.signatures.get(&id) {
+            Some(prev) if *prev != typ => self.errors.add_error(format!(
+                "function {id} is declared with types {prev} and {typ}"
+            )),
+            _ => {
+                self.signatures.insert(id.clone(), typ);
+            }
+        }
+        id
+    }
+
+    // declares (if necessary) and returns the global function pointer to a
+    // declared internal function.
+    pub fn func_ptr(&mut self, name: &str) -> VarId {
+        let typ = self
+            .signatures
+            .get(&func_id(name))
+            .unwrap_or_else(|| panic!("function {name} hasn't been declared"))
+            .clone();
+        self.global(name, ptr_ty(typ))
+    }
+
+    // starts building an internal function; the function is added to the
+    // program by FunctionBuilder::finish().
+    pub fn function(
+        &mut self,
+        name: &str,
+        params: Vec<(&str, Type)>,
+        ret_ty: Option<Type>,
+    ) -> FunctionBuilder<'_> {
+        let id = self.declare(
+            name,
+            params.iter().map(|(_, typ)| typ.clone()).collect(),
+            ret_ty.clone(),
+        );
+        let params = params
+            .into_iter()
+            .map(|(param, typ)| var_id(param, typ, Some(id.clone())))
+            .collect();
+
+        FunctionBuilder {
+            program: self,
+            func: Function {
+                id,
+                ret_ty,
+                params,
+                locals: Set::new(),
+                body: Map::new(),
+            },
+            labels: Set::new(),
+            tmp_ctr: 0,
+            bb_ctr: 0,
+        }
+    }
+
+    // returns the program if it is valid, otherwise all the problems found
+    // while building and validating it.
+    pub fn finish(self) -> Result<Program, ValidationError> {
+        let mut errors = self.errors;
+        for id in self.signatures.keys() {
+            if !self.functions.contains_key(id) {
+                errors.add_error(format!("function {id} is declared but never built"));
+            }
+        }
+
+        let program = Program {
+            structs: self.structs,
+            globals: self.globals,
+            functions: self.functions,
+            externs: self.externs,
+        };
+        if let Err(e) = validate(&program) {
+            errors += e;
+        }
+
+        if errors.is_empty() {
+            Ok(program)
+        } else {
+            Err(errors)
+        }
+    }
+
+    // creates an alloc id for objects of type typ that hasn't been used yet.
+    fn fresh_alloc_id(&mut self, typ: Type) -> VarId {
+        loop {
+            self.alloc_ctr += 1;
+            let name = format!("_a{}", self.alloc_ctr);
+            if !self.alloc_ids.contains(&name) {
+                self.alloc_ids.insert(name.clone());
+                return var_id(&name, typ, None);
+            }
+        }
+    }
+
+    fn field(&self, src: &VarId, name: &str) -> FieldId {
+        let fields = src
+            .typ()
+            .get_deref_type()
+            .and_then(|typ| match &*typ.0 {
+                LirType::Struct(id) => self.structs.get(id),
+                _ => None,
+            })
+            .unwrap_or_else(|| {
+                panic!(
+                    "{} isn't a pointer to a defined struct",
+                    src.typed_to_string()
+                )
+            });
+        fields
+            .iter()
+            .find(|f| *f.name == name)
+            .unwrap_or_else(|| panic!("{} has no field {name}", src.typed_to_string()))
+            .clone()
+    }
+}
+
+// SECTION: functions
+
+#[derive(Debug)]
+pub struct FunctionBuilder<'p> {
+    program: &'p mut ProgramBuilder,
+    func: Function,
+    labels: Set<BbId>, // labels handed out by fresh_label() or used by block()
+    tmp_ctr: u32,      // for generating fresh temporary variables
+    bb_ctr: u32,       // for generating fresh basic blocks
+}
+
+impl<'p> FunctionBuilder<'p> {
+    pub fn id(&self) -> &FuncId {
+        &self.func.id
+    }
+
+    pub fn params(&self) -> &[VarId] {
+        &self.func.params
+    }
+
+    // returns the parameter with the given name.
+    pub fn param(&self, name: &str) -> VarId {
+        self.func
+            .params
+            .iter()
+            .find(|p| p.name() == name)
+            .unwrap_or_else(|| panic!("function {} has no parameter {name}", self.func.id))
+            .clone()
+    }
+
+    // declares a local variable.
+    pub fn local(&mut self, name: &str, typ: Type) -> VarId {
+        let var = var_id(name, typ, Some(self.func.id.clone()));
+        self.func.locals.insert(var.clone());
+        var
+    }
+
+    // returns the global variable with the given name.
+    pub fn global(&self, name: &str) -> VarId {
+        self.program
+            .globals
+            .iter()
+            .find(|g| g.name() == name)
+            .unwrap_or_else(|| panic!("there is no global {name}"))
+            .clone()
+    }
+
+    // see ProgramBuilder::func_ptr().
+    pub fn func_ptr(&mut self, name: &str) -> VarId {
+        self.program.func_ptr(name)
+    }
+
+    // creates a fresh temporary variable and declares it as a local.
+    pub fn fresh_temp(&mut self, typ: Type) -> VarId {
+        loop {
+            self.tmp_ctr += 1;
+            let name = format!("_t{}", self.tmp_ctr);
+            let taken = self.func.locals.iter().any(|v| v.name() == name)
+                || self.func.params.iter().any(|v| v.name() == name);
+            if !taken {
+                return self.local(&name, typ);
+            }
+        }
+    }
+
+    // creates a fresh basic block label.
+    pub fn fresh_label(&mut self) -> BbId {
+        loop {
+            self.bb_ctr += 1;
+            let label = bb_id(&format!("bb{}", self.bb_ctr));
+            if self.labels.insert(label.clone()) {
+                return label;
+            }
+        }
+    }
+
+    // starts building the entry block.
+    pub fn entry(&mut self) -> BlockBuilder<'_, 'p> {
+        self.block(&bb_id("entry"))
+    }
+
+    // starts building the block with the given label. panics if that block
+    // has already been started.
+    pub fn block(&mut self, label: &BbId) -> BlockBuilder<'_, 'p> {
+        self.labels.insert(label.clone());
+        assert!(
+            !self.func.body.contains_key(label),
+            "block {label} of function {} has already been built",
+            self.func.id
+        );
+        BlockBuilder {
+            func: self,
+            label: label.clone(),
+            insts: vec![],
+        }
+    }
+
+    // adds the function to the program.
+    pub fn finish(self) {
+        let mut func = self.func;
+        let program = self.program;
+
+        // every label handed out should have become a block, but we leave
+        // checking jumps to missing blocks to the validator.
+        for label in &self.labels {
+            if !func.body.contains_key(label) && is_target(&func, label) {
+                program.errors.add_error(format!(
+                    "block {label} of function {} was never given a terminal",
+                    func.id
+                ));
+            }
+        }
+
+        // auto-register locals.
+        let used = func
+            .body
+            .values()
+            .flat_map(|bb| {
+                bb.insts
+                    .iter()
+                    .flat_map(inst_vars)
+                    .chain(term_vars(&bb.term))
+            })
+            .filter(|v| v.scope().as_ref() == Some(&func.id) && !func.params.contains(v))
+            .collect::<Vec<_>>();
+        func.locals.extend(used);
+
+        if program.functions.contains_key(&func.id) {
+            program
+                .errors
+                .add_error(format!("function {} is built more than once", func.id));
+        }
+        program.functions.insert(func.id.clone(), func);
+    }
+}
+
+// SECTION: basic blocks
+
+#[derive(Debug)]
+pub struct BlockBuilder<'f, 'p> {
+    func: &'f mut FunctionBuilder<'p>,
+    label: BbId,
+    insts: Vec<Instruction>,
+}
+
+impl<'f, 'p> BlockBuilder<'f, 'p> {
+    pub fn label(&self) -> &BbId {
+        &self.label
+    }
+
+    // the builder of the enclosing function, e.g. to declare locals or create
+    // labels while building this block.
+    pub fn func(&mut self) -> &mut FunctionBuilder<'p> {
+        self.func
+    }
+
+    // appends an arbitrary instruction.
+    pub fn push(&mut self, inst: Instruction) {
+        if let Instruction::Alloc { id, .. } = &inst {
+            self.func.program.alloc_ids.insert(id.name().to_string());
+        }
+        self.insts.push(inst);
+    }
+
+    pub fn addrof(&mut self, rhs: &VarId) -> VarId {
+        let lhs = self.func.fresh_temp(ptr_ty(rhs.typ()));
+        self.push(Instruction::AddrOf {
+            lhs: lhs.clone(),
+            rhs: rhs.clone(),
+        });
+        lhs
+    }
+
+    // allocates num objects of type typ with a fresh alloc id.
+    pub fn alloc(&mut self, typ: Type, num: impl Into<Operand>) -> VarId {
+        let lhs = self.func.fresh_temp(ptr_ty(typ.clone()));
+        let id = self.func.program.fresh_alloc_id(typ);
+        self.push(Instruction::Alloc {
+            lhs: lhs.clone(),
+            num: num.into(),
+            id,
+        });
+        lhs
+    }
+
+    pub fn arith(
+        &mut self,
+        aop: ArithmeticOp,
+        op1: impl Into<Operand>,
+        op2: impl Into<Operand>,
+    ) -> VarId {
+        let lhs = self.func.fresh_temp(int_ty());
+        self.push(Instruction::Arith {
+            lhs: lhs.clone(),
+            aop,
+            op1: op1.into(),
+            op2: op2.into(),
+        });
+        lhs
+    }
+
+    // calls a declared extern; returns the result if the extern has a return
+    // type.
+    pub fn call_ext(&mut self, name: &str, args: Vec<Operand>) -> Option<VarId> {
+        let ext_callee = func_id(name);
+        let typ = self
+            .func
+            .program
+            .externs
+            .get(&ext_callee)
+            .unwrap_or_else(|| panic!("extern {name} hasn't been declared"))
+            .clone();
+        let lhs = ret_ty(&typ).map(|typ| self.func.fresh_temp(typ));
+        self.push(Instruction::CallExt {
+            lhs: lhs.clone(),
+            ext_callee,
+            args,
+        });
+        lhs
+    }
+
+    pub fn cmp(
+        &mut self,
+        rop: ComparisonOp,
+        op1: impl Into<Operand>,
+        op2: impl Into<Operand>,
+    ) -> VarId {
+        let lhs = self.func.fresh_temp(int_ty());
+        self.push(Instruction::Cmp {
+            lhs: lhs.clone(),
+            rop,
+            op1: op1.into(),
+            op2: op2.into(),
+        });
+        lhs
+    }
+
+    pub fn copy(&mut self, op: impl Into<Operand>) -> VarId {
+        let op = op.into();
+        let lhs = self.func.fresh_temp(op.typ());
+        self.copy_to(&lhs, op);
+        lhs
+    }
+
+    // assigns op to an existing variable.
+    pub fn copy_to(&mut self, lhs: &VarId, op: impl Into<Operand>) {
+        self.push(Instruction::Copy {
+            lhs: lhs.clone(),
+            op: op.into(),
+        });
+    }
+
+    pub fn free(&mut self, src: &VarId) {
+        self.push(Instruction::Free { src: src.clone() });
+    }
+
+    pub fn gep(&mut self, src: &VarId, idx: impl Into<Operand>) -> VarId {
+        let lhs = self.func.fresh_temp(src.typ());
+        self.push(Instruction::Gep {
+            lhs: lhs.clone(),
+            src: src.clone(),
+            idx: idx.into(),
+        });
+        lhs
+    }
+
+    // gets a pointer to the field with the given name of the struct src
+    // points to.
+    pub fn gfp(&mut self, src: &VarId, field: &str) -> VarId {
+        let field = self.func.program.field(src, field);
+        let lhs = self.func.fresh_temp(ptr_ty(field.typ.clone()));
+        self.push(Instruction::Gfp {
+            lhs: lhs.clone(),
+            src: src.clone(),
+            field,
+        });
+        lhs
+    }
+
+    pub fn load(&mut self, src: &VarId) -> VarId {
+        let typ = src
+            .typ()
+            .get_deref_type()
+            .unwrap_or_else(|| panic!("cannot load from non-pointer {}", src.typed_to_string()))
+            .clone();
+        let lhs = self.func.fresh_temp(typ);
+        self.push(Instruction::Load {
+            lhs: lhs.clone(),
+            src: src.clone(),
+        });
+        lhs
+    }
+
+    // the result has the type of the first argument.
+    pub fn phi(&mut self, args: Vec<Operand>) -> VarId {
+        let typ = args.first().map_or_else(int_ty, Operand::typ);
+        let lhs = self.func.fresh_temp(typ);
+        self.push(Instruction::Phi {
+            lhs: lhs.clone(),
+            args,
+        });
+        lhs
+    }
+
+    pub fn store(&mut self, dst: &VarId, op: impl Into<Operand>) {
+        self.push(Instruction::Store {
+            dst: dst.clone(),
+            op: op.into(),
+        });
+    }
+
+    // ends the block with an arbitrary terminal.
+    pub fn terminate(self, term: Terminal) {
+        let bb = BasicBlock {
+            id: self.label.clone(),
+            insts: self.insts,
+            term,
+        };
+        self.func.func.body.insert(self.label, bb);
+    }
+
+    pub fn branch(self, cond: impl Into<Operand>, tt: &BbId, ff: &BbId) {
+        self.terminate(Terminal::Branch {
+            cond: cond.into(),
+            tt: tt.clone(),
+            ff: ff.clone(),
+        });
+    }
+
+    pub fn jump(self, target: &BbId) {
+        self.terminate(Terminal::Jump(target.clone()));
+    }
+
+    pub fn ret(self, op: impl Into<Operand>) {
+        self.terminate(Terminal::Ret(Some(op.into())));
+    }
+
+    pub fn ret_void(self) {
+        self.terminate(Terminal::Ret(None));
+    }
+
+    // calls a declared internal function. returns the result (if the callee
+    // has a return type) and a builder for the fresh block the call returns
+    // to.
+    pub fn call(self, name: &str, args: Vec<Operand>) -> (Option<VarId>, BlockBuilder<'f, 'p>) {
+        let callee = func_id(name);
+        let typ = self
+            .func
+            .program
+            .signatures
+            .get(&callee)
+            .unwrap_or_else(|| panic!("function {name} hasn't been declared"))
+            .clone();
+        self.call_with(&typ, |lhs, next_bb| Terminal::CallDirect {
+            lhs,
+            callee,
+            args,
+            next_bb,
+        })
+    }
+
+    // calls the function callee points to; see call().
+    pub fn call_indirect(
+        self,
+        callee: &VarId,
+        args: Vec<Operand>,
+    ) -> (Option<VarId>, BlockBuilder<'f, 'p>) {
+        let typ = callee
+            .typ()
+            .get_deref_type()
+            .filter(|typ| typ.is_function())
+            .unwrap_or_else(|| panic!("{} isn't a function pointer", callee.typed_to_string()))
+            .clone();
+        let callee = callee.clone();
+        self.call_with(&typ, |lhs, next_bb| Terminal::CallIndirect {
+            lhs,
+            callee,
+            args,
+            next_bb,
+        })
+    }
+
+    fn call_with(
+        self,
+        typ: &Type,
+        term: impl FnOnce(Option<VarId>, BbId) -> Terminal,
+    ) -> (Option<VarId>, BlockBuilder<'f, 'p>) {
+        let lhs = ret_ty(typ).map(|typ| self.func.fresh_temp(typ));
+        let next_bb = self.func.fresh_label();
+
+        let bb = BasicBlock {
+            id: self.label.clone(),
+            insts: self.insts,
+            term: term(lhs.clone(), next_bb.clone()),
+        };
+        self.func.func.body.insert(self.label, bb);
+
+        let next = self.func.block(&next_bb);
+        (lhs, next)
+    }
+}
+
+// SECTION: helpers
+
+// the return type of a function type.
+fn ret_ty(typ: &Type) -> Option<Type> {
+    match &*typ.0 {
+        LirType::Function { ret_ty, .. } => ret_ty.clone(),
+        _ => unreachable!(),
+    }
+}
+
+// whether some block of func has label as a successor.
+fn is_target(func: &Function, label: &BbId) -> bool {
+    func.body
+        .values()
+        .any(|bb| bb.term.successors().contains(label))
+}
+
+fn inst_vars(inst: &Instruction) -> Vec<VarId> {
+    use Instruction::*;
+
+    let (defs, ops): (Vec<&VarId>, Vec<&Operand>) = match inst {
+        AddrOf { lhs, rhs } => (vec![lhs, rhs], vec![]),
+        Alloc { lhs, num, .. } => (vec![lhs], vec![num]),
+        Arith { lhs, op1, op2, .. } | Cmp { lhs, op1, op2, .. } => (vec![lhs], vec![op1, op2]),
+        CallExt { lhs, args, .. } => (lhs.iter().collect(), args.iter().collect()),
+        Copy { lhs, op } => (vec![lhs], vec![op]),
+        Gep { lhs, src, idx } => (vec![lhs, src], vec![idx]),
+        Free { src } => (vec![src], vec![]),
+        Gfp { lhs, src, .. } | Load { lhs, src } => (vec![lhs, src], vec![]),
+        Phi { lhs, args } => (vec![lhs], args.iter().collect()),
+        Store { dst, op } => (vec![dst], vec![op]),
+    };
+    defs.into_iter()
+        .cloned()
+        .chain(ops.into_iter().filter_map(op_var))
+        .collect()
+}
+
+fn term_vars(term: &Terminal) -> Vec<VarId> {
+    match term {
+        Terminal::Branch { cond, .. } => op_var(cond).into_iter().collect(),
+        Terminal::CallDirect { lhs, args, .. } => lhs
+            .iter()
+            .cloned()
+            .chain(args.iter().filter_map(op_var))
+            .collect(),
+        Terminal::CallIndirect {
+            lhs, callee, args, ..
+        } => lhs
+            .iter()
+            .cloned()
+            .chain(std::iter::once(callee.clone()))
+            .chain(args.iter().filter_map(op_var))
+            .collect(),
+        Terminal::Jump(_) => vec![],
+        Terminal::Ret(op) => op.iter().filter_map(op_var).collect(),
+    }
+}
+
+fn op_var(op: &Operand) -> Option<VarId> {
+    match op {
+        Operand::Var(v) => Some(v.clone()),
+        Operand::CInt(_) => None,
+    }
+}
@@ -101,7 +101,9 @@ impl Display for Function {
         } else {
             "".to_string()
         };
-        let body = join(&self.body, |(_, x)| x.to_string(), "\n");
+        let mut blocks = self.body.values().collect::<Vec<_>>();
+        blocks.sort_by_key(|bb| block_order(&bb.id));
+        let body = join(blocks, |x| x.to_string(), "\n");
         write!(
             f,
             "fn {name}({params}) -> {ret_ty} {{\n{locals}{}{body}}}\n",
@@ -110,6 +112,15 @@ impl Display for Function {
     }
 }
 
+// blocks are displayed with entry first and the others ordered by label,
+// comparing numeric suffixes as numbers (so bb2 comes before bb10).
+fn block_order(label: &BbId) -> (bool, &str, usize, &str) {
+    let name = label.name();
+    let prefix = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    let suffix = &name[prefix.len()..];
+    (name != "entry", prefix, suffix.len(), suffix)
+}
+
 impl Display for BasicBlock {
     fn fmt(&self, f: &mut Formatter) -> Result {
         let lbl = self.id.to_string();
@@ -141,6 +152,7 @@ impl Display for Instruction {
             }
             Cmp { lhs, rop, op1, op2 } => format!("{lhs} = $cmp {rop} {op1} {op2}"),
             Copy { lhs, op } => format!("{lhs} = $copy {op}"),
+            Free { src } => format!("$free {src}"),
             Gep { lhs, src, idx } => {
                 format!("{lhs} = $gep {src} {idx}")
             }
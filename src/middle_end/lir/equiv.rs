@@ -0,0 +1,543 @@
+// alpha-equivalence of programs: two programs are equivalent if they are equal
+// up to a consistent renaming of local variables (including parameters and
+// temporaries), basic blocks and alloc ids. structs, globals, externs and
+// function names must be identical.
+//
+// blocks are matched by walking both control-flow graphs in parallel from
+// entry, and variables and alloc ids are matched as they are encountered. when
+// the programs aren't equivalent, diff() describes the differences function by
+// function and block by block; differing blocks are shown as a minimal
+// line-based diff, with the right-hand program's names replaced by the
+// matching left-hand names where they are known.
+
+use super::*;
+
+// SECTION: public interface
+
+pub fn alpha_equivalent(left: &Program, right: &Program) -> bool {
+    diff(left, right).is_none()
+}
+
+// returns None if the programs are alpha-equivalent.
+pub fn diff(left: &Program, right: &Program) -> Option<Diff> {
+    let mut diff = Diff::default();
+
+    for (id, fields) in &left.structs {
+        match right.structs.get(id) {
+            None => diff.program.push(format!("struct {id} only in left")),
+            Some(other) if other != fields => diff.program.push(format!("struct {id} differs")),
+            Some(_) => {}
+        }
+    }
+    for id in right.structs.keys() {
+        if !left.structs.contains_key(id) {
+            diff.program.push(format!("struct {id} only in right"));
+        }
+    }
+
+    for g in left.globals.difference(&right.globals) {
+        diff.program
+            .push(format!("global {} only in left", g.typed_to_string()));
+    }
+    for g in right.globals.difference(&left.globals) {
+        diff.program
+            .push(format!("global {} only in right", g.typed_to_string()));
+    }
+
+    for (id, typ) in &left.externs {
+        match right.externs.get(id) {
+            None => diff.program.push(format!("extern {id}:{typ} only in left")),
+            Some(other) if other != typ => diff.program.push(format!(
+                "extern {id} has type {typ} in left and {other} in right"
+            )),
+            Some(_) => {}
+        }
+    }
+    for (id, typ) in &right.externs {
+        if !left.externs.contains_key(id) {
+            diff.program
+                .push(format!("extern {id}:{typ} only in right"));
+        }
+    }
+
+    // alloc ids are unique across the whole program.
+    let mut allocs = Bijection::default();
+    for (id, func) in &left.functions {
+        match right.functions.get(id) {
+            None => diff.program.push(format!("function {id} only in left")),
+            Some(other) => {
+                let mut matcher = Matcher::new(left, func, other, &mut allocs);
+                let func_diff = matcher.run();
+                if !func_diff.is_empty() {
+                    diff.functions.insert(id.clone(), func_diff);
+                }
+            }
+        }
+    }
+    for id in right.functions.keys() {
+        if !left.functions.contains_key(id) {
+            diff.program.push(format!("function {id} only in right"));
+        }
+    }
+
+    if diff.program.is_empty() && diff.functions.is_empty() {
+        None
+    } else {
+        Some(diff)
+    }
+}
+
+// SECTION: differences
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Diff {
+    // differences in structs, globals, externs and the set of functions.
+    pub program: Vec<String>,
+    pub functions: Map<FuncId, FunctionDiff>,
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FunctionDiff {
+    // differences in the signature and declared locals.
+    pub signature: Vec<String>,
+    pub blocks: Vec<BlockDiff>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BlockDiff {
+    // matching blocks whose instructions differ.
+    Changed {
+        left: BbId,
+        right: BbId,
+        lines: Vec<DiffLine>,
+    },
+    OnlyLeft(BbId),
+    OnlyRight(BbId),
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DiffLine {
+    Same(String),
+    Left(String),
+    Right(String),
+}
+
+impl FunctionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.signature.is_empty() && self.blocks.is_empty()
+    }
+}
+
+impl std::fmt::Display for Diff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.program {
+            writeln!(f, "{line}")?;
+        }
+        for (id, func) in &self.functions {
+            writeln!(f, "function {id}:")?;
+            for line in &func.signature {
+                writeln!(f, "  {line}")?;
+            }
+            for block in &func.blocks {
+                match block {
+                    BlockDiff::Changed { left, right, lines } => {
+                        if left == right {
+                            writeln!(f, "  block {left}:")?;
+                        } else {
+                            writeln!(f, "  block {left} (right: {right}):")?;
+                        }
+                        for line in lines {
+                            match line {
+                                DiffLine::Same(s) => writeln!(f, "      {s}")?,
+                                DiffLine::Left(s) => writeln!(f, "    - {s}")?,
+                                DiffLine::Right(s) => writeln!(f, "    + {s}")?,
+                            }
+                        }
+                    }
+                    BlockDiff::OnlyLeft(bb) => writeln!(f, "  block {bb} only in left")?,
+                    BlockDiff::OnlyRight(bb) => writeln!(f, "  block {bb} only in right")?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// SECTION: matching
+
+// a partial one-to-one mapping between left and right names.
+#[derive(Clone, Debug)]
+struct Bijection<T> {
+    fwd: Map<T, T>,
+    bwd: Map<T, T>,
+}
+
+impl<T> Default for Bijection<T> {
+    fn default() -> Self {
+        Bijection {
+            fwd: Map::new(),
+            bwd: Map::new(),
+        }
+    }
+}
+
+impl<T: Clone + Ord> Bijection<T> {
+    // maps l to r, unless that conflicts with the existing mapping.
+    fn bind(&mut self, l: &T, r: &T) -> bool {
+        match (self.fwd.get(l), self.bwd.get(r)) {
+            (None, None) => {
+                self.fwd.insert(l.clone(), r.clone());
+                self.bwd.insert(r.clone(), l.clone());
+                true
+            }
+            (Some(r2), Some(l2)) => r2 == r && l2 == l,
+            _ => false,
+        }
+    }
+}
+
+// an instruction or terminal of a basic block.
+#[derive(Clone, Copy)]
+enum Item<'a> {
+    Inst(&'a Instruction),
+    Term(&'a Terminal),
+}
+
+struct Matcher<'a> {
+    left_program: &'a Program,
+    left: &'a Function,
+    right: &'a Function,
+    vars: Bijection<VarId>,
+    blocks: Bijection<BbId>,
+    allocs: &'a mut Bijection<VarId>,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(
+        left_program: &'a Program,
+        left: &'a Function,
+        right: &'a Function,
+        allocs: &'a mut Bijection<VarId>,
+    ) -> Self {
+        Matcher {
+            left_program,
+            left,
+            right,
+            vars: Bijection::default(),
+            blocks: Bijection::default(),
+            allocs,
+        }
+    }
+
+    fn run(&mut self) -> FunctionDiff {
+        let mut diff = FunctionDiff::default();
+        let (left, right) = (self.left, self.right);
+
+        if left.ret_ty != right.ret_ty {
+            diff.signature.push("return types differ".to_string());
+        }
+        if left.params.len() != right.params.len()
+            || left
+                .params
+                .iter()
+                .zip(&right.params)
+                .any(|(l, r)| !self.vars.bind(l, r) || l.typ() != r.typ())
+        {
+            diff.signature.push(format!(
+                "parameters differ: ({}) vs ({})",
+                typed_list(left.params.iter()),
+                typed_list(right.params.iter())
+            ));
+        }
+
+        // walk both control-flow graphs in parallel.
+        let entry = bb_id("entry");
+        let mut worklist = vec![];
+        let mut visited = Set::new();
+        let mut changed = vec![];
+        if left.body.contains_key(&entry) && right.body.contains_key(&entry) {
+            self.blocks.bind(&entry, &entry);
+            worklist.push((entry.clone(), entry));
+        }
+        while let Some((l, r)) = worklist.pop() {
+            if !visited.insert(l.clone()) {
+                continue;
+            }
+            let (lbb, rbb) = (&left.body[&l], &right.body[&r]);
+            if let Some(lines) = self.match_block(lbb, rbb) {
+                changed.push((l, r, lines));
+            }
+
+            // keep following the graphs even if the terminals differ.
+            let (lsucc, rsucc) = (lbb.term.successors(), rbb.term.successors());
+            if lsucc.len() == rsucc.len() {
+                for (ls, rs) in lsucc.iter().zip(&rsucc).rev() {
+                    if self.blocks.bind(ls, rs)
+                        && left.body.contains_key(ls)
+                        && right.body.contains_key(rs)
+                    {
+                        worklist.push((ls.clone(), rs.clone()));
+                    }
+                }
+            }
+        }
+
+        // render the differing blocks once all the names that can be matched
+        // are known.
+        for (left, right, lines) in changed {
+            let lines = lines
+                .into_iter()
+                .map(|(side, item)| match side {
+                    Side::Both => DiffLine::Same(self.render_left(item)),
+                    Side::Left => DiffLine::Left(self.render_left(item)),
+                    Side::Right => DiffLine::Right(self.render_right(item)),
+                })
+                .collect();
+            diff.blocks.push(BlockDiff::Changed { left, right, lines });
+        }
+
+        for bb in left.body.keys() {
+            if !self.blocks.fwd.contains_key(bb) {
+                diff.blocks.push(BlockDiff::OnlyLeft(bb.clone()));
+            }
+        }
+        for bb in right.body.keys() {
+            if !self.blocks.bwd.contains_key(bb) {
+                diff.blocks.push(BlockDiff::OnlyRight(bb.clone()));
+            }
+        }
+
+        // locals that aren't used anywhere are only compared by type.
+        let unused = |locals: &Set<VarId>, used: &Map<VarId, VarId>| {
+            let mut typs = locals
+                .iter()
+                .filter(|v| !used.contains_key(v))
+                .map(|v| v.typ())
+                .collect::<Vec<_>>();
+            typs.sort();
+            typs
+        };
+        if unused(&left.locals, &self.vars.fwd) != unused(&right.locals, &self.vars.bwd) {
+            diff.signature.push(format!(
+                "unused locals differ: {{{}}} vs {{{}}}",
+                typed_list(
+                    left.locals
+                        .iter()
+                        .filter(|v| !self.vars.fwd.contains_key(v))
+                ),
+                typed_list(
+                    right
+                        .locals
+                        .iter()
+                        .filter(|v| !self.vars.bwd.contains_key(v))
+                )
+            ));
+        }
+
+        diff
+    }
+
+    // matches the blocks and returns None, or an alignment of their items if
+    // they don't match.
+    fn match_block(
+        &mut self,
+        left: &'a BasicBlock,
+        right: &'a BasicBlock,
+    ) -> Option<Vec<(Side, Item<'a>)>> {
+        let litems = items(left);
+        let ritems = items(right);
+
+        if litems.len() == ritems.len() {
+            let snapshot = self.snapshot();
+            if litems
+                .iter()
+                .zip(&ritems)
+                .all(|(l, r)| self.match_item(*l, *r))
+            {
+                return None;
+            }
+            // undo the partial match.
+            self.restore(snapshot);
+        }
+
+        // align the items with the same shape using a longest common
+        // subsequence, then match the aligned items.
+        let (n, m) = (litems.len(), ritems.len());
+        let mut lcs = vec![vec![0; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if self.shape(litems[i]) == self.shape(ritems[j]) {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let mut lines = vec![];
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n
+                && j < m
+                && self.shape(litems[i]) == self.shape(ritems[j])
+                && lcs[i][j] == lcs[i + 1][j + 1] + 1
+            {
+                let snapshot = self.snapshot();
+                if self.match_item(litems[i], ritems[j]) {
+                    lines.push((Side::Both, litems[i]));
+                } else {
+                    self.restore(snapshot);
+                    lines.push((Side::Left, litems[i]));
+                    lines.push((Side::Right, ritems[j]));
+                }
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lcs[i + 1][j] >= lcs[i][j + 1]) {
+                lines.push((Side::Left, litems[i]));
+                i += 1;
+            } else {
+                lines.push((Side::Right, ritems[j]));
+                j += 1;
+            }
+        }
+        Some(lines)
+    }
+
+    fn snapshot(&self) -> (Bijection<VarId>, Bijection<VarId>, Bijection<BbId>) {
+        (self.vars.clone(), self.allocs.clone(), self.blocks.clone())
+    }
+
+    fn restore(
+        &mut self,
+        (vars, allocs, blocks): (Bijection<VarId>, Bijection<VarId>, Bijection<BbId>),
+    ) {
+        self.vars = vars;
+        *self.allocs = allocs;
+        self.blocks = blocks;
+    }
+
+    // matches two items, extending the mappings as necessary.
+    fn match_item(&mut self, left: Item, right: Item) -> bool {
+        if self.shape(left) != self.shape(right) {
+            return false;
+        }
+        let vars_match = vars_of(left).iter().zip(&vars_of(right)).all(|(l, r)| {
+            l.typ() == r.typ()
+                && match self.kind(l) {
+                    VarKind::Global => l == r,
+                    VarKind::Local => {
+                        r.scope().as_ref() == Some(&self.right.id) && self.vars.bind(l, r)
+                    }
+                    VarKind::Alloc => r.scope().is_none() && self.allocs.bind(l, r),
+                }
+        });
+        let blocks_match = match (left, right) {
+            (Item::Term(l), Item::Term(r)) => l
+                .successors()
+                .iter()
+                .zip(&r.successors())
+                .all(|(l, r)| self.blocks.bind(l, r)),
+            _ => true,
+        };
+        vars_match && blocks_match
+    }
+
+    fn kind(&self, v: &VarId) -> VarKind {
+        if v.scope().is_some() {
+            VarKind::Local
+        } else if self.left_program.globals.contains(v) {
+            VarKind::Global
+        } else {
+            VarKind::Alloc
+        }
+    }
+
+    // the item with every renamable name replaced by a placeholder of the
+    // same type.
+    fn shape(&self, item: Item) -> String {
+        let mut var = |v: &VarId| {
+            if v.scope().is_some() || !self.left_program.globals.contains(v) {
+                var_id("_", v.typ(), None)
+            } else {
+                v.clone()
+            }
+        };
+        match item {
+            Item::Inst(inst) => inst.map_vars(&mut var).to_string(),
+            Item::Term(term) => term
+                .map_vars_and_blocks(&mut var, &mut |_| bb_id("_"))
+                .to_string(),
+        }
+    }
+
+    fn render_left(&self, item: Item) -> String {
+        match item {
+            Item::Inst(inst) => inst.to_string(),
+            Item::Term(term) => term.to_string(),
+        }
+    }
+
+    // renders a right-hand item using the left-hand names where known.
+    fn render_right(&self, item: Item) -> String {
+        let mut var = |v: &VarId| {
+            self.vars
+                .bwd
+                .get(v)
+                .or_else(|| self.allocs.bwd.get(v))
+                .unwrap_or(v)
+                .clone()
+        };
+        let mut bb = |l: &BbId| self.blocks.bwd.get(l).unwrap_or(l).clone();
+        match item {
+            Item::Inst(inst) => inst.map_vars(&mut var).to_string(),
+            Item::Term(term) => term.map_vars_and_blocks(&mut var, &mut bb).to_string(),
+        }
+    }
+}
+
+// which of the blocks being compared an item is from.
+enum Side {
+    Both,
+    Left,
+    Right,
+}
+
+enum VarKind {
+    Global,
+    Local,
+    Alloc,
+}
+
+// SECTION: helpers
+
+fn items(bb: &BasicBlock) -> Vec<Item<'_>> {
+    bb.insts
+        .iter()
+        .map(Item::Inst)
+        .chain(std::iter::once(Item::Term(&bb.term)))
+        .collect()
+}
+
+// the variables of an item in the order they appear.
+fn vars_of(item: Item) -> Vec<VarId> {
+    let mut vars = vec![];
+    let mut var = |v: &VarId| {
+        vars.push(v.clone());
+        v.clone()
+    };
+    match item {
+        Item::Inst(inst) => {
+            inst.map_vars(&mut var);
+        }
+        Item::Term(term) => {
+            term.map_vars_and_blocks(&mut var, &mut |l| l.clone());
+        }
+    }
+    vars
+}
+
+fn typed_list<'a>(vars: impl Iterator<Item = &'a VarId>) -> String {
+    vars.map(|v| v.typed_to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
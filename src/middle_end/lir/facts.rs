@@ -0,0 +1,482 @@
+// exports a Program as Datalog facts for external analysis tools (e.g.,
+// Soufflé), and imports relations derived by those tools.
+//
+// every relation is written to `<dir>/<relation>.facts` as tab-separated
+// tuples, one per line. variables (including alloc ids), basic blocks and
+// instructions are identified by numbers that only depend on the program, so
+// exporting the same program twice gives the same ids. use `declarations()`
+// to get the Soufflé declarations of the exported relations.
+
+use std::io;
+use std::path::Path;
+
+use super::*;
+
+// SECTION: schema
+
+// the kinds of values stored in a fact column.
+#[derive(Clone, Copy, Debug, Display, Eq, PartialEq)]
+pub enum FactKind {
+    // the id of a variable or alloc id.
+    #[display(fmt = "number")]
+    Var,
+    // the id of a basic block.
+    #[display(fmt = "number")]
+    Block,
+    // the id of an instruction or terminal.
+    #[display(fmt = "number")]
+    Inst,
+    // the name of a function (internal or extern).
+    #[display(fmt = "symbol")]
+    Func,
+    // any other string, e.g., names and types.
+    #[display(fmt = "symbol")]
+    Symbol,
+    // any other number, e.g., argument positions.
+    #[display(fmt = "number")]
+    Number,
+}
+
+use FactKind as K;
+
+// the exported relations and their columns.
+pub const SCHEMA: &[(&str, &[(&str, FactKind)])] = &[
+    // variables, their names and types; alloc ids are variables too.
+    ("var", &[("var", K::Var), ("name", K::Symbol)]),
+    ("var_type", &[("var", K::Var), ("type", K::Symbol)]),
+    ("global", &[("var", K::Var)]),
+    ("local", &[("var", K::Var), ("func", K::Func)]),
+    (
+        "formal",
+        &[("func", K::Func), ("index", K::Number), ("var", K::Var)],
+    ),
+    ("return_var", &[("func", K::Func), ("var", K::Var)]),
+    // the control-flow graph.
+    ("function", &[("func", K::Func)]),
+    (
+        "block",
+        &[("block", K::Block), ("func", K::Func), ("label", K::Symbol)],
+    ),
+    ("entry", &[("func", K::Func), ("block", K::Block)]),
+    ("succ", &[("from", K::Block), ("to", K::Block)]),
+    (
+        "inst_block",
+        &[("inst", K::Inst), ("block", K::Block), ("index", K::Number)],
+    ),
+    // instructions relevant to pointer analyses.
+    (
+        "addrof",
+        &[("inst", K::Inst), ("lhs", K::Var), ("rhs", K::Var)],
+    ),
+    (
+        "alloc",
+        &[("inst", K::Inst), ("lhs", K::Var), ("site", K::Var)],
+    ),
+    (
+        "copy",
+        &[("inst", K::Inst), ("lhs", K::Var), ("rhs", K::Var)],
+    ),
+    (
+        "load",
+        &[("inst", K::Inst), ("lhs", K::Var), ("src", K::Var)],
+    ),
+    (
+        "store",
+        &[("inst", K::Inst), ("dst", K::Var), ("src", K::Var)],
+    ),
+    (
+        "gep",
+        &[("inst", K::Inst), ("lhs", K::Var), ("src", K::Var)],
+    ),
+    (
+        "gfp",
+        &[
+            ("inst", K::Inst),
+            ("lhs", K::Var),
+            ("src", K::Var),
+            ("field", K::Symbol),
+        ],
+    ),
+    // calls.
+    ("call_direct", &[("inst", K::Inst), ("callee", K::Func)]),
+    ("call_indirect", &[("inst", K::Inst), ("callee", K::Var)]),
+    ("call_ext", &[("inst", K::Inst), ("callee", K::Func)]),
+    (
+        "actual_arg",
+        &[("inst", K::Inst), ("index", K::Number), ("var", K::Var)],
+    ),
+    ("call_lhs", &[("inst", K::Inst), ("var", K::Var)]),
+];
+
+// Soufflé declarations for all exported relations, marked as inputs.
+pub fn declarations() -> String {
+    SCHEMA
+        .iter()
+        .map(|(name, columns)| {
+            let columns = columns
+                .iter()
+                .map(|(col, kind)| format!("{col}: {kind}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!(".decl {name}({columns})\n.input {name}\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// SECTION: ids
+
+// numbers the variables, basic blocks and instructions of a program. ids are
+// assigned in program order (which is deterministic since programs use ordered
+// maps).
+#[derive(Clone, Debug, Default)]
+pub struct FactIds {
+    vars: Vec<VarId>,
+    var_ids: Map<VarId, usize>,
+    blocks: Vec<(FuncId, BbId)>,
+    block_ids: Map<(FuncId, BbId), usize>,
+    insts: Vec<InstLoc>,
+    inst_ids: Map<InstLoc, usize>,
+}
+
+impl FactIds {
+    pub fn new(program: &Program) -> Self {
+        let mut ids = FactIds::default();
+
+        for global in &program.globals {
+            ids.add_var(global);
+        }
+
+        for func in program.functions.values() {
+            for var in func.params.iter().chain(&func.locals) {
+                ids.add_var(var);
+            }
+
+            for (label, bb) in &func.body {
+                let block = (func.id.clone(), label.clone());
+                ids.block_ids.insert(block.clone(), ids.blocks.len());
+                ids.blocks.push(block);
+
+                for (idx, inst) in bb.insts.iter().enumerate() {
+                    if let Instruction::Alloc { id, .. } = inst {
+                        ids.add_var(id);
+                    }
+                    ids.add_inst(InstLoc::new(&func.id, label, idx));
+                }
+                ids.add_inst(InstLoc::new(&func.id, label, bb.insts.len()));
+            }
+        }
+
+        ids
+    }
+
+    fn add_var(&mut self, var: &VarId) {
+        if !self.var_ids.contains_key(var) {
+            self.var_ids.insert(var.clone(), self.vars.len());
+            self.vars.push(var.clone());
+        }
+    }
+
+    fn add_inst(&mut self, loc: InstLoc) {
+        self.inst_ids.insert(loc.clone(), self.insts.len());
+        self.insts.push(loc);
+    }
+
+    // panics if the variable is not part of the program.
+    pub fn var(&self, var: &VarId) -> usize {
+        self.var_ids[var]
+    }
+
+    // panics if the block is not part of the program.
+    pub fn block(&self, func: &FuncId, bb: &BbId) -> usize {
+        self.block_ids[&(func.clone(), bb.clone())]
+    }
+
+    // panics if the instruction is not part of the program.
+    pub fn inst(&self, loc: &InstLoc) -> usize {
+        self.inst_ids[loc]
+    }
+
+    pub fn var_of(&self, id: usize) -> Option<&VarId> {
+        self.vars.get(id)
+    }
+
+    pub fn block_of(&self, id: usize) -> Option<&(FuncId, BbId)> {
+        self.blocks.get(id)
+    }
+
+    pub fn inst_of(&self, id: usize) -> Option<&InstLoc> {
+        self.insts.get(id)
+    }
+}
+
+// SECTION: export
+
+// the facts describing a program, by relation name.
+#[derive(Clone, Debug)]
+pub struct Facts {
+    pub ids: FactIds,
+    pub relations: Map<&'static str, Vec<Vec<String>>>,
+}
+
+impl Facts {
+    // the tuples of the given relation; panics if there is no such relation.
+    pub fn relation(&self, name: &str) -> &[Vec<String>] {
+        &self.relations[name]
+    }
+
+    // writes each relation to `<dir>/<relation>.facts`, creating `dir` if
+    // necessary.
+    pub fn write_to_dir(&self, dir: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        for (name, tuples) in &self.relations {
+            let contents = tuples
+                .iter()
+                .map(|tuple| tuple.join("\t") + "\n")
+                .collect::<String>();
+            std::fs::write(dir.join(format!("{name}.facts")), contents)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn export_facts(program: &Program) -> Facts {
+    use Instruction as I;
+    use Terminal as T;
+
+    let ids = FactIds::new(program);
+    let mut relations: Map<&'static str, Vec<Vec<String>>> =
+        SCHEMA.iter().map(|(name, _)| (*name, vec![])).collect();
+
+    // add a tuple to a relation, checking its arity against the schema.
+    let mut add = |name: &'static str, tuple: Vec<String>| {
+        debug_assert!(SCHEMA
+            .iter()
+            .any(|(rel, columns)| *rel == name && columns.len() == tuple.len()));
+        relations.get_mut(name).unwrap().push(tuple);
+    };
+    let var = |v: &VarId| ids.var(v).to_string();
+
+    for (id, v) in ids.vars.iter().enumerate() {
+        add("var", vec![id.to_string(), v.name().to_string()]);
+        add("var_type", vec![id.to_string(), v.typ().to_string()]);
+    }
+    for global in &program.globals {
+        add("global", vec![var(global)]);
+    }
+
+    for (name, func) in &program.functions {
+        let name = name.to_string();
+        add("function", vec![name.clone()]);
+
+        for (idx, param) in func.params.iter().enumerate() {
+            add("local", vec![var(param), name.clone()]);
+            add("formal", vec![name.clone(), idx.to_string(), var(param)]);
+        }
+        for local in &func.locals {
+            add("local", vec![var(local), name.clone()]);
+        }
+
+        for (label, bb) in &func.body {
+            let block = ids.block(&func.id, label).to_string();
+            add(
+                "block",
+                vec![block.clone(), name.clone(), label.to_string()],
+            );
+            if label.name() == "entry" {
+                add("entry", vec![name.clone(), block.clone()]);
+            }
+            for succ in bb.term.successors() {
+                add(
+                    "succ",
+                    vec![block.clone(), ids.block(&func.id, &succ).to_string()],
+                );
+            }
+
+            let inst_id = |idx: usize| ids.inst(&InstLoc::new(&func.id, label, idx)).to_string();
+
+            for (idx, inst) in bb.insts.iter().enumerate() {
+                let inst_id = inst_id(idx);
+                add(
+                    "inst_block",
+                    vec![inst_id.clone(), block.clone(), idx.to_string()],
+                );
+
+                match inst {
+                    I::AddrOf { lhs, rhs } => add("addrof", vec![inst_id, var(lhs), var(rhs)]),
+                    I::Alloc { lhs, id, .. } => add("alloc", vec![inst_id, var(lhs), var(id)]),
+                    I::Copy {
+                        lhs,
+                        op: Operand::Var(rhs),
+                    } => add("copy", vec![inst_id, var(lhs), var(rhs)]),
+                    I::Load { lhs, src } => add("load", vec![inst_id, var(lhs), var(src)]),
+                    I::Store {
+                        dst,
+                        op: Operand::Var(src),
+                    } => add("store", vec![inst_id, var(dst), var(src)]),
+                    I::Gep { lhs, src, .. } => add("gep", vec![inst_id, var(lhs), var(src)]),
+                    I::Gfp { lhs, src, field } => add(
+                        "gfp",
+                        vec![inst_id, var(lhs), var(src), field.name.to_string()],
+                    ),
+                    I::CallExt {
+                        lhs,
+                        ext_callee,
+                        args,
+                    } => {
+                        add("call_ext", vec![inst_id.clone(), ext_callee.to_string()]);
+                        for (rel, tuple) in call_tuples(&ids, &inst_id, lhs, args) {
+                            add(rel, tuple);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            let term_id = inst_id(bb.insts.len());
+            add(
+                "inst_block",
+                vec![term_id.clone(), block.clone(), bb.insts.len().to_string()],
+            );
+
+            let call = match &bb.term {
+                T::CallDirect {
+                    lhs, callee, args, ..
+                } => {
+                    add("call_direct", vec![term_id.clone(), callee.to_string()]);
+                    Some((lhs, args))
+                }
+                T::CallIndirect {
+                    lhs, callee, args, ..
+                } => {
+                    add("call_indirect", vec![term_id.clone(), var(callee)]);
+                    Some((lhs, args))
+                }
+                T::Ret(Some(Operand::Var(v))) => {
+                    add("return_var", vec![name.clone(), var(v)]);
+                    None
+                }
+                _ => None,
+            };
+            if let Some((lhs, args)) = call {
+                for (rel, tuple) in call_tuples(&ids, &term_id, lhs, args) {
+                    add(rel, tuple);
+                }
+            }
+        }
+    }
+
+    Facts { ids, relations }
+}
+
+// the actual_arg and call_lhs tuples of a call.
+fn call_tuples(
+    ids: &FactIds,
+    inst_id: &str,
+    lhs: &Option<VarId>,
+    args: &[Operand],
+) -> Vec<(&'static str, Vec<String>)> {
+    let mut tuples = vec![];
+    for (i, arg) in args.iter().enumerate() {
+        if let Operand::Var(arg) = arg {
+            tuples.push((
+                "actual_arg",
+                vec![inst_id.to_string(), i.to_string(), ids.var(arg).to_string()],
+            ));
+        }
+    }
+    if let Some(lhs) = lhs {
+        tuples.push((
+            "call_lhs",
+            vec![inst_id.to_string(), ids.var(lhs).to_string()],
+        ));
+    }
+    tuples
+}
+
+// SECTION: import
+
+// a value in an imported tuple, resolved against the program.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum FactValue {
+    Var(VarId),
+    Block(FuncId, BbId),
+    Inst(InstLoc),
+    Func(FuncId),
+    Symbol(String),
+    Number(i64),
+}
+
+// an error while importing a relation, with explanatory message.
+#[derive(Clone, Debug, Display, Eq, PartialEq)]
+pub struct FactsError(pub String);
+impl std::error::Error for FactsError {}
+
+// reads a relation derived by an external tool from a tab-separated file (such
+// as the `.csv` outputs of Soufflé), resolving the columns of each tuple
+// against the program according to `columns`. `ids` must come from the
+// program the facts were exported from.
+pub fn import_relation(
+    program: &Program,
+    ids: &FactIds,
+    path: &Path,
+    columns: &[FactKind],
+) -> Result<Set<Vec<FactValue>>, FactsError> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| FactsError(format!("could not read {}: {e}", path.display())))?;
+    parse_relation(program, ids, &contents, columns)
+}
+
+// like import_relation(), but reads the tuples from a string.
+pub fn parse_relation(
+    program: &Program,
+    ids: &FactIds,
+    contents: &str,
+    columns: &[FactKind],
+) -> Result<Set<Vec<FactValue>>, FactsError> {
+    let mut tuples = Set::new();
+
+    for (row, line) in contents.lines().enumerate() {
+        if line.is_empty() {
+            continue;
+        }
+
+        let fields = line.split('\t').collect::<Vec<_>>();
+        if fields.len() != columns.len() {
+            return Err(FactsError(format!(
+                "line {}: expected {} columns, got {}",
+                row + 1,
+                columns.len(),
+                fields.len()
+            )));
+        }
+
+        let tuple = fields
+            .iter()
+            .zip(columns)
+            .map(|(field, kind)| resolve(program, ids, field, *kind))
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| FactsError(format!("line {}: cannot resolve \"{line}\"", row + 1)))?;
+        tuples.insert(tuple);
+    }
+
+    Ok(tuples)
+}
+
+fn resolve(program: &Program, ids: &FactIds, field: &str, kind: FactKind) -> Option<FactValue> {
+    let id = || field.parse::<usize>().ok();
+    match kind {
+        K::Var => ids.var_of(id()?).cloned().map(FactValue::Var),
+        K::Block => ids
+            .block_of(id()?)
+            .map(|(func, bb)| FactValue::Block(func.clone(), bb.clone())),
+        K::Inst => ids.inst_of(id()?).cloned().map(FactValue::Inst),
+        K::Func => {
+            let func = func_id(field);
+            (program.functions.contains_key(&func) || program.externs.contains_key(&func))
+                .then_some(FactValue::Func(func))
+        }
+        K::Symbol => Some(FactValue::Symbol(field.to_string())),
+        K::Number => field.parse().ok().map(FactValue::Number),
+    }
+}
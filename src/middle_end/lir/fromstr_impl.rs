@@ -35,13 +35,14 @@ decl = { "let" ~ local ~ ("," ~ local)* }
 local = { ident ~ ":" ~ type_id }
 basic_block = { ident ~ ":" ~ NEWLINE* ~ inst* ~ terminal }
 
-inst = _{ (addrof | alloc | arith | callext | cmp | copy | gep | gfp | load | phi | store) ~ NEWLINE }
+inst = _{ (addrof | alloc | arith | callext | cmp | copy | free | gep | gfp | load | phi | store) ~ NEWLINE }
 addrof = { ident ~ "=" ~ "$addrof" ~ ident }
 alloc = { ident ~ "=" ~ "$alloc" ~ operand ~ "[" ~ ident ~ "]" }
 arith = { ident ~ "=" ~ "$arith" ~ aop ~ operand ~ operand }
 callext = { (ident ~ "=")? ~ "$call_ext" ~ ident ~ "(" ~ (operand ~ ("," ~ operand)*)? ~ ")" }
 cmp = { ident ~ "=" ~ "$cmp" ~ rop ~ operand ~ operand }
 copy = { ident ~ "=" ~ "$copy" ~ operand }
+free = { "$free" ~ ident }
 gep = { ident ~ "=" ~ "$gep" ~ ident ~ operand }
 gfp = { ident ~ "=" ~ "$gfp" ~ ident ~ ident }
 load = { ident ~ "=" ~ "$load" ~ ident }
@@ -555,6 +556,11 @@ fn create_basic_block(
                 let op = create_operand(inner.next().unwrap())?;
                 insts.push(Copy { lhs, op });
             }
+            Rule::free => {
+                let mut inner = inst.into_inner();
+                let src = lookup(inner.next().unwrap().as_str())?;
+                insts.push(Free { src });
+            }
             Rule::gep => {
                 let mut inner = inst.into_inner();
                 let lhs = lookup(inner.next().unwrap().as_str())?;
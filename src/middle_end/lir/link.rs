@@ -0,0 +1,221 @@
+// links several LIR modules into a single program:
+//
+// - structs with the same name must have the same fields, and are unified.
+// - globals with the same name must have the same type, and are unified (e.g.,
+//   the function pointers of functions used by several modules).
+// - every function is defined by at most one module, and exactly one module
+//   defines main.
+// - an extern defined as a function by some module is resolved to that
+//   function, whose type must match the extern's. calls to it are rewritten
+//   from $call_ext to $call_dir, splitting the calling block. the remaining
+//   externs must have the same type in every module that declares them.
+// - alloc ids that are already used by an earlier module are renamed.
+//
+// modules don't need to be valid on their own, but the linked program is
+// validated.
+
+use super::*;
+use crate::commons::ValidationError;
+
+// SECTION: linking
+
+pub fn link(modules: &[Program]) -> Result<Program, ValidationError> {
+    let mut errors = ValidationError::new();
+    let mut structs: Map<StructId, Set<FieldId>> = Map::new();
+    let mut globals: Map<String, VarId> = Map::new();
+    let mut functions: Map<FuncId, Function> = Map::new();
+    let mut externs: Map<FuncId, Type> = Map::new();
+    let mut alloc_ids: Set<String> = Set::new();
+
+    for module in modules {
+        for (id, fields) in &module.structs {
+            match structs.get(id) {
+                Some(prev) if prev != fields => {
+                    errors.add_error(format!("struct {id} has conflicting definitions"))
+                }
+                Some(_) => {}
+                None => {
+                    structs.insert(id.clone(), fields.clone());
+                }
+            }
+        }
+
+        for global in &module.globals {
+            match globals.get(global.name()) {
+                Some(prev) if prev.typ() != global.typ() => errors.add_error(format!(
+                    "global {global} has conflicting types {} and {}",
+                    prev.typ(),
+                    global.typ()
+                )),
+                Some(_) => {}
+                None => {
+                    globals.insert(global.name().to_string(), global.clone());
+                }
+            }
+        }
+
+        for (id, typ) in &module.externs {
+            match externs.get(id) {
+                Some(prev) if prev != typ => errors.add_error(format!(
+                    "extern {id} is declared with conflicting types {prev} and {typ}"
+                )),
+                Some(_) => {}
+                None => {
+                    externs.insert(id.clone(), typ.clone());
+                }
+            }
+        }
+
+        let renamed = rename_alloc_ids(module, &mut alloc_ids);
+        for (id, func) in &module.functions {
+            if functions.contains_key(id) {
+                errors.add_error(format!("function {id} is defined by more than one module"));
+            } else {
+                let mut func = func.clone();
+                for bb in func.body.values_mut() {
+                    for inst in &mut bb.insts {
+                        if let Instruction::Alloc { id, .. } = inst {
+                            if let Some(new_id) = renamed.get(id) {
+                                *id = new_id.clone();
+                            }
+                        }
+                    }
+                }
+                functions.insert(id.clone(), func);
+            }
+        }
+    }
+
+    if !functions.contains_key(&func_id("main")) {
+        errors.add_error("no module defines main".to_string());
+    }
+
+    // resolve externs against the defined functions.
+    let mut resolved = Set::new();
+    for (id, typ) in &externs {
+        if let Some(func) = functions.get(id) {
+            let func_typ = func_ty(
+                func.ret_ty.clone(),
+                func.params.iter().map(|p| p.typ()).collect(),
+            );
+            if id.name() == "main" {
+                errors.add_error("extern main refers to main, which can't be called".to_string());
+            } else if *typ != func_typ {
+                errors.add_error(format!(
+                    "extern {id} has type {typ} but is defined with type {func_typ}"
+                ));
+            } else {
+                resolved.insert(id.clone());
+            }
+        }
+    }
+    externs.retain(|id, _| !resolved.contains(id));
+    for func in functions.values_mut() {
+        resolve_calls(func, &resolved);
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let program = Program {
+        structs,
+        globals: globals.into_values().collect(),
+        functions,
+        externs,
+    };
+    validate(&program)?;
+    Ok(program)
+}
+
+// SECTION: helpers
+
+// returns new names for the alloc ids of module that are already in used, and
+// adds the (new) names of all the module's alloc ids to used.
+fn rename_alloc_ids(module: &Program, used: &mut Set<String>) -> Map<VarId, VarId> {
+    let ids = module
+        .functions
+        .values()
+        .flat_map(|func| func.body.values())
+        .flat_map(|bb| &bb.insts)
+        .filter_map(|inst| match inst {
+            Instruction::Alloc { id, .. } => Some(id.clone()),
+            _ => None,
+        })
+        .collect::<Set<_>>();
+    let names = ids
+        .iter()
+        .map(|id| id.name().to_string())
+        .collect::<Set<_>>();
+
+    let mut renamed = Map::new();
+    for id in ids {
+        if used.contains(id.name()) {
+            let new_name = (1..)
+                .map(|n| format!("{}_{n}", id.name()))
+                .find(|name| !used.contains(name) && !names.contains(name))
+                .unwrap();
+            used.insert(new_name.clone());
+            renamed.insert(id.clone(), var_id(&new_name, id.typ(), None));
+        } else {
+            used.insert(id.name().to_string());
+        }
+    }
+    renamed
+}
+
+// rewrites the calls to resolved externs into direct calls. since calls are
+// terminals, the rest of the calling block is moved to a fresh block.
+fn resolve_calls(func: &mut Function, resolved: &Set<FuncId>) {
+    let mut labels = func.body.keys().cloned().collect::<Set<_>>();
+    let mut bb_ctr = 0;
+    let mut fresh_label = || loop {
+        bb_ctr += 1;
+        let label = bb_id(&format!("bb{bb_ctr}"));
+        if labels.insert(label.clone()) {
+            return label;
+        }
+    };
+
+    let mut body = Map::new();
+    for (label, bb) in std::mem::take(&mut func.body) {
+        let mut curr_bb = label;
+        let mut insts = vec![];
+        for inst in bb.insts {
+            match inst {
+                Instruction::CallExt {
+                    lhs,
+                    ext_callee,
+                    args,
+                } if resolved.contains(&ext_callee) => {
+                    let next_bb = fresh_label();
+                    let term = Terminal::CallDirect {
+                        lhs,
+                        callee: ext_callee,
+                        args,
+                        next_bb: next_bb.clone(),
+                    };
+                    body.insert(
+                        curr_bb.clone(),
+                        BasicBlock {
+                            id: curr_bb,
+                            insts: std::mem::take(&mut insts),
+                            term,
+                        },
+                    );
+                    curr_bb = next_bb;
+                }
+                inst => insts.push(inst),
+            }
+        }
+        body.insert(
+            curr_bb.clone(),
+            BasicBlock {
+                id: curr_bb,
+                insts,
+                term: bb.term,
+            },
+        );
+    }
+    func.body = body;
+}
@@ -245,3 +245,25 @@ impl std::ops::Index<BbId> for Function {
         self.body.get(&index).unwrap()
     }
 }
+
+// SECTION: Operand
+
+// allows constants and variables to be passed wherever an operand is expected.
+
+impl From<i32> for Operand {
+    fn from(n: i32) -> Self {
+        Operand::CInt(n)
+    }
+}
+
+impl From<VarId> for Operand {
+    fn from(v: VarId) -> Self {
+        Operand::Var(v)
+    }
+}
+
+impl From<&VarId> for Operand {
+    fn from(v: &VarId) -> Self {
+        Operand::Var(v.clone())
+    }
+}
@@ -355,6 +355,7 @@ fn check_declared(program: &Program) -> ValidationError {
                             check_var(v.clone());
                         }
                     }
+                    I::Free { src } => check_var(src.clone()),
                     I::Gep { lhs, src, idx } => {
                         check_var(lhs.clone());
                         check_var(src.clone());
@@ -515,6 +516,11 @@ fn check_types(program: &Program) -> ValidationError {
                         (LT::Pointer(_), LT::Int) if matches!(op, O::CInt(0)) => {}
                         _ => report_err(),
                     },
+                    // src must be a pointer, and not a function pointer.
+                    I::Free { src } => match src.typ().get_deref_type() {
+                        Some(typ) if !typ.is_function() => {}
+                        _ => report_err(),
+                    },
                     // idx must be an integer. src and lhs must be pointers to the same type.
                     I::Gep { lhs, src, idx } => match (&*lhs.typ().0, &*src.typ().0, &*idx.typ().0)
                     {
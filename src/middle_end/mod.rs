@@ -1 +1,8 @@
+pub mod analysis;
+pub mod dot;
 pub mod lir;
+pub mod passes;
+pub mod translation_validation;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,122 @@
+// transformations of LIR programs. every pass takes a valid program and
+// returns an equivalent valid program.
+
+// use ordered sets and maps to allow for deterministic outputs.
+#[allow(unused_imports)]
+use std::collections::{BTreeMap as Map, BTreeSet as Set};
+
+use std::fmt;
+
+use crate::middle_end::analysis::*;
+use crate::middle_end::lir::*;
+use crate::middle_end::translation_validation::{check_program, Bounds, Verdict};
+
+mod canonicalize;
+
+pub use self::canonicalize::*;
+
+pub type Pass = fn(Program) -> Program;
+
+// the passes that can be selected by name (e.g., from the command line).
+pub const PASSES: &[(&str, Pass)] = &[("canonicalize", canonicalize)];
+
+pub fn pass_by_name(name: &str) -> Option<Pass> {
+    PASSES
+        .iter()
+        .find(|(pass, _)| *pass == name)
+        .map(|(_, pass)| *pass)
+}
+
+// runs a sequence of passes. when verification is on, the output of each pass
+// is validated and each of its functions is checked to refine the
+// corresponding function of the pass's input.
+#[derive(Default)]
+pub struct PassManager {
+    passes: Vec<(String, Pass)>,
+    verify: Option<Bounds>,
+}
+
+// a pass that produced an invalid program (func is None) or miscompiled a
+// function.
+#[derive(Clone, Debug)]
+pub struct PassError {
+    pub pass: String,
+    pub func: Option<FuncId>,
+    pub message: String,
+}
+
+impl fmt::Display for PassError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pass {} ", self.pass)?;
+        match &self.func {
+            Some(func) => write!(f, "miscompiles {func}")?,
+            None => write!(f, "produced an invalid program")?,
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for PassError {}
+
+impl PassManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, name: &str, pass: Pass) {
+        self.passes.push((name.to_string(), pass));
+    }
+
+    pub fn add_by_name(&mut self, name: &str) -> Result<(), String> {
+        let pass = pass_by_name(name).ok_or_else(|| {
+            let names = PASSES.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+            format!("Unknown pass {name}; the passes are: {}", names.join(", "))
+        })?;
+        self.add(name, pass);
+        Ok(())
+    }
+
+    pub fn verify(&mut self, bounds: Bounds) {
+        self.verify = Some(bounds);
+    }
+
+    // run the passes in order. returns the final program along with warnings
+    // about the functions whose refinement could neither be established nor
+    // refuted.
+    pub fn run(&self, program: Program) -> Result<(Program, Vec<String>), PassError> {
+        let mut program = program;
+        let mut warnings = vec![];
+        for (name, pass) in &self.passes {
+            let Some(bounds) = &self.verify else {
+                program = pass(program);
+                continue;
+            };
+
+            let transformed = pass(program.clone());
+            if let Err(err) = validate(&transformed) {
+                return Err(PassError {
+                    pass: name.clone(),
+                    func: None,
+                    message: err.errors.into_iter().collect::<Vec<_>>().join("; "),
+                });
+            }
+            for (func, verdict) in check_program(&program, &transformed, bounds) {
+                match verdict {
+                    Verdict::Refines { .. } => {}
+                    Verdict::Mismatch(cex) => {
+                        return Err(PassError {
+                            pass: name.clone(),
+                            func: Some(func),
+                            message: cex.to_string(),
+                        })
+                    }
+                    Verdict::Unknown(why) => {
+                        warnings.push(format!("pass {name}: could not verify {func}: {why}"))
+                    }
+                }
+            }
+            program = transformed;
+        }
+        Ok((program, warnings))
+    }
+}
@@ -0,0 +1,133 @@
+// canonical renaming of basic blocks and temporaries, so that the text of a
+// program depends on its structure rather than on the order in which the
+// lowering happened to create blocks and temporaries:
+//
+// - entry keeps its name; the other blocks are renamed bb1, bb2, ... in
+//   reverse postorder, followed by any unreachable blocks.
+// - temporaries (locals whose names start with '_', which cflat identifiers
+//   can't) keep their prefix (e.g., _t or _ret) but are renumbered in the order
+//   they are first assigned to, visiting the blocks in their new order.
+//   temporaries that are never assigned come next in the order they are first
+//   used, followed by unused ones.
+//
+// since Display shows blocks in label order, the displayed program lists the
+// blocks in reverse postorder.
+
+use super::*;
+
+pub fn canonicalize(program: Program) -> Program {
+    let functions = program
+        .functions
+        .into_values()
+        .map(|func| (func.id.clone(), canonicalize_function(func)))
+        .collect();
+    Program {
+        functions,
+        ..program
+    }
+}
+
+fn canonicalize_function(func: Function) -> Function {
+    // the new block order.
+    let mut order = reverse_postorder(&func);
+    let reachable = order.iter().cloned().collect::<Set<_>>();
+    let mut unreachable = func
+        .body
+        .keys()
+        .filter(|bb| !reachable.contains(bb))
+        .cloned()
+        .collect::<Vec<_>>();
+    unreachable.sort_by_key(|bb| (bb.name().len(), bb.clone()));
+    order.extend(unreachable);
+
+    let blocks = order
+        .iter()
+        .filter(|bb| bb.name() != "entry")
+        .enumerate()
+        .map(|(i, bb)| (bb.clone(), bb_id(&format!("bb{}", i + 1))))
+        .chain(std::iter::once((bb_id("entry"), bb_id("entry"))))
+        .collect::<Map<_, _>>();
+
+    // the order in which temporaries are first assigned, then first used.
+    let is_temp = |v: &VarId| v.name().starts_with('_') && func.locals.contains(v);
+    let mut temps = vec![];
+    let mut seen = Set::new();
+    for bb in order.iter().map(|bb| &func.body[bb]) {
+        let defs = bb.insts.iter().filter_map(|inst| inst.lhs());
+        for v in defs.chain(bb.term.lhs()) {
+            if is_temp(v) && seen.insert(v.clone()) {
+                temps.push(v.clone());
+            }
+        }
+    }
+    for bb in order.iter().map(|bb| &func.body[bb]) {
+        let mut visit = |v: &VarId| {
+            if is_temp(v) && seen.insert(v.clone()) {
+                temps.push(v.clone());
+            }
+            v.clone()
+        };
+        for inst in &bb.insts {
+            inst.map_vars(&mut visit);
+        }
+        bb.term.map_vars_and_blocks(&mut visit, &mut |l| l.clone());
+    }
+    temps.extend(
+        func.locals
+            .iter()
+            .filter(|v| is_temp(v) && !seen.contains(*v))
+            .cloned(),
+    );
+
+    // number the temporaries, skipping names taken by other variables.
+    let taken = func
+        .params
+        .iter()
+        .chain(&func.locals)
+        .filter(|v| !is_temp(v))
+        .map(|v| v.name().to_string())
+        .collect::<Set<_>>();
+    let mut ctr = 0;
+    let vars = temps
+        .into_iter()
+        .map(|v| {
+            let prefix = v.name().trim_end_matches(|c: char| c.is_ascii_digit());
+            let name = loop {
+                ctr += 1;
+                let name = format!("{prefix}{ctr}");
+                if !taken.contains(&name) {
+                    break name;
+                }
+            };
+            let renamed = var_id(&name, v.typ(), v.scope());
+            (v, renamed)
+        })
+        .collect::<Map<_, _>>();
+
+    let mut var = |v: &VarId| vars.get(v).unwrap_or(v).clone();
+    let mut bb = |l: &BbId| blocks.get(l).unwrap_or(l).clone();
+    let body = func
+        .body
+        .values()
+        .map(|block| {
+            let id = bb(&block.id);
+            let block = BasicBlock {
+                id: id.clone(),
+                insts: block
+                    .insts
+                    .iter()
+                    .map(|inst| inst.map_vars(&mut var))
+                    .collect(),
+                term: block.term.map_vars_and_blocks(&mut var, &mut bb),
+            };
+            (id, block)
+        })
+        .collect();
+
+    Function {
+        params: func.params.iter().map(&mut var).collect(),
+        locals: func.locals.iter().map(&mut var).collect(),
+        body,
+        ..func
+    }
+}
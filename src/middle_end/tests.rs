@@ -0,0 +1,13 @@
+// tests for middle-end functionality.
+
+use super::*;
+
+mod arbitrary_tests;
+mod builder_tests;
+mod canonicalize_tests;
+mod dot_tests;
+mod equiv_tests;
+mod facts_tests;
+mod interval_tests;
+mod link_tests;
+mod translation_validation_tests;
@@ -0,0 +1,43 @@
+// property tests for the random LIR program generator.
+
+use super::lir::*;
+use super::passes::PASSES;
+use arbitrary::Arbitrary;
+
+#[test]
+fn display_roundtrip() {
+    arbtest::builder().run(|u| {
+        let program = Program::arbitrary(u)?;
+        let code = program.to_string();
+        match code.parse::<Program>() {
+            Ok(parsed) => assert_eq!(parsed, program, "Input:\n{code}"),
+            Err(err) => panic!("Parse error: {err:?}\nInput:\n{code}"),
+        }
+        Ok(())
+    });
+}
+
+#[test]
+fn generates_valid_programs() {
+    arbtest::builder().run(|u| {
+        let program = Program::arbitrary(u)?;
+        if let Err(err) = validate(&program) {
+            panic!("The generated program is not valid: {err:?}\nProgram:\n{program}");
+        }
+        Ok(())
+    });
+}
+
+#[test]
+fn passes_preserve_validity() {
+    arbtest::builder().run(|u| {
+        let program = Program::arbitrary(u)?;
+        for (name, pass) in PASSES {
+            let transformed = pass(program.clone());
+            if let Err(err) = validate(&transformed) {
+                panic!("Pass {name} produced an invalid program: {err:?}\nInput:\n{program}\nOutput:\n{transformed}");
+            }
+        }
+        Ok(())
+    });
+}
@@ -0,0 +1,123 @@
+// program builder tests.
+
+use super::lir::builder::*;
+use super::lir::*;
+use crate::interpreter::{interpret, ExternRegistry};
+
+#[test]
+fn counting_loop() {
+    let mut p = ProgramBuilder::new();
+    let mut f = p.function("main", vec![], Some(int_ty()));
+    let i = f.local("i", int_ty());
+    let header = f.fresh_label();
+    let body = f.fresh_label();
+    let exit = f.fresh_label();
+
+    f.entry().jump(&header);
+
+    let mut b = f.block(&header);
+    let t = b.cmp(LirOp![<], &i, 10);
+    b.branch(t, &body, &exit);
+
+    let mut b = f.block(&body);
+    let t = b.arith(LirOp![+], &i, 1);
+    b.copy_to(&i, t);
+    b.jump(&header);
+
+    f.block(&exit).ret(&i);
+    f.finish();
+
+    let program = p.finish().expect("the built program should be valid");
+    let expected: Program = r"fn main() -> int {
+let i:int, _t1:int, _t2:int
+entry:
+  $jump bb1
+bb1:
+  _t1 = $cmp lt i 10
+  $branch _t1 bb2 bb3
+bb2:
+  _t2 = $arith add i 1
+  i = $copy _t2
+  $jump bb1
+bb3:
+  $ret i
+}
+"
+    .parse()
+    .unwrap();
+    assert_eq!(program, expected);
+    assert_eq!(interpret(program, ExternRegistry::default()), Ok(10));
+}
+
+#[test]
+fn calls_structs_and_externs() {
+    let mut p = ProgramBuilder::new();
+    let pair = p.struct_def("pair", vec![("fst", int_ty()), ("snd", int_ty())]);
+    p.extern_decl("print", vec![int_ty()], None);
+
+    let mut f = p.function(
+        "sum",
+        vec![("p", ptr_ty(struct_ty(pair.clone())))],
+        Some(int_ty()),
+    );
+    let param = f.param("p");
+    let mut b = f.entry();
+    let fst = b.gfp(&param, "fst");
+    let snd = b.gfp(&param, "snd");
+    let x = b.load(&fst);
+    let y = b.load(&snd);
+    let sum = b.arith(LirOp![+], x, y);
+    b.ret(sum);
+    f.finish();
+
+    let mut f = p.function("main", vec![], Some(int_ty()));
+    let sum_ptr = f.func_ptr("sum");
+    let mut b = f.entry();
+    let obj = b.alloc(struct_ty(pair), 1);
+    let fst = b.gfp(&obj, "fst");
+    b.store(&fst, 20);
+    let snd = b.gfp(&obj, "snd");
+    b.store(&snd, 1);
+    let (direct, mut b) = b.call("sum", vec![(&obj).into()]);
+    let direct = direct.unwrap();
+    b.call_ext("print", vec![(&direct).into()]);
+    let (indirect, mut b) = b.call_indirect(&sum_ptr, vec![(&obj).into()]);
+    let total = b.arith(LirOp![+], direct, indirect.unwrap());
+    b.ret(total);
+    f.finish();
+
+    let program = p.finish().expect("the built program should be valid");
+    assert_eq!(program.functions[&func_id("main")].body.len(), 3);
+    assert_eq!(interpret(program, ExternRegistry::default()), Ok(42));
+}
+
+#[test]
+fn problems_are_reported_by_finish() {
+    let mut p = ProgramBuilder::new();
+    p.declare("helper", vec![], None);
+    let mut f = p.function("main", vec![], Some(int_ty()));
+    let ptr = f.local("ptr", ptr_ty(int_ty()));
+    let missing = f.fresh_label();
+    let mut b = f.entry();
+    b.arith(LirOp![+], &ptr, 1);
+    b.jump(&missing);
+
+    // started but never given a terminal.
+    let mut b = f.block(&missing);
+    b.copy(1);
+    drop(b);
+    f.finish();
+
+    let errors = p.finish().unwrap_err().errors;
+    assert!(errors.contains("block bb1 of function main was never given a terminal"));
+    assert!(errors.contains("function helper is declared but never built"));
+}
+
+#[test]
+#[should_panic(expected = "block entry of function main has already been built")]
+fn blocks_cannot_be_extended_after_their_terminal() {
+    let mut p = ProgramBuilder::new();
+    let mut f = p.function("main", vec![], Some(int_ty()));
+    f.entry().ret(0);
+    f.entry().ret(1);
+}
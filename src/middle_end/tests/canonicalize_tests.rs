@@ -0,0 +1,87 @@
+// canonicalisation pass tests.
+
+use super::lir::equiv::alpha_equivalent;
+use super::lir::*;
+use super::passes::*;
+
+fn parse(code: &str) -> Program {
+    let program: Program = code.parse().expect("Failed to parse LIR code");
+    validate(&program).expect("The test program is not valid.");
+    program
+}
+
+const SHUFFLED: &str = r"fn main() -> int {
+let i:int, _t9:int, _t2:int, _ret4:int
+bb12:
+  _t2 = $arith add i 1
+  i = $copy _t2
+  $jump bb3
+bb3:
+  _t9 = $cmp lt i 10
+  $branch _t9 bb12 bb10
+bb10:
+  _ret4 = $copy i
+  $ret _ret4
+entry:
+  $jump bb3
+}
+";
+
+const CANONICAL: &str = r"fn main() -> int {
+let _ret2:int, _t1:int, _t3:int, i:int
+entry:
+  $jump bb1
+
+bb1:
+  _t1 = $cmp lt i 10
+  $branch _t1 bb3 bb2
+
+bb2:
+  _ret2 = $copy i
+  $ret _ret2
+
+bb3:
+  _t3 = $arith add i 1
+  i = $copy _t3
+  $jump bb1
+}
+";
+
+#[test]
+fn renames_blocks_and_temps() {
+    let original = parse(SHUFFLED);
+    let canonical = canonicalize(original.clone());
+
+    assert_eq!(canonical.to_string(), CANONICAL);
+    assert!(validate(&canonical).is_ok());
+    assert!(alpha_equivalent(&original, &canonical));
+}
+
+#[test]
+fn is_idempotent() {
+    let canonical = canonicalize(parse(SHUFFLED));
+    assert_eq!(canonicalize(canonical.clone()), canonical);
+    assert_eq!(canonicalize(parse(CANONICAL)), canonical);
+}
+
+#[test]
+fn blocks_are_displayed_in_label_order() {
+    let mut program = parse(CANONICAL);
+    let main = program.functions.get_mut(&func_id("main")).unwrap();
+    // bb10 should come after bb3 even though it sorts before it as a string.
+    let mut bb3 = main.body.remove(&bb_id("bb3")).unwrap();
+    bb3.id = bb_id("bb10");
+    main.body.insert(bb_id("bb10"), bb3);
+    main.body.get_mut(&bb_id("bb1")).unwrap().term = Terminal::Branch {
+        cond: Operand::Var(var_id("_t1", int_ty(), Some(func_id("main")))),
+        tt: bb_id("bb10"),
+        ff: bb_id("bb2"),
+    };
+
+    assert_eq!(
+        program.to_string(),
+        CANONICAL
+            .replace("bb3:", "bb10:")
+            .replace("bb3 bb2", "bb10 bb2")
+    );
+}
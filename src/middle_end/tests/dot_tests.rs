@@ -0,0 +1,106 @@
+// graphviz output, call graph and dominator tests.
+
+use super::analysis::*;
+use super::dot::*;
+use super::lir::*;
+use std::collections::BTreeSet as Set;
+
+const PROGRAM: &str = r#"inc:&(int) -> int
+
+extern print:(int) -> _
+
+fn inc(x:int) -> int {
+let _t1:int
+entry:
+  _t1 = $arith add x 1
+  $ret _t1
+}
+
+fn main() -> int {
+let f:&(int) -> int, i:int, r:int, _t1:int
+entry:
+  f = $copy inc
+  $jump bb1
+bb1:
+  _t1 = $cmp lt i 3
+  $branch _t1 bb2 bb4
+bb2:
+  r = $call_idr f(i) then bb3
+bb3:
+  i = $call_dir inc(i) then bb1
+bb4:
+  $call_ext print(r)
+  $ret r
+}
+"#;
+
+fn parse(code: &str) -> Program {
+    let program: Program = code.parse().expect("Failed to parse LIR code");
+    validate(&program).expect("The test program is not valid.");
+    program
+}
+
+#[test]
+fn dominators() {
+    let program = parse(PROGRAM);
+    let idoms = immediate_dominators(&program.functions[&func_id("main")]);
+
+    let expected = [
+        ("bb1", "entry"),
+        ("bb2", "bb1"),
+        ("bb3", "bb2"),
+        ("bb4", "bb1"),
+    ]
+    .into_iter()
+    .map(|(bb, idom)| (bb_id(bb), bb_id(idom)))
+    .collect();
+    assert_eq!(idoms, expected);
+}
+
+#[test]
+fn calls() {
+    let program = parse(PROGRAM);
+    let graph = call_graph(&program);
+
+    assert_eq!(graph[&func_id("inc")], Set::new());
+    assert_eq!(
+        graph[&func_id("main")],
+        Set::from([
+            (func_id("inc"), CallKind::Direct),
+            (func_id("inc"), CallKind::Indirect),
+            (func_id("print"), CallKind::Extern),
+        ])
+    );
+}
+
+#[test]
+fn cfg_edges() {
+    let program = parse(PROGRAM);
+    let dot = cfg_to_dot(&program.functions[&func_id("main")]);
+
+    assert!(dot.starts_with("digraph \"main\" {\n"));
+    assert!(dot.contains("\"main.bb1\" -> \"main.bb2\" [label=\"true\"]"));
+    assert!(dot.contains("\"main.bb1\" -> \"main.bb4\" [label=\"false\"]"));
+    assert!(dot.contains("\"main.bb2\" -> \"main.bb3\" [label=\"return\", style=dashed]"));
+    assert!(dot.contains("\"main.entry\" -> \"main.bb1\"\n"));
+    assert!(dot.contains("  $ret r\\l\", peripheries=2]"));
+}
+
+#[test]
+fn whole_program_graphs() {
+    let program = parse(PROGRAM);
+
+    let cfgs = program_cfgs_to_dot(&program);
+    assert!(cfgs.contains("subgraph \"cluster_inc\" {"));
+    assert!(cfgs.contains("subgraph \"cluster_main\" {"));
+
+    let calls = call_graph_to_dot(&program);
+    assert!(calls.contains("\"print\" [shape=ellipse]"));
+    assert!(calls.contains("\"main\" -> \"inc\"\n"));
+    assert!(calls.contains("\"main\" -> \"inc\" [style=dashed]"));
+    assert!(calls.contains("\"main\" -> \"print\" [style=dotted]"));
+
+    let doms = program_dominator_trees_to_dot(&program);
+    assert!(doms.contains("\"main.bb1\" -> \"main.bb4\""));
+    assert!(!doms.contains("\"main.bb3\" -> \"main.bb1\""));
+}
@@ -0,0 +1,100 @@
+// alpha-equivalence and diff tests.
+
+use super::lir::equiv::*;
+use super::lir::*;
+
+fn parse(code: &str) -> Program {
+    code.parse().expect("Failed to parse LIR code")
+}
+
+const LEFT: &str = r"fn main() -> int {
+let i:int, p:&int, _t1:int, _t2:int
+entry:
+  p = $alloc 1 [_a1]
+  $jump bb1
+bb1:
+  _t1 = $cmp lt i 10
+  $branch _t1 bb2 bb3
+bb2:
+  _t2 = $arith add i 1
+  i = $copy _t2
+  $jump bb1
+bb3:
+  $store p i
+  $ret i
+}
+";
+
+#[test]
+fn renamed_programs_are_equivalent() {
+    let right = parse(
+        r"fn main() -> int {
+let n:int, q:&int, _t5:int, _t7:int
+bb4:
+  $store q n
+  $ret n
+bb9:
+  _t7 = $arith add n 1
+  n = $copy _t7
+  $jump bb3
+bb3:
+  _t5 = $cmp lt n 10
+  $branch _t5 bb9 bb4
+entry:
+  q = $alloc 1 [_a8]
+  $jump bb3
+}
+",
+    );
+
+    assert!(alpha_equivalent(&parse(LEFT), &right));
+    assert_eq!(diff(&parse(LEFT), &right), None);
+}
+
+#[test]
+fn renaming_must_be_consistent() {
+    // _t1 and _t2 can't both be renamed to _t1.
+    let right = parse(
+        &LEFT
+            .replace("_t2", "_t1")
+            .replace(", _t1:int, _t1:int", ", _t1:int"),
+    );
+    assert!(!alpha_equivalent(&parse(LEFT), &right));
+    assert!(!alpha_equivalent(&right, &parse(LEFT)));
+}
+
+#[test]
+fn minimal_diff() {
+    let right = parse(
+        r"fn main() -> int {
+let n:int, q:&int, _t5:int, _t7:int
+entry:
+  q = $alloc 1 [_a8]
+  $jump bb5
+bb5:
+  _t5 = $cmp lte n 10
+  $branch _t5 bb6 bb7
+bb6:
+  _t7 = $arith add n 1
+  n = $copy _t7
+  $jump bb5
+bb7:
+  $ret n
+}
+",
+    );
+
+    let diff = diff(&parse(LEFT), &right).expect("the programs differ");
+    assert_eq!(
+        diff.to_string(),
+        r"function main:
+  block bb1 (right: bb5):
+    - _t1 = $cmp lt i 10
+    + _t1 = $cmp lte i 10
+      $branch _t1 bb2 bb3
+  block bb3 (right: bb7):
+    - $store p i
+      $ret i
+"
+    );
+}
@@ -0,0 +1,170 @@
+// datalog fact export and import tests.
+
+use super::lir::facts::*;
+use super::lir::*;
+
+const PROGRAM: &str = r"struct node {
+  next:&node
+}
+
+g:&node
+
+extern print:(int) -> _
+
+fn link(p:&node) -> &node {
+let q:&node, f:&&node
+entry:
+  q = $alloc 1 [_a1]
+  f = $gfp q next
+  $store f p
+  $ret q
+}
+
+fn main() -> int {
+let r:&node, s:node
+entry:
+  r = $call_dir link(g) then bb1
+bb1:
+  s = $load r
+  $call_ext print(0)
+  $ret 0
+}
+";
+
+fn program() -> Program {
+    let program: Program = PROGRAM.parse().expect("Failed to parse LIR code");
+    validate(&program).expect("The test program is not valid.");
+    program
+}
+
+fn tuples(facts: &Facts, relation: &str) -> Vec<String> {
+    facts
+        .relation(relation)
+        .iter()
+        .map(|t| t.join("\t"))
+        .collect()
+}
+
+#[test]
+fn ids_are_stable() {
+    let program = program();
+    let facts = export_facts(&program);
+    let again = export_facts(&program.clone());
+    assert_eq!(facts.relations, again.relations);
+
+    let ids = &facts.ids;
+    let g = var_id("g", ptr_ty(struct_ty(struct_id("node"))), None);
+    assert_eq!(ids.var_of(ids.var(&g)), Some(&g));
+    let loc = InstLoc::new(&func_id("main"), &bb_id("bb1"), 2);
+    assert_eq!(ids.inst_of(ids.inst(&loc)), Some(&loc));
+}
+
+#[test]
+fn exported_relations() {
+    let program = program();
+    let facts = export_facts(&program);
+    let ids = &facts.ids;
+
+    let node_ptr = ptr_ty(struct_ty(struct_id("node")));
+    let var = |name: &str, typ: Type, func: &str| {
+        ids.var(&var_id(name, typ, Some(func_id(func)))).to_string()
+    };
+    let q = var("q", node_ptr.clone(), "link");
+    let p = var("p", node_ptr.clone(), "link");
+    let f = var("f", ptr_ty(node_ptr.clone()), "link");
+    let r = var("r", node_ptr.clone(), "main");
+    let s = var("s", struct_ty(struct_id("node")), "main");
+    let g = ids.var(&var_id("g", node_ptr.clone(), None)).to_string();
+    let site = ids.var(&var_id("_a1", struct_ty(struct_id("node")), None));
+    let inst = |func: &str, bb: &str, idx: usize| {
+        ids.inst(&InstLoc::new(&func_id(func), &bb_id(bb), idx))
+            .to_string()
+    };
+
+    assert_eq!(
+        tuples(&facts, "alloc"),
+        vec![format!("{}\t{q}\t{site}", inst("link", "entry", 0))]
+    );
+    assert_eq!(
+        tuples(&facts, "gfp"),
+        vec![format!("{}\t{f}\t{q}\tnext", inst("link", "entry", 1))]
+    );
+    assert_eq!(
+        tuples(&facts, "store"),
+        vec![format!("{}\t{f}\t{p}", inst("link", "entry", 2))]
+    );
+    assert_eq!(
+        tuples(&facts, "load"),
+        vec![format!("{}\t{s}\t{r}", inst("main", "bb1", 0))]
+    );
+    assert_eq!(
+        tuples(&facts, "call_direct"),
+        vec![format!("{}\tlink", inst("main", "entry", 0))]
+    );
+    assert_eq!(
+        tuples(&facts, "call_ext"),
+        vec![format!("{}\tprint", inst("main", "bb1", 1))]
+    );
+    assert_eq!(
+        tuples(&facts, "actual_arg"),
+        vec![format!("{}\t0\t{g}", inst("main", "entry", 0))]
+    );
+    assert_eq!(
+        tuples(&facts, "call_lhs"),
+        vec![format!("{}\t{r}", inst("main", "entry", 0))]
+    );
+    assert_eq!(tuples(&facts, "return_var"), vec![format!("link\t{q}")]);
+    assert_eq!(
+        tuples(&facts, "succ"),
+        vec![format!(
+            "{}\t{}",
+            ids.block(&func_id("main"), &bb_id("entry")),
+            ids.block(&func_id("main"), &bb_id("bb1"))
+        )]
+    );
+    assert!(tuples(&facts, "var_type").contains(&format!("{site}\tnode")));
+    assert!(declarations()
+        .contains(".decl gfp(inst: number, lhs: number, src: number, field: symbol)\n.input gfp"));
+}
+
+#[test]
+fn write_and_import() {
+    let program = program();
+    let facts = export_facts(&program);
+    let dir = tempfile::tempdir().unwrap();
+    facts.write_to_dir(dir.path()).unwrap();
+
+    let alloc = std::fs::read_to_string(dir.path().join("alloc.facts")).unwrap();
+    assert_eq!(alloc, tuples(&facts, "alloc")[0].clone() + "\n");
+
+    // pretend an external tool derived that r points to the allocation site.
+    let node_ptr = ptr_ty(struct_ty(struct_id("node")));
+    let r = var_id("r", node_ptr, Some(func_id("main")));
+    let site = var_id("_a1", struct_ty(struct_id("node")), None);
+    let derived = dir.path().join("points_to.csv");
+    std::fs::write(
+        &derived,
+        format!("{}\t{}\tmain\n", facts.ids.var(&r), facts.ids.var(&site)),
+    )
+    .unwrap();
+
+    let imported = import_relation(
+        &program,
+        &facts.ids,
+        &derived,
+        &[FactKind::Var, FactKind::Var, FactKind::Func],
+    )
+    .unwrap();
+    assert_eq!(
+        imported.into_iter().collect::<Vec<_>>(),
+        vec![vec![
+            FactValue::Var(r),
+            FactValue::Var(site),
+            FactValue::Func(func_id("main"))
+        ]]
+    );
+
+    assert!(parse_relation(&program, &facts.ids, "1\t2\n", &[FactKind::Var]).is_err());
+    assert!(parse_relation(&program, &facts.ids, "nope\n", &[FactKind::Func]).is_err());
+    assert!(parse_relation(&program, &facts.ids, "99999\n", &[FactKind::Block]).is_err());
+}
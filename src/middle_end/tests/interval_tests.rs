@@ -0,0 +1,239 @@
+// interval analysis tests.
+
+use super::analysis::*;
+use super::lir::*;
+
+fn analyze(code: &str) -> (Program, IntervalAnalysis) {
+    let program: Program = code.parse().expect("Failed to parse LIR code");
+    validate(&program).expect("The test program is not valid.");
+    let result = analyze_intervals(&program);
+    (program, result)
+}
+
+fn loc(func: &str, bb: &str, idx: usize) -> InstLoc {
+    InstLoc::new(&func_id(func), &bb_id(bb), idx)
+}
+
+fn local(func: &str, name: &str) -> Operand {
+    Operand::Var(var_id(name, int_ty(), Some(func_id(func))))
+}
+
+fn warnings(result: &IntervalAnalysis) -> Vec<(InstLoc, IntervalWarningKind)> {
+    result
+        .warnings
+        .iter()
+        .map(|w| (w.loc.clone(), w.kind))
+        .collect()
+}
+
+const COUNTING_LOOP: &str = r"fn main() -> int {
+let i:int, _t1:int, _t2:int
+entry:
+  $jump bb1
+bb1:
+  _t1 = $cmp lt i 10
+  $branch _t1 bb2 bb3
+bb2:
+  _t2 = $arith sub i 5
+  _t2 = $arith div 100 _t2
+  i = $arith add i 1
+  $jump bb1
+bb3:
+  $ret i
+}
+";
+
+#[test]
+fn loop_bounds_after_narrowing() {
+    let (_, result) = analyze(COUNTING_LOOP);
+    let i = local("main", "i");
+
+    assert_eq!(
+        result.interval_before(&loc("main", "bb1", 0), &i),
+        Some(Interval::new(0, 10))
+    );
+    assert_eq!(
+        result.interval_before(&loc("main", "bb2", 0), &i),
+        Some(Interval::new(0, 9))
+    );
+    assert_eq!(
+        result.interval_before(&loc("main", "bb3", 0), &i),
+        Some(Interval::constant(10))
+    );
+    assert!(result.is_tracked(
+        &func_id("main"),
+        &var_id("i", int_ty(), Some(func_id("main")))
+    ));
+
+    // `i - 5` may be zero, but the increment can't overflow.
+    assert_eq!(
+        warnings(&result),
+        vec![(loc("main", "bb2", 1), IntervalWarningKind::DivisionByZero)]
+    );
+}
+
+#[test]
+fn division_by_parameter() {
+    let (_, result) = analyze(
+        r"fn f(x:int) -> int {
+let _t1:int, _t2:int, _t3:int
+entry:
+  _t1 = $arith div 10 x
+  _t2 = $cmp gt x 0
+  $branch _t2 bb1 bb2
+bb1:
+  _t3 = $arith div 10 x
+  $jump bb2
+bb2:
+  $ret _t1
+}
+
+fn main() -> int {
+entry:
+  $ret 0
+}
+",
+    );
+
+    assert_eq!(
+        result.interval_before(&loc("f", "bb1", 0), &local("f", "x")),
+        Some(Interval::new(1, i32::MAX as i64))
+    );
+    assert_eq!(
+        warnings(&result),
+        vec![(loc("f", "entry", 0), IntervalWarningKind::DivisionByZero)]
+    );
+}
+
+#[test]
+fn negative_alloc() {
+    let (_, result) = analyze(
+        r"fn f(n:int) -> &int {
+let p:&int, q:&int
+entry:
+  p = $alloc n [_a1]
+  q = $alloc 3 [_a2]
+  $ret p
+}
+
+fn main() -> int {
+entry:
+  $ret 0
+}
+",
+    );
+
+    assert_eq!(
+        warnings(&result),
+        vec![(loc("f", "entry", 0), IntervalWarningKind::NegativeAlloc)]
+    );
+}
+
+#[test]
+fn overflow() {
+    let (_, result) = analyze(
+        r"fn main() -> int {
+let x:int, y:int, z:int
+entry:
+  x = $copy 2147483647
+  y = $arith sub x 1
+  z = $arith add x 1
+  z = $arith div -2147483648 -1
+  z = $arith mul y y
+  $ret z
+}
+",
+    );
+
+    assert_eq!(
+        warnings(&result),
+        vec![
+            (loc("main", "entry", 2), IntervalWarningKind::Overflow),
+            (loc("main", "entry", 3), IntervalWarningKind::Overflow),
+            (loc("main", "entry", 4), IntervalWarningKind::Overflow),
+        ]
+    );
+    assert_eq!(
+        result.interval_before(&loc("main", "entry", 3), &local("main", "z")),
+        Some(Interval::constant(i32::MAX as i64 + 1))
+    );
+}
+
+#[test]
+fn unreachable_code_has_no_state() {
+    let (_, result) = analyze(
+        r"fn main() -> int {
+let x:int, _t1:int
+entry:
+  _t1 = $cmp gt x 0
+  $branch _t1 bb1 bb2
+bb1:
+  x = $arith div 1 0
+  $jump bb2
+bb2:
+  $ret x
+}
+",
+    );
+
+    // x is zero-initialized, so the true branch is never taken.
+    assert_eq!(result.state_before(&loc("main", "bb1", 0)), None);
+    assert!(result.warnings.is_empty());
+    assert_eq!(
+        result.interval_before(&loc("main", "bb2", 0), &local("main", "x")),
+        Some(Interval::constant(0))
+    );
+}
+
+#[test]
+fn comparisons_overwriting_their_operands() {
+    let (_, result) = analyze(
+        r"fn f(x:int) -> int {
+entry:
+  x = $cmp gt x 10
+  $branch x bb1 bb2
+bb1:
+  $jump bb2
+bb2:
+  $ret x
+}
+
+fn main() -> int {
+let _t1:int
+entry:
+  _t1 = $call_dir f(20) then bb1
+bb1:
+  $ret _t1
+}
+",
+    );
+
+    // the branch tests the result of the comparison, not the old x.
+    assert_eq!(
+        result.interval_before(&loc("f", "bb1", 0), &local("f", "x")),
+        Some(Interval::constant(1))
+    );
+    assert_eq!(
+        result.interval_before(&loc("f", "bb2", 0), &local("f", "x")),
+        Some(Interval::new(0, 1))
+    );
+}
+
+#[test]
+fn interval_arithmetic() {
+    let top = Interval::top();
+    assert_eq!(top.to_string(), "[-inf, +inf]");
+    assert_eq!(
+        Interval::new(0, 5).widen(&Interval::new(0, 6)),
+        Interval::new(0, i64::MAX)
+    );
+    assert_eq!(
+        Interval::new(0, i64::MAX).narrow(&Interval::new(0, 10)),
+        Interval::new(0, 10)
+    );
+    assert_eq!(Interval::new(-3, 2).meet(&Interval::new(3, 4)), None);
+    assert_eq!(
+        Interval::new(-3, 2).join(&Interval::new(3, 4)),
+        Interval::new(-3, 4)
+    );
+}
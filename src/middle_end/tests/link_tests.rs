@@ -0,0 +1,132 @@
+// linker tests.
+
+use super::lir::*;
+use crate::interpreter::{interpret, ExternRegistry};
+
+fn parse(code: &str) -> Program {
+    code.parse().expect("Failed to parse LIR code")
+}
+
+fn link_errors(modules: &[&str]) -> Vec<String> {
+    let modules = modules.iter().map(|m| parse(m)).collect::<Vec<_>>();
+    link(&modules)
+        .expect_err("linking should fail")
+        .errors
+        .into_iter()
+        .collect()
+}
+
+const MAIN: &str = r"struct pair {
+  fst:int
+  snd:int
+}
+
+extern print:(int) -> _
+extern square:(int) -> int
+
+fn main() -> int {
+let p:&pair, x:int, y:int
+entry:
+  p = $alloc 1 [_a1]
+  x = $call_ext square(3)
+  $call_ext print(x)
+  y = $arith add x 1
+  $ret y
+}
+";
+
+const SQUARE: &str = r"struct pair {
+  fst:int
+  snd:int
+}
+
+fn square(n:int) -> int {
+let p:&pair, r:int
+entry:
+  p = $alloc 1 [_a1]
+  r = $arith mul n n
+  $ret r
+}
+";
+
+#[test]
+fn resolves_externs() {
+    let program = link(&[parse(MAIN), parse(SQUARE)]).expect("linking should succeed");
+
+    let expected = parse(
+        r"struct pair {
+  fst:int
+  snd:int
+}
+
+extern print:(int) -> _
+
+fn main() -> int {
+let p:&pair, x:int, y:int
+entry:
+  p = $alloc 1 [_a1]
+  x = $call_dir square(3) then bb1
+bb1:
+  $call_ext print(x)
+  y = $arith add x 1
+  $ret y
+}
+
+fn square(n:int) -> int {
+let p:&pair, r:int
+entry:
+  p = $alloc 1 [_a1_1]
+  r = $arith mul n n
+  $ret r
+}
+",
+    );
+    assert_eq!(program, expected);
+    assert_eq!(interpret(program, ExternRegistry::default()), Ok(10));
+}
+
+#[test]
+fn reports_conflicts() {
+    let other_pair = r"struct pair {
+  fst:int
+}
+
+g:int
+
+fn square(n:int) -> int {
+entry:
+  $ret n
+}
+";
+    let other_g = r"g:&int
+
+fn main() -> int {
+entry:
+  $ret 0
+}
+";
+
+    assert_eq!(
+        link_errors(&[MAIN, other_pair, other_g]),
+        vec![
+            "function main is defined by more than one module",
+            "global g has conflicting types int and &int",
+            "struct pair has conflicting definitions",
+        ]
+    );
+}
+
+#[test]
+fn requires_main_and_matching_externs() {
+    let square = r"fn square(n:int, m:int) -> int {
+entry:
+  $ret n
+}
+";
+
+    assert_eq!(link_errors(&[square]), vec!["no module defines main"]);
+    assert_eq!(
+        link_errors(&[MAIN, square]),
+        vec!["extern square has type (int) -> int but is defined with type (int,int) -> int"]
+    );
+}
@@ -0,0 +1,401 @@
+// translation validation tests.
+
+use super::lir::*;
+use super::passes::*;
+use super::translation_validation::*;
+use arbitrary::Arbitrary;
+
+// the functions under test are called f; parse adds a trivial main.
+fn parse(code: &str) -> Program {
+    let code = format!("{code}\nfn main() -> int {{\nentry:\n  $ret 0\n}}\n");
+    let program: Program = code.parse().expect("Failed to parse LIR code");
+    validate(&program).expect("The test program is not valid.");
+    program
+}
+
+fn check(original: &str, transformed: &str) -> Verdict {
+    check_function(
+        &parse(original),
+        &parse(transformed),
+        &func_id("f"),
+        &Bounds::default(),
+    )
+}
+
+fn assert_refines(original: &str, transformed: &str) {
+    match check(original, transformed) {
+        Verdict::Refines { .. } => {}
+        verdict => panic!("expected refinement, got {verdict}"),
+    }
+}
+
+fn mismatch(original: &str, transformed: &str) -> Counterexample {
+    match check(original, transformed) {
+        Verdict::Mismatch(cex) => cex,
+        verdict => panic!("expected a mismatch, got {verdict}"),
+    }
+}
+
+// SECTION: the solver
+
+fn x() -> Lin {
+    Lin::atom(0)
+}
+
+fn y() -> Lin {
+    Lin::atom(1)
+}
+
+fn k(n: i64) -> Lin {
+    Lin::constant(n)
+}
+
+#[test]
+fn solver_finds_models() {
+    // x + y == 10, x - y >= 4, y > 2
+    let cs = [
+        Constraint::eq(&x().add(&y()).unwrap(), &k(10)).unwrap(),
+        Constraint::le(&k(4), &x().sub(&y()).unwrap()).unwrap(),
+        Constraint::lt(&k(2), &y()).unwrap(),
+    ];
+    let Solution::Sat(model) = solve(&cs) else {
+        panic!("expected a model")
+    };
+    assert!(cs.iter().all(|c| c.holds(&model) == Some(true)));
+}
+
+#[test]
+fn solver_refutes() {
+    // x < y and y < x
+    let cs = [
+        Constraint::lt(&x(), &y()).unwrap(),
+        Constraint::lt(&y(), &x()).unwrap(),
+    ];
+    assert_eq!(solve(&cs), Solution::Unsat);
+
+    // 2x == 1 has no integer solution.
+    let cs = [Constraint::eq(&x().scale(2).unwrap(), &k(1)).unwrap()];
+    assert_eq!(solve(&cs), Solution::Unsat);
+
+    // 0 <= 3x <= 2 and x != 0
+    let cs = [
+        Constraint::le(&k(0), &x().scale(3).unwrap()).unwrap(),
+        Constraint::le(&x().scale(3).unwrap(), &k(2)).unwrap(),
+        Constraint::ne(&x(), &k(0)).unwrap(),
+    ];
+    assert_eq!(solve(&cs), Solution::Unsat);
+}
+
+#[test]
+fn solver_splits_disequalities() {
+    // 0 <= x <= 2, x != 0, x != 1
+    let cs = [
+        Constraint::le(&k(0), &x()).unwrap(),
+        Constraint::le(&x(), &k(2)).unwrap(),
+        Constraint::ne(&x(), &k(0)).unwrap(),
+        Constraint::ne(&x(), &k(1)).unwrap(),
+    ];
+    assert_eq!(solve(&cs), Solution::Sat([(0, 2)].into()));
+}
+
+// SECTION: checking functions
+
+const SUM: &str = r"fn f(n:int) -> int {
+let i:int, s:int, _t1:int
+entry:
+  $jump bb1
+bb1:
+  _t1 = $cmp lt i n
+  $branch _t1 bb2 bb3
+bb2:
+  s = $arith add s i
+  i = $arith add i 1
+  $jump bb1
+bb3:
+  $ret s
+}
+";
+
+#[test]
+fn identical_functions_refine() {
+    assert_refines(SUM, SUM);
+    assert!(matches!(
+        check(SUM, SUM),
+        Verdict::Refines {
+            complete: false,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn canonicalization_refines() {
+    let original = parse(SUM);
+    let renamed = canonicalize(original.clone());
+    let verdict = check_function(&original, &renamed, &func_id("f"), &Bounds::default());
+    assert!(matches!(verdict, Verdict::Refines { .. }), "{verdict}");
+}
+
+#[test]
+fn wrong_operator_is_caught() {
+    let cex = mismatch(
+        SUM,
+        &SUM.replace("s = $arith add s i", "s = $arith sub s i"),
+    );
+    assert!(cex.reason.starts_with("the return value differs"), "{cex}");
+    // the loop must have run at least twice for the sums to differ.
+    let n = cex.inputs.iter().find(|(x, _)| x == "n").unwrap().1;
+    assert!(n >= 2, "{cex}");
+}
+
+#[test]
+fn algebraic_rewrites_refine() {
+    let original = r"fn f(x:int, y:int) -> int {
+let a:int, b:int, c:int
+entry:
+  a = $arith mul x 2
+  b = $arith mul x y
+  c = $arith add a b
+  $ret c
+}
+";
+    let transformed = r"fn f(x:int, y:int) -> int {
+let a:int, b:int, c:int
+entry:
+  b = $arith mul x y
+  a = $arith add x x
+  c = $arith add b a
+  $ret c
+}
+";
+    assert_refines(original, transformed);
+}
+
+#[test]
+fn division_by_constants_is_exact() {
+    assert_refines(
+        r"fn f(x:int) -> int {
+let a:int, b:int
+entry:
+  a = $arith mul x 6
+  b = $arith div a 3
+  $ret b
+}
+",
+        r"fn f(x:int) -> int {
+let b:int
+entry:
+  b = $arith add x x
+  $ret b
+}
+",
+    );
+}
+
+#[test]
+fn original_runtime_errors_allow_anything() {
+    // the original fails when d is 0, so the transformed function may return
+    // anything in that case.
+    assert_refines(
+        r"fn f(d:int) -> int {
+let q:int
+entry:
+  q = $arith div 10 d
+  $ret 1
+}
+",
+        r"fn f(d:int) -> int {
+entry:
+  $ret 1
+}
+",
+    );
+
+    // but not the other way around.
+    let cex = mismatch(
+        r"fn f(d:int) -> int {
+entry:
+  $ret 1
+}
+",
+        r"fn f(d:int) -> int {
+let q:int
+entry:
+  q = $arith div 10 d
+  $ret 1
+}
+",
+    );
+    assert_eq!(cex.inputs, vec![("d".to_string(), 0)]);
+}
+
+#[test]
+fn stores_are_compared() {
+    let original = r"fn f(p:&int, q:&int) -> int {
+let x:int
+entry:
+  $store p 1
+  $store q 2
+  x = $load p
+  $ret x
+}
+";
+    // forwarding the stored value is wrong when p and q alias.
+    let cex = mismatch(
+        original,
+        r"fn f(p:&int, q:&int) -> int {
+entry:
+  $store p 1
+  $store q 2
+  $ret 1
+}
+",
+    );
+    assert!(cex.reason.starts_with("the return value differs"), "{cex}");
+
+    // dropping a store changes the memory.
+    let cex = mismatch(
+        r"fn f(p:&int) -> int {
+entry:
+  $store p 1
+  $ret 0
+}
+",
+        r"fn f(p:&int) -> int {
+entry:
+  $ret 0
+}
+",
+    );
+    assert!(
+        cex.reason
+            .starts_with("the memory written at the return differs"),
+        "{cex}"
+    );
+}
+
+#[test]
+fn independent_stores_commute() {
+    let original = r"fn f(n:int) -> int {
+let p:&int, q:&int, x:int
+entry:
+  p = $alloc 1 [_a1]
+  q = $alloc 1 [_a2]
+  $store p n
+  $store q 2
+  x = $load p
+  $ret x
+}
+";
+    assert_refines(
+        original,
+        r"fn f(n:int) -> int {
+let p:&int, q:&int, x:int
+entry:
+  p = $alloc 1 [_a1]
+  q = $alloc 1 [_a2]
+  $store q 2
+  $store p n
+  $ret n
+}
+",
+    );
+}
+
+#[test]
+fn calls_are_compared() {
+    let original = r"g:int
+
+extern print:(int) -> _
+
+fn f() -> int {
+entry:
+  $call_ext print(g)
+  g = $copy 1
+  $ret g
+}
+";
+    assert_refines(original, original);
+
+    // g may change during the call, so moving the assignment is wrong.
+    let cex = mismatch(
+        original,
+        &original.replace(
+            "  $call_ext print(g)\n  g = $copy 1\n",
+            "  g = $copy 1\n  $call_ext print(1)\n",
+        ),
+    );
+    assert!(cex.reason.contains("call #1"), "{cex}");
+}
+
+#[test]
+fn canonicalization_refines_on_random_programs() {
+    arbtest::builder().budget_ms(2000).run(|u| {
+        let program = Program::arbitrary(u)?;
+        let renamed = canonicalize(program.clone());
+        let bounds = Bounds {
+            depth: 8,
+            max_paths: 32,
+        };
+        for (f, verdict) in check_program(&program, &renamed, &bounds) {
+            if let Verdict::Mismatch(cex) = verdict {
+                panic!("{f}: {cex}\nProgram:\n{program}");
+            }
+        }
+        Ok(())
+    });
+}
+
+// SECTION: the pass manager
+
+// a pass that breaks additions.
+fn broken(program: Program) -> Program {
+    let mut program = program;
+    for func in program.functions.values_mut() {
+        for bb in func.body.values_mut() {
+            for inst in bb.insts.iter_mut() {
+                if let Instruction::Arith { aop, .. } = inst {
+                    if *aop == ArithmeticOp::Add {
+                        *aop = ArithmeticOp::Subtract;
+                    }
+                }
+            }
+        }
+    }
+    program
+}
+
+const INCREMENT: &str = r"fn f(x:int) -> int {
+let y:int
+entry:
+  y = $arith add x 1
+  $ret y
+}
+";
+
+#[test]
+fn pass_manager_verifies_passes() {
+    let mut manager = PassManager::new();
+    manager.add("canonicalize", canonicalize);
+    manager.verify(Bounds::default());
+    let (program, warnings) = manager
+        .run(parse(SUM))
+        .expect("canonicalization is correct");
+    assert_eq!(program, canonicalize(parse(SUM)));
+    assert_eq!(warnings, Vec::<String>::new());
+
+    manager.add("broken", broken);
+    let err = manager
+        .run(parse(INCREMENT))
+        .expect_err("the broken pass must be caught");
+    assert_eq!(err.pass, "broken");
+    assert!(
+        err.to_string().starts_with("pass broken miscompiles f:"),
+        "{err}"
+    );
+
+    // without verification the broken pass goes unnoticed.
+    let mut manager = PassManager::new();
+    manager.add("broken", broken);
+    assert!(manager.run(parse(INCREMENT)).is_ok());
+}
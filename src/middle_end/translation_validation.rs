@@ -0,0 +1,1473 @@
+// translation validation: checking that a transformed LIR function refines
+// the original one.
+//
+// both functions are executed symbolically from the same symbolic inputs
+// (parameters, globals and the initial contents of memory), exploring every
+// path up to a bounded number of basic blocks. each path ends with a path
+// condition and an outcome. for every pair of paths whose conditions can hold
+// together, the outcomes must agree: the return value, the final values of the
+// globals, the memory cells written, and the sequence of calls (callee,
+// arguments, and the globals and memory visible to the callee). paths on
+// which the original function fails at runtime constrain nothing, since
+// transformations may assume runtime errors don't happen.
+//
+// integers are linear expressions over unknowns, decided by the small solver
+// in `solver`. everything the solver can't represent (multiplying two
+// unknowns, dividing by an unknown, ordering pointers into different objects)
+// becomes an opaque unknown, keyed by the operation and its operands so that
+// the same computation in both functions yields the same unknown. a
+// counterexample is only reported if its model is consistent with what the
+// opaque unknowns stand for; otherwise the verdict is `Unknown`.
+//
+// the heap model is deliberately simple:
+//
+// - memory that existed before the function was called is a single region
+//   indexed by integer addresses (nil is address 0). objects allocated by the
+//   function are separate regions that never alias it, and the k-th
+//   allocation of one function corresponds to the k-th allocation of the
+//   other. accessing an allocated object out of bounds is treated as a
+//   runtime error.
+// - a call may change any global and any memory cell, so after a call every
+//   global and cell is a fresh unknown (shared by both functions, provided
+//   their calls line up). this is also why pointers loaded after a call are
+//   assumed to point into the pre-existing region; passes that depend on
+//   objects allocated before a call may therefore be rejected.
+
+// use ordered sets and maps to allow for deterministic outputs.
+#[allow(unused_imports)]
+use std::collections::{BTreeMap as Map, BTreeSet as Set};
+
+use std::fmt;
+
+use crate::middle_end::lir::*;
+
+mod solver;
+
+pub use self::solver::*;
+
+// how much of each function to explore.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Bounds {
+    // the maximum number of basic blocks executed along a path.
+    pub depth: usize,
+    // the maximum number of paths explored per function.
+    pub max_paths: usize,
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Bounds {
+            depth: 32,
+            max_paths: 256,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Verdict {
+    // the functions agree on every pair of explored paths. `complete` is false
+    // if some paths were cut off by the bounds.
+    Refines { paths: usize, complete: bool },
+    // the transformed function can behave differently.
+    Mismatch(Counterexample),
+    // the checker could neither prove nor refute refinement.
+    Unknown(String),
+}
+
+// inputs under which the two functions behave differently.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Counterexample {
+    pub reason: String,
+    // the relevant unknowns (e.g., parameters and globals) and their values.
+    pub inputs: Vec<(String, i64)>,
+}
+
+impl fmt::Display for Counterexample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.reason)?;
+        if !self.inputs.is_empty() {
+            let inputs = self
+                .inputs
+                .iter()
+                .map(|(x, v)| format!("{x} = {v}"))
+                .collect::<Vec<_>>();
+            write!(f, " when {}", inputs.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Refines {
+                paths,
+                complete: true,
+            } => write!(f, "verified ({paths} path pairs)"),
+            Verdict::Refines {
+                paths,
+                complete: false,
+            } => write!(f, "verified up to the bounds ({paths} path pairs)"),
+            Verdict::Mismatch(cex) => write!(f, "mismatch: {cex}"),
+            Verdict::Unknown(why) => write!(f, "unknown: {why}"),
+        }
+    }
+}
+
+// check every function of `original` that also exists in `transformed`.
+// functions that disappeared or changed signature are mismatches.
+pub fn check_program(
+    original: &Program,
+    transformed: &Program,
+    bounds: &Bounds,
+) -> Map<FuncId, Verdict> {
+    original
+        .functions
+        .keys()
+        .map(|f| (f.clone(), check_function(original, transformed, f, bounds)))
+        .collect()
+}
+
+pub fn check_function(
+    original: &Program,
+    transformed: &Program,
+    func: &FuncId,
+    bounds: &Bounds,
+) -> Verdict {
+    let Some(f) = original.functions.get(func) else {
+        return Verdict::Unknown(format!("{func} is not a function of the original program"));
+    };
+    let Some(g) = transformed.functions.get(func) else {
+        return Verdict::Mismatch(Counterexample {
+            reason: format!("{func} was removed"),
+            inputs: vec![],
+        });
+    };
+    let types = |h: &Function| {
+        (
+            h.ret_ty.clone(),
+            h.params.iter().map(|p| p.typ()).collect::<Vec<_>>(),
+        )
+    };
+    if types(f) != types(g) {
+        return Verdict::Mismatch(Counterexample {
+            reason: format!("the signature of {func} changed"),
+            inputs: vec![],
+        });
+    }
+
+    let mut checker = Checker {
+        atoms: vec![],
+        atom_ids: Map::new(),
+        params: f.params.iter().map(|p| p.name().to_string()).collect(),
+    };
+    let left = checker.explore(original, f, bounds);
+    let right = checker.explore(transformed, g, bounds);
+    checker.compare_paths(&left, &right)
+}
+
+// SECTION: symbolic values
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum Base {
+    // memory that existed before the call; the offset is the address.
+    Heap,
+    // the k-th object allocated by the function; the offset is the index.
+    Alloc(usize),
+}
+
+// a pointer: an offset into a region, followed by a path of field accesses.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+struct Ptr {
+    base: Base,
+    offset: Lin,
+    fields: Vec<FieldId>,
+}
+
+impl Ptr {
+    fn nil() -> Self {
+        Ptr {
+            base: Base::Heap,
+            offset: Lin::constant(0),
+            fields: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum FnVal {
+    Func(FuncId),
+    Unknown(Atom),
+}
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum SymVal {
+    Int(Lin),
+    Ptr(Ptr),
+    Fn(FnVal),
+    // structs are never inspected as a whole, only copied.
+    Struct(Atom),
+}
+
+// what an unknown stands for. unknowns with the same key are the same unknown
+// in both functions.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum AtomKey {
+    // the value of the i-th parameter.
+    Param(usize),
+    // the value of a global at the start of an epoch. the epochs of a path are
+    // separated by its calls.
+    Global(usize, VarId),
+    // the contents of a memory cell at the start of an epoch.
+    Memory(usize, Ptr, Type),
+    // the value returned by the call that starts an epoch.
+    CallResult(usize),
+    // the zero-initialized value of a struct type.
+    ZeroStruct(StructId),
+    // an operation the solver can't reason about.
+    Op(OpaqueOp, Vec<SymVal>),
+}
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+enum OpaqueOp {
+    Mul,
+    Div,
+    // ComparisonOp isn't Ord, so this is its discriminant.
+    Cmp(u8),
+}
+
+// SECTION: symbolic execution
+
+#[derive(Clone, Debug)]
+struct Cell {
+    addr: Ptr,
+    typ: Type,
+    val: SymVal,
+    // false for cells that were only read.
+    written: bool,
+}
+
+#[derive(Clone, Debug)]
+struct Allocation {
+    size: Lin,
+    // the epoch in which the object was allocated.
+    epoch: usize,
+}
+
+// the state visible to a callee, or to the caller when the function returns.
+#[derive(Clone, Debug)]
+struct Snapshot {
+    globals: Map<VarId, SymVal>,
+    writes: Vec<Cell>,
+    epoch: usize,
+    allocs: Vec<Allocation>,
+    cells: Vec<Cell>,
+}
+
+#[derive(Clone, Debug)]
+enum Callee {
+    Internal(FnVal),
+    External(FuncId),
+}
+
+#[derive(Clone, Debug)]
+struct Event {
+    callee: Callee,
+    args: Vec<SymVal>,
+    state: Snapshot,
+}
+
+#[derive(Clone, Debug)]
+enum End {
+    Return {
+        value: Option<SymVal>,
+        state: Snapshot,
+    },
+    Error(String),
+    // the path hit the bounds.
+    Truncated,
+    // the path did something the checker doesn't model.
+    Unsupported(String),
+}
+
+#[derive(Clone, Debug)]
+struct Path {
+    pc: Vec<Constraint>,
+    events: Vec<Event>,
+    end: End,
+}
+
+#[derive(Clone, Debug)]
+struct PathState {
+    pc: Vec<Constraint>,
+    env: Map<VarId, SymVal>,
+    globals: Map<VarId, SymVal>,
+    epoch: usize,
+    cells: Vec<Cell>,
+    allocs: Vec<Allocation>,
+    events: Vec<Event>,
+    bb: BbId,
+    idx: usize,
+    blocks: usize,
+}
+
+impl PathState {
+    fn end(self, end: End) -> Path {
+        Path {
+            pc: self.pc,
+            events: self.events,
+            end,
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            globals: self.globals.clone(),
+            writes: self.cells.iter().filter(|c| c.written).cloned().collect(),
+            epoch: self.epoch,
+            allocs: self.allocs.clone(),
+            cells: self.cells.clone(),
+        }
+    }
+}
+
+// the result of executing part of a path.
+enum Next {
+    Running(PathState),
+    Done(Path),
+}
+
+// a path that did something the checker doesn't model.
+type Stuck = Box<Path>;
+
+fn stuck(s: PathState, why: impl Into<String>) -> Stuck {
+    Box::new(s.end(End::Unsupported(why.into())))
+}
+
+struct Checker {
+    atoms: Vec<AtomKey>,
+    atom_ids: Map<AtomKey, Atom>,
+    // the parameter names of the original function, for counterexamples.
+    params: Vec<String>,
+}
+
+impl Checker {
+    fn atom(&mut self, key: AtomKey) -> Atom {
+        if let Some(a) = self.atom_ids.get(&key) {
+            return *a;
+        }
+        let a = self.atoms.len();
+        self.atoms.push(key.clone());
+        self.atom_ids.insert(key, a);
+        a
+    }
+
+    // an unknown value of the given type.
+    fn unknown(&mut self, key: AtomKey, typ: &Type) -> SymVal {
+        let a = self.atom(key);
+        match &*typ.0 {
+            LirType::Int => SymVal::Int(Lin::atom(a)),
+            LirType::Pointer(_) => SymVal::Ptr(Ptr {
+                base: Base::Heap,
+                offset: Lin::atom(a),
+                fields: vec![],
+            }),
+            LirType::Function { .. } => SymVal::Fn(FnVal::Unknown(a)),
+            LirType::Struct(_) => SymVal::Struct(a),
+        }
+    }
+
+    fn zero(&mut self, typ: &Type) -> SymVal {
+        match &*typ.0 {
+            LirType::Int => SymVal::Int(Lin::constant(0)),
+            LirType::Pointer(_) => SymVal::Ptr(Ptr::nil()),
+            LirType::Function { .. } => unreachable!("function values are not allowed in LIR"),
+            LirType::Struct(id) => SymVal::Struct(self.atom(AtomKey::ZeroStruct(id.clone()))),
+        }
+    }
+
+    fn explore(&mut self, program: &Program, func: &Function, bounds: &Bounds) -> Vec<Path> {
+        let mut env = Map::new();
+        for (i, p) in func.params.iter().enumerate() {
+            let v = self.unknown(AtomKey::Param(i), &p.typ());
+            env.insert(p.clone(), v);
+        }
+        for x in &func.locals {
+            let v = self.zero(&x.typ());
+            env.insert(x.clone(), v);
+        }
+        let globals = program
+            .globals
+            .iter()
+            .map(|g| {
+                (
+                    g.clone(),
+                    self.unknown(AtomKey::Global(0, g.clone()), &g.typ()),
+                )
+            })
+            .collect();
+
+        let mut worklist = vec![PathState {
+            pc: vec![],
+            env,
+            globals,
+            epoch: 0,
+            cells: vec![],
+            allocs: vec![],
+            events: vec![],
+            bb: bb_id("entry"),
+            idx: 0,
+            blocks: 1,
+        }];
+        let mut paths = vec![];
+        while let Some(s) = worklist.pop() {
+            if paths.len() + worklist.len() >= bounds.max_paths || s.blocks > bounds.depth {
+                paths.push(s.end(End::Truncated));
+                continue;
+            }
+            for next in self.step(func, s) {
+                match next {
+                    Next::Running(s) => worklist.push(s),
+                    Next::Done(path) => paths.push(path),
+                }
+            }
+        }
+        paths
+    }
+
+    // execute one instruction or terminal.
+    fn step(&mut self, func: &Function, s: PathState) -> Vec<Next> {
+        let bb = &func.body[&s.bb];
+        let result = if s.idx < bb.insts.len() {
+            let inst = &bb.insts[s.idx];
+            self.execute_inst(inst, s).map(|states| {
+                states
+                    .into_iter()
+                    .map(|next| match next {
+                        Next::Running(mut s) => {
+                            s.idx += 1;
+                            Next::Running(s)
+                        }
+                        done => done,
+                    })
+                    .collect()
+            })
+        } else {
+            self.execute_terminal(&bb.term, s)
+        };
+        result.unwrap_or_else(|path| vec![Next::Done(*path)])
+    }
+
+    fn execute_inst(&mut self, inst: &Instruction, s: PathState) -> Result<Vec<Next>, Stuck> {
+        use Instruction::*;
+
+        match inst {
+            AddrOf { .. } => Err(stuck(s, "$addrof is not supported")),
+            Phi { .. } => Err(stuck(s, "$phi is not supported")),
+            Free { .. } => Err(stuck(s, "$free is not supported")),
+            Alloc { lhs, num, id: _ } => {
+                let n = match self.eval(&s, num) {
+                    SymVal::Int(n) => n,
+                    v => return Err(stuck(s, format!("non-integer size {v:?}"))),
+                };
+                let negative = Constraint::lt(&n, &Lin::constant(0)).unwrap();
+                Ok(self
+                    .split(s, negative)
+                    .into_iter()
+                    .map(|(mut s, negative)| {
+                        if negative {
+                            return Next::Done(s.end(End::Error(
+                                "cannot allocate a negative number of elements".into(),
+                            )));
+                        }
+                        s.allocs.push(Allocation {
+                            size: n.clone(),
+                            epoch: s.epoch,
+                        });
+                        let addr = Ptr {
+                            base: Base::Alloc(s.allocs.len() - 1),
+                            offset: Lin::constant(0),
+                            fields: vec![],
+                        };
+                        bind(&mut s, lhs, SymVal::Ptr(addr));
+                        Next::Running(s)
+                    })
+                    .collect())
+            }
+            Arith { lhs, aop, op1, op2 } => {
+                let (SymVal::Int(n1), SymVal::Int(n2)) = (self.eval(&s, op1), self.eval(&s, op2))
+                else {
+                    return Err(stuck(s, "arithmetic on non-integers"));
+                };
+                self.arith(s, lhs, *aop, n1, n2)
+            }
+            Cmp { lhs, rop, op1, op2 } => {
+                let (v1, v2) = (self.eval(&s, op1), self.eval(&s, op2));
+                self.execute_cmp(s, lhs, *rop, v1, v2)
+            }
+            Copy { lhs, op } => {
+                let mut s = s;
+                let v = self.eval(&s, op);
+                bind(&mut s, lhs, v);
+                Ok(vec![Next::Running(s)])
+            }
+            Gep { lhs, src, idx } => {
+                let (SymVal::Ptr(ptr), SymVal::Int(i)) = (lookup(&s, src), self.eval(&s, idx))
+                else {
+                    return Err(stuck(s, "$gep on a non-pointer"));
+                };
+                if !ptr.fields.is_empty() {
+                    return Ok(vec![Next::Done(
+                        s.end(End::Error("src in $gep must be a heap pointer".into())),
+                    )]);
+                }
+                let Some(offset) = ptr.offset.add(&i) else {
+                    return Err(stuck(s, "overflow"));
+                };
+                let mut next = vec![];
+                let checks = if ptr.base == Base::Heap {
+                    self.split(s, Constraint::eq(&ptr.offset, &Lin::constant(0)).unwrap())
+                } else {
+                    vec![(s, false)]
+                };
+                for (mut s, is_nil) in checks {
+                    if is_nil {
+                        next.push(Next::Done(
+                            s.end(End::Error("src in $gep must be a heap pointer".into())),
+                        ));
+                    } else {
+                        let ptr = Ptr {
+                            offset: offset.clone(),
+                            ..ptr.clone()
+                        };
+                        bind(&mut s, lhs, SymVal::Ptr(ptr));
+                        next.push(Next::Running(s));
+                    }
+                }
+                Ok(next)
+            }
+            Gfp { lhs, src, field } => {
+                let SymVal::Ptr(mut ptr) = lookup(&s, src) else {
+                    return Err(stuck(s, "$gfp on a non-pointer"));
+                };
+                let mut s = s;
+                ptr.fields.push(field.clone());
+                bind(&mut s, lhs, SymVal::Ptr(ptr));
+                Ok(vec![Next::Running(s)])
+            }
+            Load { lhs, src } => {
+                let SymVal::Ptr(ptr) = lookup(&s, src) else {
+                    return Err(stuck(s, "$load from a non-pointer"));
+                };
+                let typ = lhs.typ();
+                let mut next = vec![];
+                for access in self.access(s, &ptr)? {
+                    match access {
+                        Next::Running(s) => {
+                            for (mut s, v) in self.read(s, &ptr, &typ, 0)? {
+                                bind(&mut s, lhs, v);
+                                next.push(Next::Running(s));
+                            }
+                        }
+                        done => next.push(done),
+                    }
+                }
+                Ok(next)
+            }
+            Store { dst, op } => {
+                let SymVal::Ptr(ptr) = lookup(&s, dst) else {
+                    return Err(stuck(s, "$store to a non-pointer"));
+                };
+                let typ = dst.typ().get_deref_type().unwrap().clone();
+                let v = coerce(self.eval(&s, op), &typ);
+                let mut next = vec![];
+                for access in self.access(s, &ptr)? {
+                    match access {
+                        Next::Running(s) => {
+                            for s in self.write(s, &ptr, &typ, &v, 0)? {
+                                next.push(Next::Running(s));
+                            }
+                        }
+                        done => next.push(done),
+                    }
+                }
+                Ok(next)
+            }
+            CallExt {
+                lhs,
+                ext_callee,
+                args,
+            } => {
+                let args = args.iter().map(|a| self.eval(&s, a)).collect();
+                let callee = Callee::External(ext_callee.clone());
+                Ok(vec![Next::Running(self.call(s, callee, args, lhs))])
+            }
+        }
+    }
+
+    fn execute_terminal(&mut self, term: &Terminal, s: PathState) -> Result<Vec<Next>, Stuck> {
+        match term {
+            Terminal::Jump(bb) => Ok(vec![Next::Running(goto(s, bb))]),
+            Terminal::Branch { cond, tt, ff } => {
+                let SymVal::Int(n) = self.eval(&s, cond) else {
+                    return Ok(vec![Next::Done(
+                        s.end(End::Error("argument of $branch is not an int".into())),
+                    )]);
+                };
+                let nonzero = Constraint::ne(&n, &Lin::constant(0)).unwrap();
+                Ok(self
+                    .split(s, nonzero)
+                    .into_iter()
+                    .map(|(s, holds)| Next::Running(goto(s, if holds { tt } else { ff })))
+                    .collect())
+            }
+            Terminal::CallDirect {
+                lhs,
+                callee,
+                args,
+                next_bb,
+            } => {
+                let args = args.iter().map(|a| self.eval(&s, a)).collect();
+                let callee = Callee::Internal(FnVal::Func(callee.clone()));
+                Ok(vec![Next::Running(self.call(
+                    goto(s, next_bb),
+                    callee,
+                    args,
+                    lhs,
+                ))])
+            }
+            Terminal::CallIndirect {
+                lhs,
+                callee,
+                args,
+                next_bb,
+            } => {
+                let SymVal::Fn(f) = lookup(&s, callee) else {
+                    return Ok(vec![Next::Done(
+                        s.end(End::Error("tried to call non-function value".into())),
+                    )]);
+                };
+                let args = args.iter().map(|a| self.eval(&s, a)).collect();
+                let callee = Callee::Internal(f);
+                Ok(vec![Next::Running(self.call(
+                    goto(s, next_bb),
+                    callee,
+                    args,
+                    lhs,
+                ))])
+            }
+            Terminal::Ret(op) => {
+                let value = op.as_ref().map(|op| self.eval(&s, op));
+                let state = s.snapshot();
+                Ok(vec![Next::Done(s.end(End::Return { value, state }))])
+            }
+        }
+    }
+
+    // record a call, then forget everything the callee might change.
+    fn call(
+        &mut self,
+        mut s: PathState,
+        callee: Callee,
+        args: Vec<SymVal>,
+        lhs: &Option<VarId>,
+    ) -> PathState {
+        s.events.push(Event {
+            callee,
+            args,
+            state: s.snapshot(),
+        });
+        s.epoch += 1;
+        s.cells.clear();
+        let epoch = s.epoch;
+        s.globals = s
+            .globals
+            .keys()
+            .map(|g| {
+                (
+                    g.clone(),
+                    self.unknown(AtomKey::Global(epoch, g.clone()), &g.typ()),
+                )
+            })
+            .collect();
+        if let Some(lhs) = lhs {
+            let v = self.unknown(AtomKey::CallResult(epoch), &lhs.typ());
+            bind(&mut s, lhs, v);
+        }
+        s
+    }
+
+    fn eval(&self, s: &PathState, op: &Operand) -> SymVal {
+        match op {
+            Operand::CInt(n) => SymVal::Int(Lin::constant(*n as i64)),
+            Operand::Var(x) => lookup(s, x),
+        }
+    }
+
+    fn arith(
+        &mut self,
+        s: PathState,
+        lhs: &VarId,
+        aop: ArithmeticOp,
+        n1: Lin,
+        n2: Lin,
+    ) -> Result<Vec<Next>, Stuck> {
+        let mut s = s;
+        let linear = match aop {
+            ArithmeticOp::Add => n1.add(&n2),
+            ArithmeticOp::Subtract => n1.sub(&n2),
+            ArithmeticOp::Multiply => match (n1.as_constant(), n2.as_constant()) {
+                (Some(k), _) => n2.scale(k),
+                (_, Some(k)) => n1.scale(k),
+                _ => None,
+            },
+            ArithmeticOp::Divide => None,
+        };
+        if let Some(n) = linear {
+            bind(&mut s, lhs, SymVal::Int(n));
+            return Ok(vec![Next::Running(s)]);
+        }
+        if aop != ArithmeticOp::Divide {
+            let v = self.opaque(OpaqueOp::Mul, vec![SymVal::Int(n1), SymVal::Int(n2)], lhs);
+            bind(&mut s, lhs, v);
+            return Ok(vec![Next::Running(s)]);
+        }
+
+        let mut next = vec![];
+        let zero = Constraint::eq(&n2, &Lin::constant(0)).unwrap();
+        for (mut s, is_zero) in self.split(s, zero) {
+            if is_zero {
+                next.push(Next::Done(s.end(End::Error("division by zero".into()))));
+                continue;
+            }
+            match (n1.as_constant(), n2.as_constant()) {
+                (Some(a), Some(b)) => {
+                    bind(&mut s, lhs, SymVal::Int(Lin::constant(a / b)));
+                    next.push(Next::Running(s));
+                }
+                (_, Some(k)) if k.checked_abs().is_some() => {
+                    // q = n1 / k rounds towards zero, so |k|*|q| <= |n1| < |k|*|q| + |k|
+                    // with q having the sign of n1 * k.
+                    let v = self.opaque(
+                        OpaqueOp::Div,
+                        vec![SymVal::Int(n1.clone()), SymVal::Int(n2.clone())],
+                        lhs,
+                    );
+                    let SymVal::Int(q) = v.clone() else {
+                        unreachable!()
+                    };
+                    let nonneg = Constraint::le(&Lin::constant(0), &n1).unwrap();
+                    for (mut s, holds) in self.split(s, nonneg) {
+                        // kq = k*q is between n1 - (|k| - 1) and n1 on the side of zero.
+                        let Some(kq) = q.scale(k) else {
+                            return Err(stuck(s, "overflow"));
+                        };
+                        let slack = k.abs() - 1;
+                        let bounds = if holds {
+                            [
+                                Constraint::le(&kq, &n1),
+                                n1.offset(-slack).and_then(|lo| Constraint::le(&lo, &kq)),
+                            ]
+                        } else {
+                            [
+                                Constraint::le(&n1, &kq),
+                                n1.offset(slack).and_then(|hi| Constraint::le(&kq, &hi)),
+                            ]
+                        };
+                        let Some(bounds) = bounds.into_iter().collect::<Option<Vec<_>>>() else {
+                            return Err(stuck(s, "overflow"));
+                        };
+                        s.pc.extend(bounds);
+                        bind(&mut s, lhs, v.clone());
+                        if self.feasible(&s.pc) {
+                            next.push(Next::Running(s));
+                        }
+                    }
+                }
+                _ => {
+                    let v = self.opaque(
+                        OpaqueOp::Div,
+                        vec![SymVal::Int(n1.clone()), SymVal::Int(n2.clone())],
+                        lhs,
+                    );
+                    bind(&mut s, lhs, v);
+                    next.push(Next::Running(s));
+                }
+            }
+        }
+        Ok(next)
+    }
+
+    fn opaque(&mut self, op: OpaqueOp, args: Vec<SymVal>, lhs: &VarId) -> SymVal {
+        self.unknown(AtomKey::Op(op, args), &lhs.typ())
+    }
+
+    fn execute_cmp(
+        &mut self,
+        s: PathState,
+        lhs: &VarId,
+        rop: ComparisonOp,
+        v1: SymVal,
+        v2: SymVal,
+    ) -> Result<Vec<Next>, Stuck> {
+        use ComparisonOp::*;
+
+        let result = |s: PathState, b: bool| {
+            let mut s = s;
+            bind(&mut s, lhs, SymVal::Int(Lin::constant(b as i64)));
+            Next::Running(s)
+        };
+        let linear = |n1: &Lin, n2: &Lin| match rop {
+            Eq => Constraint::eq(n1, n2),
+            Neq => Constraint::ne(n1, n2),
+            Less => Constraint::lt(n1, n2),
+            LessEq => Constraint::le(n1, n2),
+            Greater => Constraint::lt(n2, n1),
+            GreaterEq => Constraint::le(n2, n1),
+        };
+        let is_eq = matches!(rop, Eq | LessEq | GreaterEq);
+
+        let constraint = match (&v1, &v2) {
+            (SymVal::Int(n1), SymVal::Int(n2)) => linear(n1, n2),
+            // 0 compared with a pointer is nil.
+            (SymVal::Ptr(p), SymVal::Int(n)) | (SymVal::Int(n), SymVal::Ptr(p))
+                if n.as_constant() == Some(0) =>
+            {
+                let nil = Ptr::nil();
+                let (p1, p2) = if matches!(v1, SymVal::Ptr(_)) {
+                    (p, &nil)
+                } else {
+                    (&nil, p)
+                };
+                return self.execute_cmp(
+                    s,
+                    lhs,
+                    rop,
+                    SymVal::Ptr(p1.clone()),
+                    SymVal::Ptr(p2.clone()),
+                );
+            }
+            (SymVal::Ptr(p1), SymVal::Ptr(p2)) if p1.base == p2.base && p1.fields == p2.fields => {
+                linear(&p1.offset, &p2.offset)
+            }
+            // pointers into different objects, or to different fields, are
+            // never equal.
+            (SymVal::Ptr(_), SymVal::Ptr(_)) if matches!(rop, Eq | Neq) => {
+                return Ok(vec![result(s, rop == Neq)]);
+            }
+            (SymVal::Fn(FnVal::Func(f1)), SymVal::Fn(FnVal::Func(f2))) => {
+                let ord = f1.cmp(f2);
+                return Ok(vec![result(
+                    s,
+                    match rop {
+                        Eq => ord.is_eq(),
+                        Neq => ord.is_ne(),
+                        Less => ord.is_lt(),
+                        LessEq => ord.is_le(),
+                        Greater => ord.is_gt(),
+                        GreaterEq => ord.is_ge(),
+                    },
+                )]);
+            }
+            (SymVal::Fn(f1), SymVal::Fn(f2)) if f1 == f2 => {
+                return Ok(vec![result(s, is_eq)]);
+            }
+            (SymVal::Struct(_), _) | (_, SymVal::Struct(_)) => {
+                return Err(stuck(s, "comparison of structs"));
+            }
+            _ => {
+                let op = OpaqueOp::Cmp(rop as u8);
+                let SymVal::Int(b) = self.opaque(op, vec![v1.clone(), v2.clone()], lhs) else {
+                    unreachable!()
+                };
+                Constraint::ne(&b, &Lin::constant(0))
+            }
+        };
+        let Some(constraint) = constraint else {
+            return Err(stuck(s, "overflow"));
+        };
+        Ok(self
+            .split(s, constraint)
+            .into_iter()
+            .map(|(s, holds)| result(s, holds))
+            .collect())
+    }
+
+    // the runtime checks for dereferencing ptr: the running states are those
+    // in which the access is fine.
+    fn access(&mut self, s: PathState, ptr: &Ptr) -> Result<Vec<Next>, Stuck> {
+        let zero = Lin::constant(0);
+        match ptr.base {
+            Base::Heap => Ok(self
+                .split(s, Constraint::eq(&ptr.offset, &zero).unwrap())
+                .into_iter()
+                .map(|(s, is_nil)| {
+                    if is_nil {
+                        Next::Done(s.end(End::Error("tried to dereference a null pointer".into())))
+                    } else {
+                        Next::Running(s)
+                    }
+                })
+                .collect()),
+            Base::Alloc(k) => {
+                let size = s.allocs[k].size.clone();
+                let below = Constraint::lt(&ptr.offset, &zero).unwrap();
+                let Some(above) = Constraint::le(&size, &ptr.offset) else {
+                    return Err(stuck(s, "overflow"));
+                };
+                let mut result = vec![];
+                for (s, out) in self.split(s, below) {
+                    if out {
+                        result.push(Next::Done(s.end(End::Error("out-of-bounds access".into()))));
+                        continue;
+                    }
+                    for (s, out) in self.split(s, above.clone()) {
+                        result.push(if out {
+                            Next::Done(s.end(End::Error("out-of-bounds access".into())))
+                        } else {
+                            Next::Running(s)
+                        });
+                    }
+                }
+                Ok(result)
+            }
+        }
+    }
+
+    // how cells a and b (holding values of types ta and tb) relate in s:
+    // Some(true) if they are the same cell, Some(false) if they are different
+    // cells, None if that depends on the values of the unknowns, in which case
+    // the constraint makes them equal. LIR is type safe, so cells of different
+    // types never alias unless one is a struct containing the other.
+    fn alias(
+        &self,
+        pc: &[Constraint],
+        (a, ta): (&Ptr, &Type),
+        (b, tb): (&Ptr, &Type),
+    ) -> Result<Result<bool, Constraint>, String> {
+        if a.base != b.base {
+            return Ok(Ok(false));
+        }
+        let same = Constraint::eq(&a.offset, &b.offset).ok_or("overflow")?;
+        if a.fields != b.fields {
+            let contains = |outer: &Ptr, inner: &Ptr, typ: &Type| {
+                typ.is_struct() && inner.fields.starts_with(&outer.fields)
+            };
+            if !contains(a, b, ta) && !contains(b, a, tb) {
+                return Ok(Ok(false));
+            }
+            return match self.decide(pc, &same) {
+                Some(false) => Ok(Ok(false)),
+                _ => Err("accesses to a struct and to its fields".into()),
+            };
+        }
+        if ta != tb {
+            return Ok(Ok(false));
+        }
+        Ok(match self.decide(pc, &same) {
+            Some(b) => Ok(b),
+            None => Err(same),
+        })
+    }
+
+    // the value stored at ptr, for each way the aliasing can turn out. the
+    // cells of a path are pairwise distinct, so at most one of them is ptr.
+    fn read(
+        &mut self,
+        s: PathState,
+        ptr: &Ptr,
+        typ: &Type,
+        start: usize,
+    ) -> Result<Vec<(PathState, SymVal)>, Stuck> {
+        let mut s = s;
+        for i in start..s.cells.len() {
+            match self.alias(&s.pc, (ptr, typ), (&s.cells[i].addr, &s.cells[i].typ)) {
+                Err(why) => return Err(stuck(s, why)),
+                Ok(Ok(true)) => {
+                    let v = coerce(s.cells[i].val.clone(), typ);
+                    return Ok(vec![(s, v)]);
+                }
+                Ok(Ok(false)) => {}
+                Ok(Err(same)) => {
+                    let mut equal = s.clone();
+                    equal.pc.push(same.clone());
+                    let v = coerce(s.cells[i].val.clone(), typ);
+                    s.pc.push(same.negate().unwrap());
+                    let mut result = self.read(s, ptr, typ, i + 1)?;
+                    result.push((equal, v));
+                    return Ok(result);
+                }
+            }
+        }
+
+        // nothing in this epoch says what's in the cell.
+        let v = self.initial(&s.allocs, s.epoch, ptr, typ);
+        s.cells.push(Cell {
+            addr: ptr.clone(),
+            typ: typ.clone(),
+            val: v.clone(),
+            written: false,
+        });
+        Ok(vec![(s, v)])
+    }
+
+    // the contents of a cell at the start of an epoch.
+    fn initial(&mut self, allocs: &[Allocation], epoch: usize, ptr: &Ptr, typ: &Type) -> SymVal {
+        match ptr.base {
+            // objects allocated in this epoch are still zero-initialized.
+            Base::Alloc(k) if allocs[k].epoch == epoch => self.zero(typ),
+            _ => self.unknown(AtomKey::Memory(epoch, ptr.clone(), typ.clone()), typ),
+        }
+    }
+
+    fn write(
+        &mut self,
+        s: PathState,
+        ptr: &Ptr,
+        typ: &Type,
+        v: &SymVal,
+        start: usize,
+    ) -> Result<Vec<PathState>, Stuck> {
+        let mut s = s;
+        for i in start..s.cells.len() {
+            match self.alias(&s.pc, (ptr, typ), (&s.cells[i].addr, &s.cells[i].typ)) {
+                Err(why) => return Err(stuck(s, why)),
+                Ok(Ok(true)) => {
+                    s.cells[i].val = v.clone();
+                    s.cells[i].written = true;
+                    return Ok(vec![s]);
+                }
+                Ok(Ok(false)) => {}
+                Ok(Err(same)) => {
+                    let mut equal = s.clone();
+                    equal.pc.push(same.clone());
+                    equal.cells[i].val = v.clone();
+                    equal.cells[i].written = true;
+                    s.pc.push(same.negate().unwrap());
+                    let mut result = self.write(s, ptr, typ, v, i + 1)?;
+                    result.push(equal);
+                    return Ok(result);
+                }
+            }
+        }
+
+        s.cells.push(Cell {
+            addr: ptr.clone(),
+            typ: typ.clone(),
+            val: v.clone(),
+            written: true,
+        });
+        Ok(vec![s])
+    }
+
+    // the states in which c holds (true) and doesn't (false), omitting the
+    // infeasible ones.
+    fn split(&self, s: PathState, c: Constraint) -> Vec<(PathState, bool)> {
+        match self.decide(&s.pc, &c) {
+            Some(b) => vec![(s, b)],
+            None => {
+                let mut s_true = s.clone();
+                s_true.pc.push(c.clone());
+                let mut s_false = s;
+                s_false.pc.push(c.negate().unwrap());
+                vec![(s_true, true), (s_false, false)]
+            }
+        }
+    }
+
+    // whether c follows from (or contradicts) pc.
+    fn decide(&self, pc: &[Constraint], c: &Constraint) -> Option<bool> {
+        if let Some(b) = c.decided() {
+            return Some(b);
+        }
+        let with = |c: Constraint| {
+            let mut cs = pc.to_vec();
+            cs.push(c);
+            cs
+        };
+        if solve(&with(c.negate()?)) == Solution::Unsat {
+            Some(true)
+        } else if solve(&with(c.clone())) == Solution::Unsat {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    fn feasible(&self, pc: &[Constraint]) -> bool {
+        solve(pc) != Solution::Unsat
+    }
+}
+
+fn lookup(s: &PathState, x: &VarId) -> SymVal {
+    s.env
+        .get(x)
+        .or_else(|| s.globals.get(x))
+        .cloned()
+        .unwrap_or_else(|| panic!("undefined variable {x}"))
+}
+
+fn bind(s: &mut PathState, x: &VarId, v: SymVal) {
+    let v = coerce(v, &x.typ());
+    if let Some(existing) = s.env.get_mut(x).or(s.globals.get_mut(x)) {
+        *existing = v;
+    } else {
+        panic!("undefined variable {x}");
+    }
+}
+
+fn goto(mut s: PathState, bb: &BbId) -> PathState {
+    s.bb = bb.clone();
+    s.idx = 0;
+    s.blocks += 1;
+    s
+}
+
+// the constant 0 stands for nil in pointer contexts.
+fn coerce(v: SymVal, typ: &Type) -> SymVal {
+    match v {
+        SymVal::Int(n) if typ.is_ptr() => SymVal::Ptr(Ptr {
+            base: Base::Heap,
+            offset: n,
+            fields: vec![],
+        }),
+        v => v,
+    }
+}
+
+// SECTION: comparing paths
+
+// something that must hold for the paths to agree.
+enum Obligation {
+    Equal(Lin, Lin, String),
+    Differ(String),
+    Unknown(String),
+}
+
+impl Checker {
+    fn compare_paths(&mut self, left: &[Path], right: &[Path]) -> Verdict {
+        let mut paths = 0;
+        let mut complete = true;
+        let mut unknown = None;
+        for p in left {
+            for q in right {
+                let pc = [p.pc.clone(), q.pc.clone()].concat();
+                if solve(&pc) == Solution::Unsat {
+                    continue;
+                }
+                match (&p.end, &q.end) {
+                    (End::Truncated, _) | (_, End::Truncated) => complete = false,
+                    (End::Error(_), _) => paths += 1,
+                    (End::Unsupported(why), _) | (_, End::Unsupported(why)) => {
+                        unknown.get_or_insert_with(|| why.clone());
+                    }
+                    (End::Return { .. }, End::Error(err)) => {
+                        let obligation =
+                            Obligation::Differ(format!("the transformed function fails ({err})"));
+                        match self.discharge(&pc, obligation) {
+                            Ok(()) => paths += 1,
+                            Err(Verdict::Unknown(why)) => {
+                                unknown.get_or_insert(why);
+                            }
+                            Err(verdict) => return verdict,
+                        }
+                    }
+                    (
+                        End::Return {
+                            value: v1,
+                            state: s1,
+                        },
+                        End::Return {
+                            value: v2,
+                            state: s2,
+                        },
+                    ) => {
+                        let mut obligations = vec![];
+                        if p.events.len() != q.events.len() {
+                            obligations.push(Obligation::Differ(format!(
+                                "the functions make {} and {} calls",
+                                p.events.len(),
+                                q.events.len()
+                            )));
+                        }
+                        for (i, (e1, e2)) in p.events.iter().zip(&q.events).enumerate() {
+                            let what = format!("call #{}", i + 1);
+                            match (&e1.callee, &e2.callee) {
+                                (Callee::Internal(f1), Callee::Internal(f2)) => self.compare_fns(
+                                    f1,
+                                    f2,
+                                    &format!("the callee of {what}"),
+                                    &mut obligations,
+                                ),
+                                (Callee::External(f1), Callee::External(f2)) if f1 == f2 => {}
+                                _ => obligations.push(Obligation::Differ(format!(
+                                    "{what} has a different callee"
+                                ))),
+                            }
+                            if e1.args.len() != e2.args.len() {
+                                obligations.push(Obligation::Differ(format!(
+                                    "{what} has a different number of arguments"
+                                )));
+                            }
+                            for (j, (a1, a2)) in e1.args.iter().zip(&e2.args).enumerate() {
+                                self.compare_vals(
+                                    a1,
+                                    a2,
+                                    &format!("argument {} of {what}", j + 1),
+                                    &mut obligations,
+                                );
+                            }
+                            self.compare_states(
+                                &pc,
+                                &e1.state,
+                                &e2.state,
+                                &format!("before {what}"),
+                                &mut obligations,
+                            );
+                        }
+                        match (v1, v2) {
+                            (Some(v1), Some(v2)) => {
+                                self.compare_vals(v1, v2, "the return value", &mut obligations)
+                            }
+                            (None, None) => {}
+                            _ => obligations.push(Obligation::Differ(
+                                "only one function returns a value".into(),
+                            )),
+                        }
+                        self.compare_states(&pc, s1, s2, "at the return", &mut obligations);
+
+                        for obligation in obligations {
+                            match self.discharge(&pc, obligation) {
+                                Ok(()) => {}
+                                Err(Verdict::Unknown(why)) => {
+                                    unknown.get_or_insert(why);
+                                }
+                                Err(verdict) => return verdict,
+                            }
+                        }
+                        paths += 1;
+                    }
+                }
+            }
+        }
+        match unknown {
+            Some(why) => Verdict::Unknown(why),
+            None => Verdict::Refines { paths, complete },
+        }
+    }
+
+    fn compare_fns(&self, f1: &FnVal, f2: &FnVal, what: &str, obligations: &mut Vec<Obligation>) {
+        match (f1, f2) {
+            (FnVal::Func(f1), FnVal::Func(f2)) if f1 == f2 => {}
+            (FnVal::Func(_), FnVal::Func(_)) => {
+                obligations.push(Obligation::Differ(format!("{what} differs")))
+            }
+            (FnVal::Unknown(a1), FnVal::Unknown(a2)) => obligations.push(Obligation::Equal(
+                Lin::atom(*a1),
+                Lin::atom(*a2),
+                what.into(),
+            )),
+            _ => obligations.push(Obligation::Unknown(format!("can't compare {what}"))),
+        }
+    }
+
+    fn compare_vals(
+        &self,
+        v1: &SymVal,
+        v2: &SymVal,
+        what: &str,
+        obligations: &mut Vec<Obligation>,
+    ) {
+        match (v1, v2) {
+            (SymVal::Int(n1), SymVal::Int(n2)) => {
+                obligations.push(Obligation::Equal(n1.clone(), n2.clone(), what.into()))
+            }
+            (SymVal::Ptr(p1), SymVal::Ptr(p2)) if p1.base == p2.base && p1.fields == p2.fields => {
+                obligations.push(Obligation::Equal(
+                    p1.offset.clone(),
+                    p2.offset.clone(),
+                    what.into(),
+                ))
+            }
+            (SymVal::Ptr(_), SymVal::Ptr(_)) => obligations.push(Obligation::Differ(format!(
+                "{what} points to different objects"
+            ))),
+            // an integer is only ever used as a pointer if it's 0, i.e., nil.
+            (SymVal::Int(n), SymVal::Ptr(p)) | (SymVal::Ptr(p), SymVal::Int(n))
+                if p.base == Base::Heap && p.fields.is_empty() =>
+            {
+                obligations.push(Obligation::Equal(n.clone(), p.offset.clone(), what.into()))
+            }
+            (SymVal::Fn(f1), SymVal::Fn(f2)) => self.compare_fns(f1, f2, what, obligations),
+            (SymVal::Struct(a1), SymVal::Struct(a2)) if a1 == a2 => {}
+            _ => obligations.push(Obligation::Unknown(format!("can't compare {what}"))),
+        }
+    }
+
+    // the globals and the memory written in an epoch must agree.
+    fn compare_states(
+        &mut self,
+        pc: &[Constraint],
+        s1: &Snapshot,
+        s2: &Snapshot,
+        when: &str,
+        obligations: &mut Vec<Obligation>,
+    ) {
+        for (g, v1) in &s1.globals {
+            match s2.globals.get(g) {
+                Some(v2) => {
+                    self.compare_vals(v1, v2, &format!("the value of {g} {when}"), obligations)
+                }
+                None => obligations.push(Obligation::Differ(format!("{g} was removed"))),
+            }
+        }
+
+        // every cell written by one function must hold the same value in the
+        // other, whether or not the other wrote to it.
+        for (left, (written, other)) in [(true, (s1, s2)), (false, (s2, s1))] {
+            for cell in &written.writes {
+                let what = format!("the memory written {when}");
+                let mut theirs = None;
+                for c in &other.cells {
+                    match self.alias(pc, (&cell.addr, &cell.typ), (&c.addr, &c.typ)) {
+                        Ok(Ok(true)) => {
+                            theirs = Some(c.val.clone());
+                            break;
+                        }
+                        Ok(Ok(false)) => {}
+                        _ => {
+                            obligations.push(Obligation::Unknown(format!("can't relate {what}")));
+                            theirs = Some(cell.val.clone());
+                            break;
+                        }
+                    }
+                }
+                let theirs = theirs.unwrap_or_else(|| {
+                    coerce(
+                        self.initial(&other.allocs, other.epoch, &cell.addr, &cell.typ),
+                        &cell.typ,
+                    )
+                });
+                if left {
+                    self.compare_vals(&cell.val, &theirs, &what, obligations);
+                } else {
+                    self.compare_vals(&theirs, &cell.val, &what, obligations);
+                }
+            }
+        }
+    }
+
+    fn discharge(&mut self, pc: &[Constraint], obligation: Obligation) -> Result<(), Verdict> {
+        let (query, reason) = match obligation {
+            Obligation::Equal(n1, n2, what) => {
+                let Some(c) = Constraint::ne(&n1, &n2) else {
+                    return Err(Verdict::Unknown("overflow".into()));
+                };
+                if c.decided() == Some(false) {
+                    return Ok(());
+                }
+                (Some((c, n1, n2)), what)
+            }
+            Obligation::Differ(what) => (None, what),
+            Obligation::Unknown(why) => return Err(Verdict::Unknown(why)),
+        };
+
+        let mut cs = pc.to_vec();
+        cs.extend(query.iter().map(|(c, _, _)| c.clone()));
+        match solve(&cs) {
+            Solution::Unsat => Ok(()),
+            Solution::Unknown => Err(Verdict::Unknown(format!("can't decide {reason}"))),
+            Solution::Sat(model) => {
+                if let Some(why) = self.spurious(&model) {
+                    return Err(Verdict::Unknown(format!("can't decide {reason} ({why})")));
+                }
+                let reason = match query {
+                    Some((_, n1, n2)) => format!(
+                        "{reason} differs: {} in the original, {} in the transformed function",
+                        n1.eval(&model).unwrap(),
+                        n2.eval(&model).unwrap()
+                    ),
+                    None => reason,
+                };
+                Err(Verdict::Mismatch(Counterexample {
+                    reason,
+                    inputs: self.inputs(&model),
+                }))
+            }
+        }
+    }
+
+    // why a model doesn't correspond to an actual execution, if it doesn't.
+    fn spurious(&self, model: &Model) -> Option<String> {
+        let value = |a: &Atom| *model.get(a).unwrap_or(&0);
+        let int = |v: &SymVal| match v {
+            SymVal::Int(n) => n.eval(model),
+            _ => None,
+        };
+        // unknowns missing from the model are unconstrained, so they can take
+        // whatever value they stand for.
+        for (a, key) in self
+            .atoms
+            .iter()
+            .enumerate()
+            .filter(|(a, _)| model.contains_key(a))
+        {
+            match key {
+                AtomKey::Op(OpaqueOp::Mul, args) => {
+                    let expected = int(&args[0])
+                        .zip(int(&args[1]))
+                        .and_then(|(x, y)| x.checked_mul(y));
+                    if expected != Some(value(&a)) {
+                        return Some("nonlinear arithmetic".into());
+                    }
+                }
+                AtomKey::Op(OpaqueOp::Div, args) => {
+                    let expected = int(&args[0])
+                        .zip(int(&args[1]))
+                        .and_then(|(x, y)| x.checked_div(y));
+                    if expected.is_some_and(|q| q != value(&a)) {
+                        return Some("nonlinear arithmetic".into());
+                    }
+                }
+                AtomKey::Op(OpaqueOp::Cmp(_), _) => return Some("pointer comparison".into()),
+                AtomKey::Memory(e1, p1, _) => {
+                    // cells at the same address must hold the same value.
+                    for (b, other) in self.atoms.iter().enumerate().skip(a + 1) {
+                        if let (AtomKey::Memory(e2, p2, _), true) = (other, model.contains_key(&b))
+                        {
+                            let same = e1 == e2
+                                && p1.base == p2.base
+                                && p1.fields == p2.fields
+                                && p1.offset.eval(model) == p2.offset.eval(model);
+                            if same && value(&a) != value(&b) {
+                                return Some("aliasing".into());
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    // the parameters, initial globals and initial memory in a model.
+    fn inputs(&self, model: &Model) -> Vec<(String, i64)> {
+        model
+            .iter()
+            .filter_map(|(a, v)| {
+                let name = match &self.atoms[*a] {
+                    AtomKey::Param(i) => self.params[*i].clone(),
+                    AtomKey::Global(0, g) => g.name().to_string(),
+                    AtomKey::Memory(0, ptr, _) if ptr.base == Base::Heap => {
+                        let addr = ptr.offset.eval(model)?;
+                        let fields = ptr.fields.iter().map(|f| format!(".{}", f.name));
+                        format!("*{addr}{}", fields.collect::<String>())
+                    }
+                    _ => return None,
+                };
+                Some((name, *v))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,565 @@
+// a small decision procedure for conjunctions of linear integer constraints.
+//
+// equalities with a unit coefficient are solved and substituted away,
+// disequalities are split into two strict inequalities on demand, and the
+// remaining inequalities are decided by fourier-motzkin elimination with
+// integer tightening. the procedure is sound for unsatisfiability (the rational
+// relaxation is unsatisfiable), and a satisfiable answer always comes with an
+// integer model that has been checked against the input. when neither can be
+// established (e.g., the integer model search fails or the problem grows too
+// large) the answer is `Unknown`.
+
+use super::*;
+
+// unknowns are numbered by the caller.
+pub type Atom = usize;
+
+// a linear expression: sum of coeffs[x] * x, plus constant.
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Lin {
+    pub coeffs: Map<Atom, i64>,
+    pub constant: i64,
+}
+
+impl Lin {
+    pub fn constant(n: i64) -> Self {
+        Lin {
+            coeffs: Map::new(),
+            constant: n,
+        }
+    }
+
+    pub fn atom(x: Atom) -> Self {
+        Lin {
+            coeffs: Map::from([(x, 1)]),
+            constant: 0,
+        }
+    }
+
+    pub fn as_constant(&self) -> Option<i64> {
+        self.coeffs.is_empty().then_some(self.constant)
+    }
+
+    // returns None on overflow.
+    pub fn add(&self, other: &Lin) -> Option<Lin> {
+        let mut coeffs = self.coeffs.clone();
+        for (x, a) in &other.coeffs {
+            let c = coeffs.entry(*x).or_insert(0);
+            *c = c.checked_add(*a)?;
+            if *c == 0 {
+                coeffs.remove(x);
+            }
+        }
+        Some(Lin {
+            coeffs,
+            constant: self.constant.checked_add(other.constant)?,
+        })
+    }
+
+    pub fn sub(&self, other: &Lin) -> Option<Lin> {
+        self.add(&other.scale(-1)?)
+    }
+
+    pub fn scale(&self, k: i64) -> Option<Lin> {
+        if k == 0 {
+            return Some(Lin::constant(0));
+        }
+        Some(Lin {
+            coeffs: self
+                .coeffs
+                .iter()
+                .map(|(x, a)| a.checked_mul(k).map(|a| (*x, a)))
+                .collect::<Option<_>>()?,
+            constant: self.constant.checked_mul(k)?,
+        })
+    }
+
+    pub fn offset(&self, k: i64) -> Option<Lin> {
+        self.add(&Lin::constant(k))
+    }
+
+    // the value of the expression in a model; atoms missing from the model are
+    // 0. returns None on overflow.
+    pub fn eval(&self, model: &Model) -> Option<i64> {
+        self.coeffs.iter().try_fold(self.constant, |acc, (x, a)| {
+            a.checked_mul(*model.get(x).unwrap_or(&0))
+                .and_then(|v| acc.checked_add(v))
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Rel {
+    // lin == 0
+    Eq,
+    // lin != 0
+    Ne,
+    // lin <= 0
+    Le,
+}
+
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Constraint {
+    pub lin: Lin,
+    pub rel: Rel,
+}
+
+impl Constraint {
+    pub fn eq(l1: &Lin, l2: &Lin) -> Option<Self> {
+        Some(Constraint {
+            lin: l1.sub(l2)?,
+            rel: Rel::Eq,
+        })
+    }
+
+    pub fn ne(l1: &Lin, l2: &Lin) -> Option<Self> {
+        Some(Constraint {
+            lin: l1.sub(l2)?,
+            rel: Rel::Ne,
+        })
+    }
+
+    // l1 <= l2
+    pub fn le(l1: &Lin, l2: &Lin) -> Option<Self> {
+        Some(Constraint {
+            lin: l1.sub(l2)?,
+            rel: Rel::Le,
+        })
+    }
+
+    // l1 < l2
+    pub fn lt(l1: &Lin, l2: &Lin) -> Option<Self> {
+        Some(Constraint {
+            lin: l1.sub(l2)?.offset(1)?,
+            rel: Rel::Le,
+        })
+    }
+
+    pub fn negate(&self) -> Option<Self> {
+        Some(match self.rel {
+            Rel::Eq => Constraint {
+                lin: self.lin.clone(),
+                rel: Rel::Ne,
+            },
+            Rel::Ne => Constraint {
+                lin: self.lin.clone(),
+                rel: Rel::Eq,
+            },
+            // !(lin <= 0) is 1 - lin <= 0.
+            Rel::Le => Constraint {
+                lin: self.lin.scale(-1)?.offset(1)?,
+                rel: Rel::Le,
+            },
+        })
+    }
+
+    pub fn holds(&self, model: &Model) -> Option<bool> {
+        let v = self.lin.eval(model)?;
+        Some(match self.rel {
+            Rel::Eq => v == 0,
+            Rel::Ne => v != 0,
+            Rel::Le => v <= 0,
+        })
+    }
+
+    // the truth value of a constraint without unknowns.
+    pub fn decided(&self) -> Option<bool> {
+        self.lin.as_constant().map(|v| match self.rel {
+            Rel::Eq => v == 0,
+            Rel::Ne => v != 0,
+            Rel::Le => v <= 0,
+        })
+    }
+}
+
+pub type Model = Map<Atom, i64>;
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Solution {
+    Sat(Model),
+    Unsat,
+    Unknown,
+}
+
+// limits that keep the procedure cheap; exceeding them yields `Unknown`.
+const MAX_SPLITS: usize = 64;
+const MAX_INEQUALITIES: usize = 2000;
+
+pub fn solve(constraints: &[Constraint]) -> Solution {
+    let mut splits = 0;
+    let solution = match to_wide(constraints) {
+        Some(constraints) => solve_wide(constraints, &mut splits),
+        None => return Solution::Unknown,
+    };
+    // double-check the model against the original problem.
+    match solution {
+        Solution::Sat(model) if constraints.iter().all(|c| c.holds(&model) == Some(true)) => {
+            Solution::Sat(model)
+        }
+        Solution::Sat(_) => Solution::Unknown,
+        solution => solution,
+    }
+}
+
+// SECTION: the decision procedure
+
+// internally we compute with wider integers so that combining inequalities
+// doesn't overflow as easily.
+type Wide = i128;
+
+#[derive(Clone, Debug)]
+struct WLin {
+    coeffs: Map<Atom, Wide>,
+    constant: Wide,
+}
+
+#[derive(Clone, Debug)]
+struct WConstraint {
+    lin: WLin,
+    rel: Rel,
+}
+
+fn to_wide(constraints: &[Constraint]) -> Option<Vec<WConstraint>> {
+    Some(
+        constraints
+            .iter()
+            .map(|c| WConstraint {
+                lin: WLin {
+                    coeffs: c.lin.coeffs.iter().map(|(x, a)| (*x, *a as Wide)).collect(),
+                    constant: c.lin.constant as Wide,
+                },
+                rel: c.rel,
+            })
+            .collect(),
+    )
+}
+
+impl WLin {
+    fn scale(&self, k: Wide) -> Option<WLin> {
+        Some(WLin {
+            coeffs: self
+                .coeffs
+                .iter()
+                .map(|(x, a)| a.checked_mul(k).map(|a| (*x, a)))
+                .collect::<Option<_>>()?,
+            constant: self.constant.checked_mul(k)?,
+        })
+    }
+
+    fn add(&self, other: &WLin) -> Option<WLin> {
+        let mut coeffs = self.coeffs.clone();
+        for (x, a) in &other.coeffs {
+            let c = coeffs.entry(*x).or_insert(0);
+            *c = c.checked_add(*a)?;
+            if *c == 0 {
+                coeffs.remove(x);
+            }
+        }
+        Some(WLin {
+            coeffs,
+            constant: self.constant.checked_add(other.constant)?,
+        })
+    }
+
+    // replace x with the expression e.
+    fn substitute(&self, x: Atom, e: &WLin) -> Option<WLin> {
+        match self.coeffs.get(&x) {
+            None => Some(self.clone()),
+            Some(a) => {
+                let mut rest = self.clone();
+                rest.coeffs.remove(&x);
+                rest.add(&e.scale(*a)?)
+            }
+        }
+    }
+
+    fn gcd(&self) -> Wide {
+        self.coeffs.values().fold(0, |g, a| gcd(g, a.abs()))
+    }
+
+    fn eval(&self, model: &Map<Atom, Wide>) -> Option<Wide> {
+        self.coeffs.iter().try_fold(self.constant, |acc, (x, a)| {
+            a.checked_mul(*model.get(x).unwrap_or(&0))
+                .and_then(|v| acc.checked_add(v))
+        })
+    }
+}
+
+fn gcd(a: Wide, b: Wide) -> Wide {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+fn floor_div(a: Wide, b: Wide) -> Wide {
+    let q = a / b;
+    if (a % b != 0) && ((a < 0) != (b < 0)) {
+        q - 1
+    } else {
+        q
+    }
+}
+
+fn ceil_div(a: Wide, b: Wide) -> Wide {
+    -floor_div(-a, b)
+}
+
+// the result of a sub-problem before the final model check.
+enum Partial {
+    Sat(Map<Atom, Wide>),
+    Unsat,
+    Unknown,
+}
+
+fn solve_wide(constraints: Vec<WConstraint>, splits: &mut usize) -> Solution {
+    match eliminate_equalities(constraints, splits) {
+        Partial::Sat(model) => model
+            .into_iter()
+            .map(|(x, v)| i64::try_from(v).ok().map(|v| (x, v)))
+            .collect::<Option<Model>>()
+            .map_or(Solution::Unknown, Solution::Sat),
+        Partial::Unsat => Solution::Unsat,
+        Partial::Unknown => Solution::Unknown,
+    }
+}
+
+// solve the equalities with a unit coefficient for one of their unknowns and
+// substitute them away; the other equalities become pairs of inequalities.
+fn eliminate_equalities(mut constraints: Vec<WConstraint>, splits: &mut usize) -> Partial {
+    let mut solved: Vec<(Atom, WLin)> = vec![];
+    loop {
+        let pivot = constraints.iter().enumerate().find_map(|(i, c)| {
+            (c.rel == Rel::Eq)
+                .then(|| c.lin.coeffs.iter().find(|(_, a)| a.abs() == 1))
+                .flatten()
+                .map(|(x, a)| (i, *x, *a))
+        });
+        let Some((i, x, a)) = pivot else { break };
+
+        // a*x + rest = 0, so x = -a * rest (since a is 1 or -1).
+        let mut rest = constraints.swap_remove(i).lin;
+        rest.coeffs.remove(&x);
+        let Some(e) = rest.scale(-a) else {
+            return Partial::Unknown;
+        };
+        for c in constraints.iter_mut() {
+            match c.lin.substitute(x, &e) {
+                Some(lin) => c.lin = lin,
+                None => return Partial::Unknown,
+            }
+        }
+        solved.push((x, e));
+    }
+
+    let mut rest = vec![];
+    for c in constraints {
+        match c.rel {
+            Rel::Eq => {
+                // without a unit coefficient the equality is only satisfiable if
+                // the gcd of the coefficients divides the constant.
+                let g = c.lin.gcd();
+                if g == 0 && c.lin.constant != 0 || g != 0 && c.lin.constant % g != 0 {
+                    return Partial::Unsat;
+                }
+                if g != 0 {
+                    let Some(neg) = c.lin.scale(-1) else {
+                        return Partial::Unknown;
+                    };
+                    rest.push(WConstraint {
+                        lin: c.lin,
+                        rel: Rel::Le,
+                    });
+                    rest.push(WConstraint {
+                        lin: neg,
+                        rel: Rel::Le,
+                    });
+                }
+            }
+            _ => rest.push(c),
+        }
+    }
+
+    let mut model = match split_disequalities(rest, splits) {
+        Partial::Sat(model) => model,
+        other => return other,
+    };
+    for (x, e) in solved.iter().rev() {
+        match e.eval(&model) {
+            Some(v) => {
+                model.insert(*x, v);
+            }
+            None => return Partial::Unknown,
+        }
+    }
+    Partial::Sat(model)
+}
+
+// decide the inequalities first; only if their model violates a disequality do
+// we split that disequality into its two strict sides.
+fn split_disequalities(constraints: Vec<WConstraint>, splits: &mut usize) -> Partial {
+    let (nes, les): (Vec<_>, Vec<_>) = constraints.into_iter().partition(|c| c.rel == Rel::Ne);
+
+    let model = match fourier_motzkin(les.iter().map(|c| c.lin.clone()).collect()) {
+        Partial::Sat(model) => model,
+        other => return other,
+    };
+    let violated = nes.iter().position(|c| c.lin.eval(&model) == Some(0));
+    let Some(i) = violated else {
+        return Partial::Sat(model);
+    };
+
+    *splits += 1;
+    if *splits > MAX_SPLITS {
+        return Partial::Unknown;
+    }
+
+    let mut unknown = false;
+    let ne = &nes[i].lin;
+    // lin != 0 means lin + 1 <= 0 or 1 - lin <= 0.
+    let sides = [
+        ne.add(&WLin::one()),
+        ne.scale(-1).and_then(|l| l.add(&WLin::one())),
+    ];
+    for side in sides {
+        let Some(side) = side else {
+            unknown = true;
+            continue;
+        };
+        let mut sub = les.clone();
+        sub.push(WConstraint {
+            lin: side,
+            rel: Rel::Le,
+        });
+        sub.extend(
+            nes.iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, c)| c.clone()),
+        );
+        match split_disequalities(sub, splits) {
+            Partial::Sat(model) => return Partial::Sat(model),
+            Partial::Unsat => {}
+            Partial::Unknown => unknown = true,
+        }
+    }
+    if unknown {
+        Partial::Unknown
+    } else {
+        Partial::Unsat
+    }
+}
+
+impl WLin {
+    fn one() -> WLin {
+        WLin {
+            coeffs: Map::new(),
+            constant: 1,
+        }
+    }
+
+    // divide `self <= 0` by the gcd of its coefficients, rounding the constant
+    // up, which is exact over the integers.
+    fn tighten(self) -> WLin {
+        let g = self.gcd();
+        if g <= 1 {
+            return self;
+        }
+        WLin {
+            coeffs: self.coeffs.into_iter().map(|(x, a)| (x, a / g)).collect(),
+            constant: ceil_div(self.constant, g),
+        }
+    }
+}
+
+// each inequality is `lin <= 0`.
+fn fourier_motzkin(les: Vec<WLin>) -> Partial {
+    let mut current: Vec<WLin> = les.into_iter().map(WLin::tighten).collect();
+    // the inequalities mentioning each eliminated unknown, in elimination order.
+    let mut stages: Vec<(Atom, Vec<WLin>)> = vec![];
+
+    loop {
+        let mut ground = vec![];
+        current.retain(|c| {
+            if c.coeffs.is_empty() {
+                ground.push(c.constant);
+                false
+            } else {
+                true
+            }
+        });
+        if ground.iter().any(|k| *k > 0) {
+            return Partial::Unsat;
+        }
+
+        // eliminate the unknown that creates the fewest new inequalities.
+        let atoms = current
+            .iter()
+            .flat_map(|c| c.coeffs.keys())
+            .copied()
+            .collect::<Set<_>>();
+        let cost = |x: &Atom| {
+            let pos = current
+                .iter()
+                .filter(|c| c.coeffs.get(x).is_some_and(|a| *a > 0))
+                .count();
+            let neg = current
+                .iter()
+                .filter(|c| c.coeffs.get(x).is_some_and(|a| *a < 0))
+                .count();
+            pos * neg
+        };
+        let Some(x) = atoms.iter().min_by_key(|x| cost(x)).copied() else {
+            break;
+        };
+
+        let (with, without): (Vec<_>, Vec<_>) =
+            current.into_iter().partition(|c| c.coeffs.contains_key(&x));
+        let (pos, neg): (Vec<_>, Vec<_>) = with.iter().partition(|c| c.coeffs[&x] > 0);
+
+        current = without;
+        for p in &pos {
+            for n in &neg {
+                // a*x + p' <= 0 and -b*x + n' <= 0 give b*p' + a*n' <= 0.
+                let (a, b) = (p.coeffs[&x], -n.coeffs[&x]);
+                match p.scale(b).zip(n.scale(a)).and_then(|(p, n)| p.add(&n)) {
+                    Some(c) => current.push(c.tighten()),
+                    None => return Partial::Unknown,
+                }
+            }
+        }
+        if current.len() > MAX_INEQUALITIES {
+            return Partial::Unknown;
+        }
+        stages.push((x, with));
+    }
+
+    // pick a value for each unknown in reverse elimination order; every
+    // inequality of a stage only mentions unknowns that are already assigned.
+    let mut model = Map::new();
+    for (x, cs) in stages.iter().rev() {
+        let (mut lo, mut hi) = (Wide::MIN, Wide::MAX);
+        for c in cs {
+            let a = c.coeffs[x];
+            let mut rest = c.clone();
+            rest.coeffs.remove(x);
+            let Some(k) = rest.eval(&model) else {
+                return Partial::Unknown;
+            };
+            // a*x + k <= 0
+            if a > 0 {
+                hi = hi.min(floor_div(-k, a));
+            } else {
+                lo = lo.max(ceil_div(k, -a));
+            }
+        }
+        if lo > hi {
+            // the rational relaxation has a solution, but we didn't find an
+            // integer one.
+            return Partial::Unknown;
+        }
+        model.insert(*x, 0.clamp(lo, hi));
+    }
+    Partial::Sat(model)
+}
@@ -0,0 +1,386 @@
+// delta-debugging reduction of test cases.
+
+// given a program and a predicate saying whether a program is "interesting"
+// (e.g., it still makes the validator fail with a particular message), reduce()
+// repeatedly deletes parts of the program (functions, struct fields, blocks,
+// instructions, statements, ...) as long as the result stays interesting. the
+// parts are deleted in chunks that are halved whenever no chunk can be deleted,
+// as in Zeller's ddmin algorithm.
+//
+// every candidate is printed and parsed again before the predicate sees it, so
+// the reduced program is always parseable (but not necessarily valid, unless
+// the predicate requires that).
+
+// use ordered sets and maps to allow for deterministic outputs.
+use std::collections::BTreeSet as Set;
+
+use crate::front_end::{ast, parse};
+use crate::middle_end::lir::*;
+
+// SECTION: reduction
+
+pub trait TestCase: Clone {
+    // a deletable part of the test case.
+    type Part: Clone;
+
+    // the parts that can be deleted, coarsest first.
+    fn parts(&self) -> Vec<Self::Part>;
+
+    // the test case without the given parts (which are from parts()).
+    fn without(&self, parts: &[Self::Part]) -> Self;
+
+    fn to_text(&self) -> String;
+
+    fn from_text(text: &str) -> Option<Self>;
+}
+
+// reduces an interesting test case to a smaller one that is still interesting.
+pub fn reduce<T: TestCase>(case: T, mut interesting: impl FnMut(&T) -> bool) -> T {
+    let mut case = case;
+    let mut chunk = case.parts().len();
+
+    loop {
+        let parts = case.parts();
+        if parts.is_empty() {
+            return case;
+        }
+        chunk = chunk.clamp(1, parts.len());
+
+        let reduced = parts.chunks(chunk).find_map(|deleted| {
+            let candidate = T::from_text(&case.without(deleted).to_text())?;
+            interesting(&candidate).then_some(candidate)
+        });
+
+        match reduced {
+            // try the same chunk size on the remaining parts.
+            Some(candidate) => case = candidate,
+            None if chunk == 1 => return case,
+            None => chunk /= 2,
+        }
+    }
+}
+
+// SECTION: lir programs
+
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum LirPart {
+    Function(FuncId),
+    Extern(FuncId),
+    Global(VarId),
+    Struct(StructId),
+    Field(StructId, FieldId),
+    Block(FuncId, BbId),
+    // deletes one of the targets of a $branch, turning it into a $jump.
+    BranchTarget(FuncId, BbId, bool),
+    // deletes a call, replacing it with a $jump to the block it returns to.
+    Call(FuncId, BbId),
+    Inst(FuncId, BbId, usize),
+    Local(FuncId, VarId),
+}
+
+impl TestCase for Program {
+    type Part = LirPart;
+
+    fn parts(&self) -> Vec<LirPart> {
+        use LirPart::*;
+
+        let mut parts = vec![];
+        parts.extend(
+            self.functions
+                .keys()
+                .filter(|f| f.name() != "main")
+                .map(|f| Function(f.clone())),
+        );
+        parts.extend(self.externs.keys().map(|f| Extern(f.clone())));
+        parts.extend(self.globals.iter().map(|g| Global(g.clone())));
+        parts.extend(self.structs.keys().map(|id| Struct(id.clone())));
+        for (id, fields) in &self.structs {
+            parts.extend(fields.iter().map(|f| Field(id.clone(), f.clone())));
+        }
+        for (f, func) in &self.functions {
+            parts.extend(
+                func.body
+                    .keys()
+                    .filter(|bb| bb.name() != "entry")
+                    .map(|bb| Block(f.clone(), bb.clone())),
+            );
+        }
+        for (f, func) in &self.functions {
+            for (label, bb) in &func.body {
+                match bb.term {
+                    Terminal::Branch { .. } => parts.extend([
+                        BranchTarget(f.clone(), label.clone(), true),
+                        BranchTarget(f.clone(), label.clone(), false),
+                    ]),
+                    Terminal::CallDirect { .. } | Terminal::CallIndirect { .. } => {
+                        parts.push(Call(f.clone(), label.clone()))
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for (f, func) in &self.functions {
+            for (label, bb) in &func.body {
+                parts.extend((0..bb.insts.len()).map(|i| Inst(f.clone(), label.clone(), i)));
+            }
+        }
+        for (f, func) in &self.functions {
+            parts.extend(func.locals.iter().map(|v| Local(f.clone(), v.clone())));
+        }
+        parts
+    }
+
+    fn without(&self, parts: &[LirPart]) -> Self {
+        use LirPart::*;
+
+        let deleted = parts.iter().collect::<Set<_>>();
+        let mut program = self.clone();
+
+        program
+            .functions
+            .retain(|f, _| !deleted.contains(&Function(f.clone())));
+        program
+            .externs
+            .retain(|f, _| !deleted.contains(&Extern(f.clone())));
+        program
+            .globals
+            .retain(|g| !deleted.contains(&Global(g.clone())));
+        program
+            .structs
+            .retain(|id, _| !deleted.contains(&Struct(id.clone())));
+        for (id, fields) in program.structs.iter_mut() {
+            fields.retain(|f| !deleted.contains(&Field(id.clone(), f.clone())));
+        }
+
+        for (f, func) in program.functions.iter_mut() {
+            func.body
+                .retain(|bb, _| !deleted.contains(&Block(f.clone(), bb.clone())));
+            func.locals
+                .retain(|v| !deleted.contains(&Local(f.clone(), v.clone())));
+            for (label, bb) in func.body.iter_mut() {
+                let part = |part: fn(FuncId, BbId) -> LirPart| part(f.clone(), label.clone());
+                bb.term = match &bb.term {
+                    // if both targets are deleted the branch stays.
+                    Terminal::Branch { tt, ff, .. }
+                        if deleted.contains(&part(|f, bb| BranchTarget(f, bb, true))) =>
+                    {
+                        if deleted.contains(&part(|f, bb| BranchTarget(f, bb, false))) {
+                            bb.term.clone()
+                        } else {
+                            Terminal::Jump(ff.clone())
+                        }
+                    }
+                    Terminal::Branch { tt, .. }
+                        if deleted.contains(&part(|f, bb| BranchTarget(f, bb, false))) =>
+                    {
+                        Terminal::Jump(tt.clone())
+                    }
+                    Terminal::CallDirect { next_bb, .. }
+                    | Terminal::CallIndirect { next_bb, .. }
+                        if deleted.contains(&part(Call)) =>
+                    {
+                        Terminal::Jump(next_bb.clone())
+                    }
+                    term => term.clone(),
+                };
+
+                let mut idx = 0..;
+                bb.insts.retain(|_| {
+                    let i = idx.next().unwrap();
+                    !deleted.contains(&Inst(f.clone(), label.clone(), i))
+                });
+            }
+        }
+
+        program
+    }
+
+    fn to_text(&self) -> String {
+        self.to_string()
+    }
+
+    fn from_text(text: &str) -> Option<Self> {
+        text.parse().ok()
+    }
+}
+
+// SECTION: cflat programs
+
+// statements are identified by their path from the function body: the index
+// of the statement in its block, followed by the index of the nested block (0
+// for the true branch of an if or the body of a loop, 1 for the false branch)
+// and the path within that block.
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum AstPart {
+    Function(usize),
+    Extern(usize),
+    Global(usize),
+    Field(usize, usize),
+    Decl(usize, usize),
+    Stmt(usize, Vec<usize>),
+}
+
+impl TestCase for ast::Program {
+    type Part = AstPart;
+
+    fn parts(&self) -> Vec<AstPart> {
+        use AstPart::*;
+
+        let mut parts = vec![];
+        parts.extend(
+            self.functions
+                .iter()
+                .enumerate()
+                .filter(|(_, f)| f.name != "main")
+                .map(|(i, _)| Function(i)),
+        );
+        parts.extend((0..self.externs.len()).map(Extern));
+        parts.extend((0..self.globals.len()).map(Global));
+        for (i, typedef) in self.typedefs.iter().enumerate() {
+            parts.extend((0..typedef.fields.len()).map(|j| Field(i, j)));
+        }
+        for (i, func) in self.functions.iter().enumerate() {
+            let mut paths = vec![];
+            stmt_paths(&func.body.stmts, &mut vec![], &mut paths);
+            // outer statements first.
+            paths.sort_by_key(|path| path.len());
+            parts.extend(paths.into_iter().map(|path| Stmt(i, path)));
+        }
+        for (i, func) in self.functions.iter().enumerate() {
+            parts.extend((0..func.body.decls.len()).map(|j| Decl(i, j)));
+        }
+        parts
+    }
+
+    fn without(&self, parts: &[AstPart]) -> Self {
+        use AstPart::*;
+
+        let deleted = parts.iter().collect::<Set<_>>();
+        let keep = |part: AstPart| !deleted.contains(&part);
+
+        let functions = self
+            .functions
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| keep(Function(*i)))
+            .map(|(i, func)| {
+                let decls = func
+                    .body
+                    .decls
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| keep(Decl(i, *j)))
+                    .map(|(_, decl)| decl.clone())
+                    .collect();
+                let stmts = without_stmts(&func.body.stmts, &mut vec![], &|path| {
+                    !keep(Stmt(i, path.to_vec()))
+                });
+                ast::Function {
+                    body: ast::Body { decls, stmts },
+                    ..func.clone()
+                }
+            })
+            .collect();
+
+        let typedefs = self
+            .typedefs
+            .iter()
+            .enumerate()
+            .map(|(i, typedef)| ast::Typedef {
+                name: typedef.name.clone(),
+                fields: typedef
+                    .fields
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| keep(Field(i, *j)))
+                    .map(|(_, field)| field.clone())
+                    .collect(),
+            })
+            .collect();
+
+        let filter = |decls: &[ast::Decl], part: fn(usize) -> AstPart| {
+            decls
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| keep(part(*i)))
+                .map(|(_, decl)| decl.clone())
+                .collect()
+        };
+
+        ast::Program {
+            globals: filter(&self.globals, Global),
+            typedefs,
+            externs: filter(&self.externs, Extern),
+            functions,
+        }
+    }
+
+    fn to_text(&self) -> String {
+        self.pretty_print()
+    }
+
+    fn from_text(text: &str) -> Option<Self> {
+        parse(text).ok()
+    }
+}
+
+// collects the paths of all statements in stmts, where prefix is the path of
+// the enclosing block.
+fn stmt_paths(stmts: &[ast::Stmt], prefix: &mut Vec<usize>, paths: &mut Vec<Vec<usize>>) {
+    for (i, stmt) in stmts.iter().enumerate() {
+        prefix.push(i);
+        paths.push(prefix.clone());
+        for (j, block) in nested_blocks(stmt).into_iter().enumerate() {
+            prefix.push(j);
+            stmt_paths(block, prefix, paths);
+            prefix.pop();
+        }
+        prefix.pop();
+    }
+}
+
+fn without_stmts(
+    stmts: &[ast::Stmt],
+    prefix: &mut Vec<usize>,
+    deleted: &impl Fn(&[usize]) -> bool,
+) -> Vec<ast::Stmt> {
+    use ast::Stmt::*;
+
+    let mut result = vec![];
+    for (i, stmt) in stmts.iter().enumerate() {
+        prefix.push(i);
+        if !deleted(prefix) {
+            let mut nested = |j: usize, block: &[ast::Stmt]| {
+                prefix.push(j);
+                let block = without_stmts(block, prefix, deleted);
+                prefix.pop();
+                block
+            };
+            result.push(match stmt {
+                If { guard, tt, ff } => If {
+                    guard: guard.clone(),
+                    tt: nested(0, tt),
+                    ff: nested(1, ff),
+                },
+                While { guard, body } => While {
+                    guard: guard.clone(),
+                    body: nested(0, body),
+                },
+                stmt => stmt.clone(),
+            });
+        }
+        prefix.pop();
+    }
+    result
+}
+
+fn nested_blocks(stmt: &ast::Stmt) -> Vec<&[ast::Stmt]> {
+    match stmt {
+        ast::Stmt::If { tt, ff, .. } => vec![tt, ff],
+        ast::Stmt::While { body, .. } => vec![body],
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,130 @@
+// test-case reduction tests.
+
+use super::*;
+use crate::interpreter::{interpret, ExternRegistry};
+
+const PROGRAM: &str = r"struct node {
+  next:&node
+  val:int
+}
+
+g:int
+
+extern print:(int) -> _
+
+fn helper(x:int) -> int {
+let y:int
+entry:
+  y = $arith mul x 2
+  $ret y
+}
+
+fn main() -> int {
+let n:&node, p:&int, v:int, w:int, _t1:int
+entry:
+  n = $alloc 1 [_a1]
+  $call_ext print(3)
+  v = $call_dir helper(4) then bb1
+bb1:
+  _t1 = $cmp gt v 0
+  $branch _t1 bb2 bb3
+bb2:
+  p = $alloc 1 [_a2]
+  w = $arith add p 1
+  $jump bb3
+bb3:
+  $ret v
+}
+";
+
+#[test]
+fn reduces_validation_failure() {
+    let program: Program = PROGRAM.parse().unwrap();
+    // deleting the extern makes the call to print ill-typed too, so require
+    // the ill-typed addition to stay.
+    let reduced = reduce(program, |p| {
+        validate(p).is_err_and(|e| e.errors.iter().any(|e| e.contains("ill-typed")))
+            && p.to_string().contains("w = $arith add p 1")
+    });
+
+    assert!(reduced.structs.is_empty());
+    assert!(reduced.globals.is_empty());
+    assert!(reduced.externs.is_empty());
+    assert_eq!(reduced.functions.len(), 1);
+    let insts = reduced.functions[&func_id("main")]
+        .body
+        .values()
+        .flat_map(|bb| &bb.insts)
+        .map(|inst| inst.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(insts, vec!["w = $arith add p 1"]);
+}
+
+#[test]
+fn reduces_while_interesting() {
+    let program: Program = PROGRAM.parse().unwrap();
+    // the result must still be computed by helper.
+    let reduced = reduce(program, |p| {
+        validate(p).is_ok() && interpret(p.clone(), ExternRegistry::default()) == Ok(8)
+    });
+
+    assert!(reduced.structs.is_empty());
+    assert!(reduced.globals.is_empty());
+    assert!(reduced.externs.is_empty());
+    assert_eq!(reduced.functions.len(), 2);
+    let insts = reduced
+        .functions
+        .values()
+        .flat_map(|f| f.body.values())
+        .map(|bb| bb.insts.len())
+        .sum::<usize>();
+    assert_eq!(insts, 1);
+}
+
+#[test]
+fn deletes_nested_statements() {
+    use crate::front_end::ast::*;
+
+    let assign = |name: &str, n: i32| Stmt::Assign {
+        lhs: Lval::Id(name.to_string()),
+        rhs: Rhs::Exp(Exp::Num(n)),
+    };
+    let program = Program {
+        globals: vec![],
+        typedefs: vec![],
+        externs: vec![],
+        functions: vec![Function {
+            name: "main".to_string(),
+            params: vec![],
+            rettyp: Some(int_ty()),
+            body: Body {
+                decls: vec![],
+                stmts: vec![
+                    Stmt::While {
+                        guard: Exp::Num(1),
+                        body: vec![assign("x", 1), assign("y", 2)],
+                    },
+                    Stmt::Return(Some(Exp::Num(0))),
+                ],
+            },
+        }],
+    };
+
+    assert_eq!(
+        program.parts(),
+        vec![
+            AstPart::Stmt(0, vec![0]),
+            AstPart::Stmt(0, vec![1]),
+            AstPart::Stmt(0, vec![0, 0, 0]),
+            AstPart::Stmt(0, vec![0, 0, 1]),
+        ]
+    );
+    let reduced = program.without(&[AstPart::Stmt(0, vec![0, 0, 0])]);
+    assert_eq!(
+        reduced.functions[0].body.stmts[0],
+        Stmt::While {
+            guard: Exp::Num(1),
+            body: vec![assign("y", 2)],
+        }
+    );
+}